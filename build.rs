@@ -1,6 +1,8 @@
 fn main() {
     tonic_build::configure()
         .out_dir("src")
+        // ballot 按 (number, proposer_id) 比较，number 相同时由 proposer_id 区分
+        .type_attribute("paxos.RoundNum", "#[derive(Eq, Hash, PartialOrd, Ord)]")
         .compile(&["proto/paxos.proto"], &["proto"])
         .expect("Failed to compile proto")
 }
@@ -0,0 +1,186 @@
+use crate::paxos::admin_server::Admin;
+use crate::paxos::{
+    Acceptor, ExportReply, ExportRequest, ForgetReply, ForgetRequest, GetInstanceRequest, Instance,
+    ListKeysReply, ListKeysRequest, StorageSizeReply, StorageSizeRequest,
+};
+use crate::storage::Journal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+/// 查看和维护 Acceptor 状态的服务，与 [`crate::PaxosService`] 共享存储
+#[derive(Debug, Clone)]
+pub struct AdminService {
+    storage: Arc<Mutex<HashMap<String, Acceptor>>>,
+    journal: Option<Arc<Journal>>,
+}
+
+impl AdminService {
+    pub(crate) fn new(
+        storage: Arc<Mutex<HashMap<String, Acceptor>>>,
+        journal: Option<Arc<Journal>>,
+    ) -> Self {
+        AdminService { storage, journal }
+    }
+
+    /// 按 key 排序返回以 prefix 开头的实例
+    fn instances(&self, prefix: &str) -> Vec<Instance> {
+        let storage = self.storage.lock().unwrap();
+        let mut instances: Vec<_> = storage
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, acc)| Instance {
+                key: key.clone(),
+                acceptor: Some(acc.clone()),
+                epaxos: None,
+            })
+            .collect();
+        instances.sort_by(|a, b| a.key.cmp(&b.key));
+        instances
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_keys(
+        &self,
+        request: Request<ListKeysRequest>,
+    ) -> Result<Response<ListKeysReply>, Status> {
+        let keys = self
+            .instances(&request.get_ref().prefix)
+            .into_iter()
+            .map(|inst| inst.key)
+            .collect();
+        Ok(Response::new(ListKeysReply { keys }))
+    }
+
+    async fn get_instance(
+        &self,
+        request: Request<GetInstanceRequest>,
+    ) -> Result<Response<Instance>, Status> {
+        let key = &request.get_ref().key;
+        let storage = self.storage.lock().unwrap();
+        match storage.get(key) {
+            Some(acc) => Ok(Response::new(Instance {
+                key: key.clone(),
+                acceptor: Some(acc.clone()),
+                epaxos: None,
+            })),
+            None => Err(Status::not_found(format!("no instance for key {}", key))),
+        }
+    }
+
+    async fn storage_size(
+        &self,
+        _request: Request<StorageSizeRequest>,
+    ) -> Result<Response<StorageSizeReply>, Status> {
+        let instances = self.storage.lock().unwrap().len() as u64;
+        let journal_bytes = match &self.journal {
+            Some(journal) => journal
+                .size()
+                .map_err(|e| Status::internal(e.to_string()))?,
+            None => 0,
+        };
+        Ok(Response::new(StorageSizeReply {
+            instances,
+            journal_bytes,
+        }))
+    }
+
+    async fn forget(
+        &self,
+        request: Request<ForgetRequest>,
+    ) -> Result<Response<ForgetReply>, Status> {
+        let key = &request.get_ref().key;
+        let mut storage = self.storage.lock().unwrap();
+        let existed = storage.contains_key(key);
+        if existed {
+            if let Some(journal) = &self.journal {
+                journal
+                    .remove(key)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            }
+            storage.remove(key);
+            warn!(key = %key, "instance forgotten by admin");
+        }
+        Ok(Response::new(ForgetReply { existed }))
+    }
+
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<ExportReply>, Status> {
+        let instances = self.instances(&request.get_ref().prefix);
+        Ok(Response::new(ExportReply { instances }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paxos::paxos_server::Paxos;
+    use crate::paxos::{PaxosInstanceId, Proposer, RoundNum};
+    use crate::PaxosService;
+    use tokio_test::block_on;
+
+    fn prepare(service: &PaxosService, key: &str) {
+        let proposer = Proposer {
+            id: Some(PaxosInstanceId {
+                key: key.to_string(),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 0,
+            }),
+            value: None,
+            epoch: 0,
+        };
+        assert!(block_on(service.prepare(Request::new(proposer))).is_ok());
+    }
+
+    #[test]
+    fn test_admin() {
+        let service = PaxosService::default();
+        let admin = service.admin();
+        prepare(&service, "sh");
+        prepare(&service, "sw");
+        prepare(&service, "bj");
+
+        let r = block_on(admin.list_keys(Request::new(ListKeysRequest {
+            prefix: "s".to_string(),
+        })));
+        assert_eq!(r.unwrap().into_inner().keys, vec!["sh", "sw"]);
+
+        let r = block_on(admin.get_instance(Request::new(GetInstanceRequest {
+            key: "sh".to_string(),
+        })));
+        let inst = r.unwrap().into_inner();
+        assert_eq!(inst.acceptor.unwrap().last_round.unwrap().number, 1);
+
+        let r = block_on(admin.storage_size(Request::new(StorageSizeRequest {})));
+        assert_eq!(r.unwrap().into_inner().instances, 3);
+
+        let r = block_on(admin.forget(Request::new(ForgetRequest {
+            key: "sh".to_string(),
+        })));
+        assert!(r.unwrap().into_inner().existed);
+        let r = block_on(admin.get_instance(Request::new(GetInstanceRequest {
+            key: "sh".to_string(),
+        })));
+        assert_eq!(r.unwrap_err().code(), tonic::Code::NotFound);
+
+        let r = block_on(admin.export(Request::new(ExportRequest {
+            prefix: String::new(),
+        })));
+        let keys: Vec<_> = r
+            .unwrap()
+            .into_inner()
+            .instances
+            .into_iter()
+            .map(|inst| inst.key)
+            .collect();
+        assert_eq!(keys, vec!["bj", "sw"]);
+    }
+}
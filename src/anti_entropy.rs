@@ -0,0 +1,420 @@
+use crate::auth::bearer_interceptor;
+use crate::paxos::peer_client::PeerClient;
+use crate::paxos::peer_server::Peer;
+use crate::paxos::{
+    Acceptor, DigestsReply, DigestsRequest, FenceReply, FenceRequest, FetchReply, FetchRequest,
+    GetInstanceRequest, Instance, InstanceDigest, ListKeysReply, ListKeysRequest, RangesReply,
+    RangesRequest,
+};
+use crate::server::{internal, PaxosService};
+use crate::tls;
+use crate::witness;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::transport::ClientTlsConfig;
+use tonic::{Request, Response, Status};
+use tracing::{debug, info, warn};
+
+/// 每轮 anti-entropy 把 key 分成的区间数
+const RANGES: u32 = 64;
+/// 区间数的上限，避免请求方让本节点分配过多内存
+const MAX_RANGES: u32 = 1 << 16;
+
+fn range_of(key: &str, ranges: u32) -> u32 {
+    (witness::fnv1a(key.as_bytes()) % ranges as u64) as u32
+}
+
+/// 把实例按 key 分到 ranges 个区间，每个区间内已接受的值异或成一个 hash
+///
+/// 只计入 key、值的 round 和摘要，不计入 last_round：节点之间承诺不同不需要修复。
+pub(crate) fn range_hashes(storage: &HashMap<String, Acceptor>, ranges: u32) -> Vec<u64> {
+    let mut hashes = vec![0; ranges as usize];
+    for (key, acc) in storage {
+        let value = match &acc.value {
+            Some(value) => value,
+            None => continue,
+        };
+        let round = acc.round.clone().unwrap_or_default();
+        let mut buf = key.as_bytes().to_vec();
+        buf.extend_from_slice(&round.number.to_le_bytes());
+        buf.extend_from_slice(&round.proposer_id.to_le_bytes());
+        buf.extend_from_slice(&witness::digest(value).to_le_bytes());
+        hashes[range_of(key, ranges) as usize] ^= witness::fnv1a(&buf);
+    }
+    hashes
+}
+
+/// 供其他节点 anti-entropy 和成员变更的服务，与 [`crate::PaxosService`] 共享状态
+///
+/// 只应以 [`crate::node_interceptor`] 提供，Proposer 的身份不能调用。
+#[derive(Debug, Clone)]
+pub struct PeerService {
+    service: PaxosService,
+}
+
+impl PeerService {
+    pub(crate) fn new(service: PaxosService) -> Self {
+        PeerService { service }
+    }
+}
+
+#[tonic::async_trait]
+impl Peer for PeerService {
+    async fn ranges(
+        &self,
+        request: Request<RangesRequest>,
+    ) -> Result<Response<RangesReply>, Status> {
+        let ranges = request.get_ref().ranges;
+        if ranges == 0 || ranges > MAX_RANGES {
+            return Err(Status::invalid_argument(format!(
+                "ranges must be in 1..={}, got {}",
+                MAX_RANGES, ranges
+            )));
+        }
+        let storage = self.service.storage.lock().unwrap();
+        let hashes = range_hashes(&storage, ranges);
+        Ok(Response::new(RangesReply { hashes }))
+    }
+
+    async fn digests(
+        &self,
+        request: Request<DigestsRequest>,
+    ) -> Result<Response<DigestsReply>, Status> {
+        let request = request.get_ref();
+        let prefix = &request.prefix;
+        let in_range = |key: &str| {
+            request.ranges == 0 || request.indexes.contains(&range_of(key, request.ranges))
+        };
+        let storage = self.service.storage.lock().unwrap();
+        let mut instances: Vec<_> = storage
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix.as_str()) && in_range(key))
+            .map(|(key, acc)| InstanceDigest {
+                key: key.clone(),
+                round: acc.round.clone(),
+                last_round: acc.last_round.clone(),
+                digest: acc.value.as_ref().map(witness::digest).unwrap_or(0),
+            })
+            .collect();
+        instances.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(Response::new(DigestsReply { instances }))
+    }
+
+    async fn fetch(
+        &self,
+        request: Request<GetInstanceRequest>,
+    ) -> Result<Response<Instance>, Status> {
+        let key = &request.get_ref().key;
+        let storage = self.service.storage.lock().unwrap();
+        match storage.get(key) {
+            Some(acc) => Ok(Response::new(Instance {
+                key: key.clone(),
+                acceptor: Some(acc.clone()),
+                epaxos: None,
+            })),
+            None => Err(Status::not_found(format!("no instance for key {:?}", key))),
+        }
+    }
+
+    async fn fetch_many(
+        &self,
+        request: Request<FetchRequest>,
+    ) -> Result<Response<FetchReply>, Status> {
+        let storage = self.service.storage.lock().unwrap();
+        let instances = request
+            .get_ref()
+            .keys
+            .iter()
+            .filter_map(|key| {
+                storage.get(key).map(|acc| Instance {
+                    key: key.clone(),
+                    acceptor: Some(acc.clone()),
+                    epaxos: None,
+                })
+            })
+            .collect();
+        Ok(Response::new(FetchReply { instances }))
+    }
+
+    async fn fence(&self, request: Request<FenceRequest>) -> Result<Response<FenceReply>, Status> {
+        let epoch = self
+            .service
+            .fence_epoch(request.get_ref().epoch)
+            .map_err(internal)?;
+        Ok(Response::new(FenceReply { epoch }))
+    }
+
+    async fn list_keys(
+        &self,
+        request: Request<ListKeysRequest>,
+    ) -> Result<Response<ListKeysReply>, Status> {
+        let prefix = &request.get_ref().prefix;
+        let mut keys: Vec<String> = self
+            .service
+            .storage
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix.as_str()))
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(Response::new(ListKeysReply { keys }))
+    }
+}
+
+/// 与一个节点比较实例摘要，拉取本节点缺少的已接受值，返回修复的实例数
+///
+/// 先比较各区间的 hash，只对不一致的区间列出摘要，再一次拉取所有需要的实例，
+/// 两个节点一致时只交换 [`RANGES`] 个 hash。
+/// token 为本节点的 bearer token，须在对方的 `auth.nodes` 中。
+pub async fn sync_with(
+    service: &PaxosService,
+    peer: &str,
+    tls: Option<&ClientTlsConfig>,
+    token: Option<&str>,
+) -> Result<usize> {
+    let channel = tls::endpoint(peer, tls)?.connect().await?;
+    let mut client =
+        PeerClient::with_interceptor(channel, bearer_interceptor(token.map(String::from)));
+    let remote = client
+        .ranges(RangesRequest { ranges: RANGES })
+        .await?
+        .into_inner()
+        .hashes;
+    let local = range_hashes(&service.storage.lock().unwrap(), RANGES);
+    let indexes: Vec<u32> = (0..RANGES)
+        .filter(|&i| remote.get(i as usize) != local.get(i as usize))
+        .collect();
+    if indexes.is_empty() {
+        return Ok(0);
+    }
+    let digests = client
+        .digests(DigestsRequest {
+            prefix: String::new(),
+            ranges: RANGES,
+            indexes,
+        })
+        .await?
+        .into_inner()
+        .instances;
+
+    // 先用摘要筛选，只拉取 round 更大的实例
+    let candidates: Vec<String> = {
+        let storage = service.storage.lock().unwrap();
+        digests
+            .into_iter()
+            .filter(|d| d.digest != 0)
+            .filter(|d| {
+                let round = d.round.clone().unwrap_or_default();
+                match storage.get(&d.key) {
+                    Some(local) => {
+                        let value_round = local.round.clone().unwrap_or_default();
+                        let last_round = local.last_round.clone().unwrap_or_default();
+                        round > value_round && round >= last_round
+                    }
+                    None => true,
+                }
+            })
+            .map(|d| d.key)
+            .collect()
+    };
+
+    if candidates.is_empty() {
+        return Ok(0);
+    }
+    let instances = client
+        .fetch_many(FetchRequest { keys: candidates })
+        .await?
+        .into_inner()
+        .instances;
+    let mut repaired = 0;
+    for instance in instances {
+        if let Some(acc) = instance.acceptor {
+            if service.repair(&instance.key, &acc)? {
+                repaired += 1;
+            }
+        }
+    }
+    Ok(repaired)
+}
+
+/// 后台 anti-entropy：每隔 interval 依次与每个节点比较，拉取本节点缺少的已接受值
+///
+/// 拉取的值不一定已被选定，采纳它相当于重新投递一次 accept，不会通知 Learner。
+/// 没有客户端请求时，宕机后恢复的节点也能逐渐追上其他节点。默认不开启，
+/// 由 `timeouts.anti_entropy_ms` 开启。
+/// 对方开启认证时，本节点证书的 CN 或 token 须在对方的 `auth.nodes` 中。
+pub async fn anti_entropy(
+    service: PaxosService,
+    peers: Vec<String>,
+    tls: Option<ClientTlsConfig>,
+    token: Option<String>,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        for peer in &peers {
+            match sync_with(&service, peer, tls.as_ref(), token.as_deref()).await {
+                Ok(0) => debug!(%peer, "anti-entropy found nothing to repair"),
+                Ok(n) => info!(%peer, repaired = n, "anti-entropy repaired instances"),
+                Err(e) => warn!(%peer, error = %e, "anti-entropy failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{spawn, value};
+    use crate::*;
+    use scopeguard::defer;
+    use tonic::transport::Server;
+
+    fn start_node(address: String, auth: Option<AuthConfig>) -> (triggered::Trigger, PaxosService) {
+        let service = PaxosService::default();
+        let svc = service.clone();
+        let trigger = spawn(address, move |addr, signal| {
+            let peer = PeerServer::with_interceptor(svc.peer(), node_interceptor(auth.clone()));
+            let paxos = PaxosServer::with_interceptor(svc, interceptor(auth));
+            Server::builder()
+                .add_service(peer)
+                .add_service(paxos)
+                .serve_with_shutdown(addr, signal)
+        });
+        (trigger, service)
+    }
+
+    #[test]
+    fn test_range_hashes() {
+        let accepted = |v, last_round| Acceptor {
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 1,
+            }),
+            last_round: Some(RoundNum {
+                number: last_round,
+                proposer_id: 1,
+            }),
+            value: Some(value(v)),
+        };
+        let mut a = HashMap::new();
+        a.insert("a".to_string(), accepted(1, 1));
+        a.insert("b".to_string(), accepted(2, 1));
+        let mut b = a.clone();
+        // 只有承诺不同时区间一致
+        b.insert("a".to_string(), accepted(1, 5));
+        b.insert("c".to_string(), Acceptor::default());
+        assert_eq!(range_hashes(&a, RANGES), range_hashes(&b, RANGES));
+
+        // 值不同时只有所在的区间不一致
+        b.insert("b".to_string(), accepted(3, 1));
+        let (ha, hb) = (range_hashes(&a, RANGES), range_hashes(&b, RANGES));
+        let differing: Vec<_> = (0..RANGES as usize).filter(|&i| ha[i] != hb[i]).collect();
+        assert_eq!(differing, vec![range_of("b", RANGES) as usize]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_anti_entropy() {
+        let servers: Vec<_> = (11180..11183).map(|p| format!("[::1]:{}", p)).collect();
+        let nodes: Vec<_> = servers
+            .iter()
+            .map(|s| start_node(s.clone(), None))
+            .collect();
+        defer! {
+            for (t, _) in &nodes {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // 第三个节点没有收到这些请求
+        let mut client = Client::new(servers[..2].to_vec(), 38);
+        assert!(client.connect().await.is_ok());
+        for (key, v) in [("a", 1), ("b", 2)] {
+            let chosen = client.run_propose(key.to_string(), Some(value(v))).await;
+            assert_eq!(chosen, Ok(Some(value(v))));
+        }
+        // 第三个节点已经承诺了更大的 round，不能再采纳 b 的值
+        let promised = Acceptor {
+            round: Some(RoundNum::default()),
+            last_round: Some(RoundNum {
+                number: 100,
+                proposer_id: 1,
+            }),
+            value: None,
+        };
+        let lagging = &nodes[2].1;
+        lagging
+            .storage
+            .lock()
+            .unwrap()
+            .insert("b".to_string(), promised.clone());
+
+        assert_eq!(
+            sync_with(lagging, &servers[0], None, None).await.unwrap(),
+            1
+        );
+        let storage = lagging.storage.lock().unwrap().clone();
+        assert_eq!(storage["a"].value, Some(value(1)));
+        assert_eq!(storage["b"], promised);
+        // 已经一致时不再修复
+        assert_eq!(
+            sync_with(lagging, &servers[1], None, None).await.unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_anti_entropy_with_token_auth() {
+        let mut auth = AuthConfig::default();
+        auth.tokens.insert("s3cr3t".to_string(), 39);
+        auth.nodes.tokens.push("n0de".to_string());
+        let servers: Vec<_> = (11200..11203).map(|p| format!("[::1]:{}", p)).collect();
+        let nodes: Vec<_> = servers
+            .iter()
+            .map(|s| start_node(s.clone(), Some(auth.clone())))
+            .collect();
+        defer! {
+            for (t, _) in &nodes {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut client = Client::new(servers[..2].to_vec(), 39);
+        client.set_token("s3cr3t".to_string());
+        assert!(client.connect().await.is_ok());
+        let chosen = client.run_propose("a".to_string(), Some(value(1))).await;
+        assert_eq!(chosen, Ok(Some(value(1))));
+
+        // 节点之间只认节点的身份，Proposer 的 token 和匿名请求都不行
+        let lagging = &nodes[2].1;
+        assert!(sync_with(lagging, &servers[0], None, None).await.is_err());
+        assert!(sync_with(lagging, &servers[0], None, Some("s3cr3t"))
+            .await
+            .is_err());
+        assert_eq!(
+            sync_with(lagging, &servers[0], None, Some("n0de"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(lagging.storage.lock().unwrap()["a"].value, Some(value(1)));
+
+        // Proposer 的身份不能 fence，也不能列出实例
+        let channel = tls::endpoint(&servers[0], None)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut peer =
+            PeerClient::with_interceptor(channel, bearer_interceptor(Some("s3cr3t".to_string())));
+        let fence = peer.fence(FenceRequest { epoch: i64::MAX }).await;
+        assert_eq!(fence.unwrap_err().code(), tonic::Code::PermissionDenied);
+        let keys = peer.list_keys(ListKeysRequest::default()).await;
+        assert_eq!(keys.unwrap_err().code(), tonic::Code::PermissionDenied);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::paxos::Proposer;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Request, Status};
+use tracing::warn;
+
+/// 拦截器认证通过后，写入调用方 proposer_id 的 header
+///
+/// 客户端自带的同名 header 会被拦截器覆盖，服务端只信任拦截器写入的值。
+pub const PROPOSER_ID_HEADER: &str = "x-rpaxos-proposer-id";
+
+/// [`learner_interceptor`] 认证调用方为 `auth.nodes` 中的节点后写入的 header
+const NODE_HEADER: &str = "x-rpaxos-node";
+
+/// 调用方身份到 proposer_id 的映射，以及管理员和其他节点的身份
+///
+/// ```toml
+/// [auth.subjects]
+/// "proposer-1" = 1
+///
+/// [auth.tokens]
+/// "s3cr3t" = 2
+///
+/// [auth.admins]
+/// tokens = ["adm1n"]
+///
+/// [auth.nodes]
+/// subjects = ["node-1", "node-2"]
+/// tokens = ["n0de"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// 客户端证书的 CN，需要开启双向 TLS
+    pub subjects: HashMap<String, i64>,
+    /// `authorization: Bearer <token>` 中的 token
+    pub tokens: HashMap<String, i64>,
+    /// 可以调用 Admin 服务的管理员，与 Proposer 的身份分开
+    pub admins: Principals,
+    /// 集群中的其他节点，用于 anti-entropy 和 Learner
+    pub nodes: Principals,
+}
+
+/// 不对应 proposer_id 的一组调用方
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Principals {
+    /// 客户端证书的 CN
+    pub subjects: Vec<String>,
+    /// bearer token
+    pub tokens: Vec<String>,
+}
+
+impl Principals {
+    fn contains<T>(&self, request: &Request<T>) -> bool {
+        subject(request).is_some_and(|cn| self.subjects.contains(&cn))
+            || token(request).is_some_and(|t| self.tokens.iter().any(|s| s == t))
+    }
+}
+
+impl AuthConfig {
+    /// 认证调用方，先看客户端证书，再看 bearer token
+    fn authenticate<T>(&self, request: &Request<T>) -> Option<i64> {
+        if let Some(id) = subject(request).and_then(|cn| self.subjects.get(&cn)) {
+            return Some(*id);
+        }
+        token(request).and_then(|t| self.tokens.get(t)).copied()
+    }
+}
+
+/// 客户端证书的 CN
+fn subject<T>(request: &Request<T>) -> Option<String> {
+    let certs = request.peer_certs()?;
+    certs.first().and_then(|cert| common_name(cert.get_ref()))
+}
+
+/// `authorization: Bearer <token>` 中的 token
+fn token<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// 服务端拦截器，把认证得到的 proposer_id 写入 [`PROPOSER_ID_HEADER`]
+///
+/// config 为 None 时不认证，只去掉客户端自带的 [`PROPOSER_ID_HEADER`]。
+#[allow(clippy::result_large_err)] // 签名由 tonic 的拦截器决定
+pub fn interceptor(
+    config: Option<AuthConfig>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    let config = config.map(Arc::new);
+    move |mut request: Request<()>| {
+        request.metadata_mut().remove(PROPOSER_ID_HEADER);
+        match &config {
+            Some(config) => authenticate(config, request),
+            None => Ok(request),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn authenticate(config: &AuthConfig, mut request: Request<()>) -> Result<Request<()>, Status> {
+    let id = match config.authenticate(&request) {
+        Some(id) => id,
+        None => {
+            warn!(remote = ?request.remote_addr(), "unauthenticated request");
+            return Err(Status::unauthenticated("unknown caller"));
+        }
+    };
+    request
+        .metadata_mut()
+        .insert(PROPOSER_ID_HEADER, MetadataValue::from(id));
+    Ok(request)
+}
+
+/// Learner 服务的拦截器：`auth.nodes` 中的节点可以推送被选定的值，Proposer 的身份只能读取
+///
+/// config 为 None 时不认证，只去掉客户端自带的身份 header。
+#[allow(clippy::result_large_err)]
+pub fn learner_interceptor(
+    config: Option<AuthConfig>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    let config = config.map(Arc::new);
+    move |mut request: Request<()>| {
+        request.metadata_mut().remove(PROPOSER_ID_HEADER);
+        request.metadata_mut().remove(NODE_HEADER);
+        match &config {
+            Some(config) if config.nodes.contains(&request) => {
+                request
+                    .metadata_mut()
+                    .insert(NODE_HEADER, MetadataValue::from_static("1"));
+                Ok(request)
+            }
+            Some(config) => authenticate(config, request),
+            None => Ok(request),
+        }
+    }
+}
+
+/// Admin 服务的拦截器，开启认证时只允许 `auth.admins` 中的调用方
+///
+/// Proposer 的身份不能调用 Admin 服务；config 为 None 时不认证。
+#[allow(clippy::result_large_err)]
+pub fn admin_interceptor(
+    config: Option<AuthConfig>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    require(config, |config| &config.admins, "admin")
+}
+
+/// 节点之间的服务的拦截器，开启认证时只允许 `auth.nodes` 中的调用方
+#[allow(clippy::result_large_err)]
+pub fn node_interceptor(
+    config: Option<AuthConfig>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    require(config, |config| &config.nodes, "node")
+}
+
+#[allow(clippy::result_large_err)]
+fn require(
+    config: Option<AuthConfig>,
+    principals: fn(&AuthConfig) -> &Principals,
+    role: &'static str,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    let config = config.map(Arc::new);
+    move |request: Request<()>| {
+        if let Some(config) = &config {
+            if !principals(config).contains(&request) {
+                warn!(remote = ?request.remote_addr(), role, "request without required credential");
+                return Err(Status::permission_denied(format!(
+                    "{} credential required",
+                    role
+                )));
+            }
+        }
+        Ok(request)
+    }
+}
+
+fn common_name(der: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(|s| s.to_string())
+}
+
+/// 拦截器认证得到的 proposer_id，未开启认证时为 None
+fn caller(metadata: &MetadataMap) -> Option<i64> {
+    metadata
+        .get(PROPOSER_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// 请求中 ballot 的 proposer_id 必须是调用方自己的，否则返回拒绝的原因
+pub(crate) fn deny<'a>(
+    metadata: &MetadataMap,
+    proposers: impl IntoIterator<Item = &'a Proposer>,
+) -> Option<Status> {
+    let caller = caller(metadata)?;
+    let proposer_id = proposers
+        .into_iter()
+        .map(|p| p.round.as_ref().map(|r| r.proposer_id).unwrap_or_default())
+        .find(|id| *id != caller)?;
+    warn!(caller, proposer_id, "ballot proposer_id mismatch");
+    Some(Status::permission_denied(format!(
+        "caller {} may not use proposer_id {}",
+        caller, proposer_id
+    )))
+}
+
+/// EPaxos 请求必须以调用方自己的身份发出：ballot 0 时 replica 是实例的发起者，
+/// 恢复实例时是 ballot 的 proposer_id，否则返回拒绝的原因
+pub(crate) fn deny_replica(metadata: &MetadataMap, replica: i64) -> Option<Status> {
+    let caller = caller(metadata)?;
+    if caller == replica {
+        return None;
+    }
+    warn!(caller, replica, "epaxos replica mismatch");
+    Some(Status::permission_denied(format!(
+        "caller {} may not use replica {}",
+        caller, replica
+    )))
+}
+
+/// 开启认证时只允许节点推送被选定的值，否则返回拒绝的原因
+pub(crate) fn deny_non_node(metadata: &MetadataMap) -> Option<Status> {
+    if metadata.contains_key(NODE_HEADER) {
+        return None;
+    }
+    let caller = caller(metadata)?;
+    warn!(caller, "learn from non-node caller");
+    Some(Status::permission_denied(format!(
+        "caller {} may not push learned values",
+        caller
+    )))
+}
+
+/// 在请求中带上 bearer token
+pub fn bearer<T>(request: &mut Request<T>, token: &str) {
+    if let Ok(value) = MetadataValue::from_str(&format!("Bearer {}", token)) {
+        request.metadata_mut().insert("authorization", value);
+    }
+}
+
+/// 客户端拦截器，在每个请求中带上 bearer token，用于 [`crate::AdminClient`] 等
+#[allow(clippy::result_large_err)]
+pub fn bearer_interceptor(
+    token: Option<String>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static {
+    move |mut request: Request<()>| {
+        if let Some(token) = &token {
+            bearer(&mut request, token);
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paxos::RoundNum;
+
+    fn proposer(proposer_id: i64) -> Request<Proposer> {
+        Request::new(Proposer {
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id,
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut config = AuthConfig::default();
+        config.tokens.insert("s3cr3t".to_string(), 2);
+        let intercept = interceptor(Some(config));
+
+        assert_eq!(
+            intercept(Request::new(())).unwrap_err().code(),
+            tonic::Code::Unauthenticated
+        );
+        let mut request = Request::new(());
+        bearer(&mut request, "wrong");
+        assert!(intercept(request).is_err());
+
+        // 客户端自己伪造的 header 会被覆盖
+        let mut request = Request::new(());
+        bearer(&mut request, "s3cr3t");
+        request
+            .metadata_mut()
+            .insert(PROPOSER_ID_HEADER, MetadataValue::from(1i64));
+        let request = intercept(request).unwrap();
+        assert_eq!(caller(request.metadata()), Some(2));
+
+        let mut p = proposer(2);
+        *p.metadata_mut() = request.metadata().clone();
+        assert!(deny(p.metadata(), Some(p.get_ref())).is_none());
+        let mut p = proposer(1);
+        *p.metadata_mut() = request.metadata().clone();
+        assert_eq!(
+            deny(p.metadata(), Some(p.get_ref())).unwrap().code(),
+            tonic::Code::PermissionDenied
+        );
+        // 未开启认证时不检查，也不信任客户端自带的 header
+        let p = proposer(1);
+        assert!(deny(p.metadata(), Some(p.get_ref())).is_none());
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert(PROPOSER_ID_HEADER, MetadataValue::from(1i64));
+        assert_eq!(caller(interceptor(None)(request).unwrap().metadata()), None);
+    }
+
+    #[test]
+    fn test_admin_credential() {
+        let mut config = AuthConfig::default();
+        config.tokens.insert("s3cr3t".to_string(), 2);
+        config.admins.tokens.push("adm1n".to_string());
+        let intercept = admin_interceptor(Some(config));
+
+        assert!(intercept(Request::new(())).is_err());
+        // Proposer 的 token 不能调用 Admin 服务
+        let mut request = Request::new(());
+        bearer(&mut request, "s3cr3t");
+        assert_eq!(
+            intercept(request).unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+        let mut request = Request::new(());
+        bearer(&mut request, "adm1n");
+        assert!(intercept(request).is_ok());
+        assert!(admin_interceptor(None)(Request::new(())).is_ok());
+    }
+
+    #[test]
+    fn test_learner_credential() {
+        let mut config = AuthConfig::default();
+        config.tokens.insert("s3cr3t".to_string(), 2);
+        config.nodes.tokens.push("n0de".to_string());
+        let intercept = learner_interceptor(Some(config));
+
+        // Proposer 可以读取，但不能推送；伪造的节点 header 会被去掉
+        let mut request = Request::new(());
+        bearer(&mut request, "s3cr3t");
+        request
+            .metadata_mut()
+            .insert(NODE_HEADER, MetadataValue::from_static("1"));
+        let request = intercept(request).unwrap();
+        assert_eq!(
+            deny_non_node(request.metadata()).unwrap().code(),
+            tonic::Code::PermissionDenied
+        );
+        let mut request = Request::new(());
+        bearer(&mut request, "n0de");
+        assert!(deny_non_node(intercept(request).unwrap().metadata()).is_none());
+        assert!(intercept(Request::new(())).is_err());
+
+        let request = learner_interceptor(None)(Request::new(())).unwrap();
+        assert!(deny_non_node(request.metadata()).is_none());
+    }
+}
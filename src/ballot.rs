@@ -0,0 +1,170 @@
+use crate::storage;
+use crate::RoundNum;
+use anyhow::{Error, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// HLC ballot 中逻辑计数占用的低位数
+const LOGICAL_BITS: u32 = 16;
+
+/// ballot 编号的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BallotScheme {
+    /// 从 0 开始递增的计数
+    #[default]
+    Counter,
+    /// 混合逻辑时钟：高位为毫秒时间戳，低 16 位为逻辑计数。
+    /// 重启或分区恢复后直接跳到当前时间附近，不需要多次被拒绝才追上
+    Hlc,
+}
+
+/// 为一个 Proposer 分配单调递增的 ballot
+///
+/// 已用过或看到的最大 round 记录在本地文件中，重启后从文件继续，不会重用旧的 ballot；
+/// 被拒绝时看到的更大 round 通过 [`BallotAllocator::observe`] 告知，下一个 ballot 会越过它。
+/// 未指定文件时只在内存中记录。
+#[derive(Debug, Default)]
+pub struct BallotAllocator {
+    path: Option<PathBuf>,
+    proposer_id: i64,
+    scheme: BallotScheme,
+    /// 已分配或已看到的最大 round
+    highest: Mutex<i64>,
+}
+
+impl BallotAllocator {
+    /// 只在内存中记录的分配器
+    pub fn new(proposer_id: i64) -> Self {
+        BallotAllocator {
+            proposer_id,
+            ..Default::default()
+        }
+    }
+
+    /// 从文件恢复已用过的最大 round，文件不存在时从 0 开始
+    pub fn open(path: &Path, proposer_id: i64) -> Result<Self> {
+        let highest = match fs::read_to_string(path) {
+            Ok(s) => s.trim().parse::<i64>().map_err(|e| {
+                Error::msg(format!("invalid ballot file {}: {}", path.display(), e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(BallotAllocator {
+            path: Some(path.to_path_buf()),
+            proposer_id,
+            highest: Mutex::new(highest),
+            ..Default::default()
+        })
+    }
+
+    /// 使用 scheme 分配 ballot，保留已记录的 round 和文件
+    pub fn with_scheme(&self, scheme: BallotScheme) -> Self {
+        BallotAllocator {
+            path: self.path.clone(),
+            proposer_id: self.proposer_id,
+            scheme,
+            highest: Mutex::new(*self.highest.lock().unwrap()),
+        }
+    }
+
+    /// 分配一个比之前所有 ballot 都大的 ballot，写入文件后才返回
+    pub fn next(&self) -> Result<RoundNum> {
+        let mut highest = self.highest.lock().unwrap();
+        let number = match self.scheme {
+            BallotScheme::Counter => *highest + 1,
+            BallotScheme::Hlc => std::cmp::max(*highest + 1, physical() << LOGICAL_BITS),
+        };
+        if let Some(path) = &self.path {
+            persist(path, number)?;
+        }
+        *highest = number;
+        Ok(RoundNum {
+            number,
+            proposer_id: self.proposer_id,
+        })
+    }
+
+    /// 记录在应答中看到的 round，之后分配的 ballot 会大于它，重启后也是如此
+    ///
+    /// 写文件失败时只记录日志，下一次 [`BallotAllocator::next`] 仍会写入更大的 round。
+    pub fn observe(&self, round: &RoundNum) {
+        let mut highest = self.highest.lock().unwrap();
+        if round.number <= *highest {
+            return;
+        }
+        if let Some(path) = &self.path {
+            if let Err(e) = persist(path, round.number) {
+                warn!(error = %e, path = %path.display(), "failed to persist observed ballot");
+            }
+        }
+        *highest = round.number;
+    }
+}
+
+/// 当前的毫秒时间戳
+fn physical() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn persist(path: &Path, number: i64) -> Result<()> {
+    storage::write_atomic(path, number.to_string().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ballot_allocator() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ballot");
+
+        let ballots = BallotAllocator::open(&path, 7).unwrap();
+        assert_eq!(ballots.next().unwrap().number, 1);
+        assert_eq!(ballots.next().unwrap().number, 2);
+        ballots.observe(&RoundNum {
+            number: 10,
+            proposer_id: 3,
+        });
+        let ballot = ballots.next().unwrap();
+        assert_eq!(ballot.number, 11);
+        assert_eq!(ballot.proposer_id, 7);
+
+        // 重启后不会重用已分配的 ballot
+        let ballots = BallotAllocator::open(&path, 7).unwrap();
+        assert_eq!(ballots.next().unwrap().number, 12);
+
+        // 看到的 round 同样在重启后保留
+        ballots.observe(&RoundNum {
+            number: 20,
+            proposer_id: 3,
+        });
+        let ballots = BallotAllocator::open(&path, 7).unwrap();
+        assert_eq!(ballots.next().unwrap().number, 21);
+    }
+
+    #[test]
+    fn test_hlc_ballots() {
+        let ballots = BallotAllocator::new(1).with_scheme(BallotScheme::Hlc);
+        let first = ballots.next().unwrap().number;
+        assert!(first >= (physical() - 1000) << LOGICAL_BITS);
+        // 同一毫秒内靠逻辑计数保持递增
+        let second = ballots.next().unwrap().number;
+        assert!(second > first);
+
+        // 看到未来的 ballot 时越过它
+        let ahead = (physical() + 60_000) << LOGICAL_BITS;
+        ballots.observe(&RoundNum {
+            number: ahead,
+            proposer_id: 2,
+        });
+        assert_eq!(ballots.next().unwrap().number, ahead + 1);
+    }
+}
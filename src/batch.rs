@@ -0,0 +1,280 @@
+use crate::auth;
+use crate::client::{grpc_timeout, unknown, within, Propose};
+use crate::error::ProposeError;
+use crate::metrics::ProposerMetrics;
+use crate::trace;
+use crate::{Acceptor, ProposerBatch, Value};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tonic::Request;
+use tracing::{debug, info_span, warn, Instrument};
+
+/// 一个批次最多包含的实例数
+const MAX_BATCH: usize = 256;
+
+type Reply = oneshot::Sender<Result<Option<Value>, ProposeError>>;
+
+/// 把一个时间窗口内并发的提议合并为 PrepareBatch/AcceptBatch 请求
+///
+/// 批次之间互不等待，同一批次中的实例各自判断是否被选定。
+#[derive(Debug, Clone)]
+pub(crate) struct Batcher {
+    tx: mpsc::UnboundedSender<(Propose, Reply)>,
+    metrics: Arc<ProposerMetrics>,
+}
+
+impl Batcher {
+    /// 启动后台任务，必须在 tokio runtime 中调用
+    pub(crate) fn new(
+        metrics: Arc<ProposerMetrics>,
+        token: Option<String>,
+        rpc_timeout: Option<Duration>,
+        window: Duration,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = Sender {
+            metrics: metrics.clone(),
+            token,
+            rpc_timeout,
+        };
+        tokio::spawn(collect(rx, Arc::new(sender), window));
+        Batcher { tx, metrics }
+    }
+
+    /// 把提议加入下一个批次，等待结果；超过提议的截止时间返回 [`ProposeError::Timeout`]
+    pub(crate) async fn run(&self, prop: Propose) -> Result<Option<Value>, ProposeError> {
+        let deadline = prop.deadline();
+        let (tx, rx) = oneshot::channel();
+        self.tx.send((prop, tx)).map_err(|_| stopped())?;
+        match within(deadline, rx).await {
+            Some(result) => result.map_err(|_| stopped())?,
+            None => Err(unknown(&self.metrics)),
+        }
+    }
+}
+
+async fn collect(
+    mut rx: mpsc::UnboundedReceiver<(Propose, Reply)>,
+    sender: Arc<Sender>,
+    window: Duration,
+) {
+    while let Some(first) = rx.recv().await {
+        let deadline = tokio::time::Instant::now() + window;
+        let mut batch = vec![first];
+        while batch.len() < MAX_BATCH {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(item)) => batch.push(item),
+                _ => break,
+            }
+        }
+        let sender = sender.clone();
+        tokio::spawn(async move { sender.run(batch).await });
+    }
+}
+
+struct Sender {
+    metrics: Arc<ProposerMetrics>,
+    token: Option<String>,
+    rpc_timeout: Option<Duration>,
+}
+
+impl Sender {
+    async fn run(&self, batch: Vec<(Propose, Reply)>) {
+        // 同一实例在一个批次中只发送第一个提议，其余的等待它的结果：
+        // 依次提议时后来者也会在 phase 1 沿用先被选定的值，结果相同
+        let mut first: HashMap<(String, i64), usize> = HashMap::new();
+        let mut props = vec![];
+        let mut waiters: Vec<Vec<Reply>> = vec![];
+        for (prop, reply) in batch {
+            let id = (prop.key().to_string(), prop.version());
+            match first.get(&id) {
+                Some(i) => waiters[*i].push(reply),
+                None => {
+                    first.insert(id, props.len());
+                    props.push(prop);
+                    waiters.push(vec![reply]);
+                }
+            }
+        }
+        let trace_id = trace::new_trace_id();
+        let span = info_span!("batch", instances = props.len(), trace_id = %trace_id);
+        let results = self.propose(&mut props, &trace_id).instrument(span).await;
+        for (result, replies) in results.into_iter().zip(waiters) {
+            for reply in replies {
+                let _ = reply.send(result.clone());
+            }
+        }
+    }
+
+    /// 对批次中的每个实例执行 phase 1 和 phase 2，结果与 props 一一对应
+    async fn propose(
+        &self,
+        props: &mut [Propose],
+        trace_id: &str,
+    ) -> Vec<Result<Option<Value>, ProposeError>> {
+        let mut results: Vec<Result<(), ProposeError>> = props.iter().map(|_| Ok(())).collect();
+
+        let all: Vec<usize> = (0..props.len()).collect();
+        match self.send("prepare_batch", props, &all, trace_id).await {
+            Ok(sent) => {
+                for (i, replies) in all.iter().zip(sent.replies) {
+                    let prepared = props[*i]
+                        .gathered_phase1(replies, sent.error.clone())
+                        .and_then(|replies| props[*i].prepared(replies));
+                    if let Err(e) = prepared {
+                        results[*i] = Err(e);
+                    }
+                }
+            }
+            Err(e) => return fail(props, e),
+        }
+
+        let prepared: Vec<usize> = all.into_iter().filter(|i| results[*i].is_ok()).collect();
+        if !prepared.is_empty() {
+            match self.send("accept_batch", props, &prepared, trace_id).await {
+                Ok(sent) => {
+                    for (i, replies) in prepared.iter().zip(sent.replies) {
+                        let prop = &props[*i];
+                        results[*i] = prop
+                            .gathered_phase2(replies, &sent.timed_out, sent.error.clone())
+                            .and_then(|replies| prop.resolve_phase2(replies));
+                    }
+                }
+                Err(e) => {
+                    for i in prepared {
+                        results[i] = Err(e.clone());
+                    }
+                }
+            }
+        }
+
+        props
+            .iter()
+            .zip(results)
+            .map(|(prop, result)| prop.finish(result))
+            .collect()
+    }
+
+    /// 把 props 中 indexes 指定的实例并发发给 Client 选出的 Acceptor，
+    /// 收集每个实例在各个 Acceptor 上的应答，失败和超时的 Acceptor 不计入应答
+    ///
+    /// 批次中的提议来自同一个 Client，使用第一个实例的连接。
+    async fn send(
+        &self,
+        method: &'static str,
+        props: &[Propose],
+        indexes: &[usize],
+        trace_id: &str,
+    ) -> Result<Sent, ProposeError> {
+        let acceptors = props[indexes[0]].acceptors();
+        if acceptors.is_empty() {
+            self.metrics.quorum_failures.inc();
+            return Err(ProposeError::NoQuorum {
+                reachable: 0,
+                needed: props[0].quorum().phase1_size(),
+            });
+        }
+        let batch = ProposerBatch {
+            proposers: indexes
+                .iter()
+                .map(|i| props[*i].proposer().clone())
+                .collect(),
+        };
+        let indexes_of: Vec<usize> = acceptors.iter().map(|(index, _)| *index).collect();
+        let calls = acceptors
+            .into_iter()
+            .enumerate()
+            .map(|(n, (_, mut client))| {
+                let mut request = Request::new(batch.clone());
+                trace::inject(&mut request, trace_id);
+                if let Some(token) = &self.token {
+                    auth::bearer(&mut request, token);
+                }
+                if let Some(timeout) = self.rpc_timeout {
+                    grpc_timeout(&mut request, timeout);
+                }
+                async move {
+                    let start = Instant::now();
+                    let r = if method == "prepare_batch" {
+                        within(self.rpc_timeout, client.prepare_batch(request)).await
+                    } else {
+                        within(self.rpc_timeout, client.accept_batch(request)).await
+                    };
+                    self.metrics.observe(method, start);
+                    r
+                }
+                .instrument(info_span!("rpc", method, acceptor = n))
+            });
+
+        let mut sent = Sent {
+            replies: indexes.iter().map(|_| vec![]).collect(),
+            timed_out: vec![],
+            error: None,
+        };
+        for (n, r) in join_all(calls).await.into_iter().enumerate() {
+            let acceptors = match r {
+                Some(Ok(resp)) => resp.into_inner().acceptors,
+                Some(Err(e)) => {
+                    warn!(acceptor = n, error = %e, "{} failed", method);
+                    sent.error.get_or_insert(e.into());
+                    continue;
+                }
+                None => {
+                    warn!(acceptor = n, "{} timed out", method);
+                    sent.timed_out.push(indexes_of[n]);
+                    continue;
+                }
+            };
+            if acceptors.len() != indexes.len() {
+                warn!(
+                    acceptor = n,
+                    replies = acceptors.len(),
+                    instances = indexes.len(),
+                    "{} returned a malformed reply",
+                    method
+                );
+                sent.error.get_or_insert(ProposeError::Transport(format!(
+                    "acceptor {} returned {} replies for {} instances",
+                    n,
+                    acceptors.len(),
+                    indexes.len()
+                )));
+                continue;
+            }
+            debug!(
+                acceptor = n,
+                instances = acceptors.len(),
+                "{} reply",
+                method
+            );
+            for (i, acc) in acceptors.into_iter().enumerate() {
+                sent.replies[i].push((indexes_of[n], acc));
+            }
+        }
+        Ok(sent)
+    }
+}
+
+/// 一次批量请求的结果
+struct Sent {
+    /// 与请求中的实例一一对应，每个实例在应答的 Acceptor 上的状态
+    replies: Vec<Vec<(usize, Acceptor)>>,
+    /// 超时的 Acceptor 下标
+    timed_out: Vec<usize>,
+    /// 遇到的第一个错误
+    error: Option<ProposeError>,
+}
+
+fn fail(props: &[Propose], e: ProposeError) -> Vec<Result<Option<Value>, ProposeError>> {
+    props
+        .iter()
+        .map(|prop| prop.finish(Err(e.clone())))
+        .collect()
+}
+
+fn stopped() -> ProposeError {
+    ProposeError::Transport("batcher stopped".to_string())
+}
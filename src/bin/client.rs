@@ -1,26 +1,746 @@
 extern crate rpaxos;
 
-use rpaxos::{PaxosClient, PaxosInstanceId, Proposer, RoundNum};
+use anyhow::Result;
+use futures::future::join_all;
+use rpaxos::{
+    bearer_interceptor, check_health, endpoint, instance_key, AdminClient, BallotScheme, Client,
+    ECommand, ExportRequest, FastRound, ForgetRequest, GetInstanceRequest, Instance,
+    LearnedRequest, LearnerClient, ListKeysRequest, Membership, QuorumSystem, RoundNum,
+    StorageSizeReply, StorageSizeRequest, TlsConfig, Value, WatchRequest, ZonePolicy, ZoneQuorum,
+};
+use serde_json::json;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+use structopt::StructOpt;
+use tonic::transport::{Channel, ClientTlsConfig};
+use tracing::warn;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = PaxosClient::connect("http://[::1]:11030").await?;
-
-    let request = tonic::Request::new(Proposer {
-        id: Some(PaxosInstanceId {
-            key: "sw".to_string(),
-            version: 0,
-        }),
-        round: Some(RoundNum {
-            number: 1,
-            proposer_id: 1,
-        }),
-        value: None,
+#[derive(Debug, StructOpt)]
+#[structopt(name = "client", about = "rpaxos command-line client")]
+struct Opt {
+    /// Acceptor 地址列表，逗号分隔
+    #[structopt(
+        short,
+        long,
+        use_delimiter = true,
+        number_of_values = 1,
+        default_value = "[::1]:11030,[::1]:11031,[::1]:11032"
+    )]
+    servers: Vec<String>,
+    /// Proposer ID，全局唯一
+    #[structopt(short, long, default_value = "1")]
+    proposer_id: i64,
+    /// 输出格式：text 或 json
+    #[structopt(short, long, default_value = "text", possible_values = &["text", "json"])]
+    output: String,
+    /// 校验服务端证书的 CA（PEM），设置后使用 TLS 连接
+    #[structopt(long, parse(from_os_str))]
+    ca: Option<PathBuf>,
+    /// 客户端证书（PEM），用于双向认证
+    #[structopt(long, parse(from_os_str))]
+    cert: Option<PathBuf>,
+    /// 客户端私钥（PEM）
+    #[structopt(long, parse(from_os_str))]
+    key: Option<PathBuf>,
+    /// 校验服务端证书时使用的域名
+    #[structopt(long)]
+    domain: Option<String>,
+    /// 经由与每个 Acceptor 的双向流发送请求
+    #[structopt(long)]
+    stream: bool,
+    /// 单个 Prepare/Accept 的超时，毫秒
+    #[structopt(long, default_value = "3000")]
+    rpc_timeout_ms: u64,
+    /// 整个提议的超时，毫秒；超时后结果未知
+    #[structopt(long)]
+    timeout_ms: Option<u64>,
+    /// 记录已用过 ballot 的文件，重启后不会重用
+    #[structopt(long, parse(from_os_str))]
+    ballot_file: Option<PathBuf>,
+    /// 每个 Acceptor 所在的 zone，逗号分隔，与 --servers 一一对应
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    zones: Vec<String>,
+    /// 本机所在的 zone，其中的 Acceptor 最先收到请求
+    #[structopt(long)]
+    local_zone: Option<String>,
+    /// 按 zone 计算 quorum：majority-of-zones 或 any-zone-may-fail
+    #[structopt(long)]
+    zone_policy: Option<ZonePolicy>,
+    /// 值被选定后通知的 Learner 地址，逗号分隔，不计入 quorum
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    learners: Vec<String>,
+    /// 见证者在 --servers 中的下标，逗号分隔
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    witnesses: Vec<usize>,
+    /// 开启 RS-Paxos，值编码为分片后任意这么多个分片可以恢复
+    #[structopt(long)]
+    erasure_data_shards: Option<usize>,
+    /// 使用混合逻辑时钟生成 ballot
+    #[structopt(long)]
+    hlc_ballots: bool,
+    /// 服务端开启认证时使用的 bearer token
+    #[structopt(long)]
+    token: Option<String>,
+    /// Admin 命令使用的管理员 token，不指定时使用 --token
+    #[structopt(long)]
+    admin_token: Option<String>,
+    /// 通知 Learner 和成员变更时使用的节点 token，不指定时使用 --token
+    #[structopt(long)]
+    node_token: Option<String>,
+    /// 日志级别，日志输出到 stderr
+    #[structopt(long, default_value = "warn")]
+    log_level: tracing::Level,
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// 对 key/version 提议一个值，输出最终被选定的值
+    Propose {
+        key: String,
+        value: i64,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+        /// 不指定时分配一个新的 ballot
+        #[structopt(long)]
+        round: Option<i64>,
+    },
+    /// 读取 key/version 已选定的值
+    Get {
+        key: String,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+        /// 不指定时分配一个新的 ballot
+        #[structopt(long)]
+        round: Option<i64>,
+    },
+    /// 只执行 phase 1
+    Phase1 {
+        key: String,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+        /// 不指定时分配一个新的 ballot
+        #[structopt(long)]
+        round: Option<i64>,
+    },
+    /// 输出每个 Acceptor 上 key/version 的状态
+    Dump {
+        key: String,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+    },
+    /// 列出每个 Acceptor 上的 key
+    Keys {
+        #[structopt(long, default_value = "")]
+        prefix: String,
+    },
+    /// 输出每个 Acceptor 上以 prefix 开头的全部实例
+    Export {
+        #[structopt(long, default_value = "")]
+        prefix: String,
+    },
+    /// 输出每个 Acceptor 的存储大小
+    Size,
+    /// 强制删除每个 Acceptor 上 key/version 的状态，只在修复故障时使用
+    Forget {
+        key: String,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+    },
+    /// 输出集群中各节点是否就绪
+    Status,
+    /// 通过 Paxos 把成员变更为给定的 Acceptor 列表
+    Reconfigure {
+        #[structopt(required = true)]
+        members: Vec<String>,
+    },
+    /// 输出当前生效的成员配置
+    Members,
+    /// 作为协调者为 key 打开 fast round，输出其 round
+    OpenFast { key: String },
+    /// 在已打开的 fast round 中直接提交值，输出最终被选定的值
+    FastPropose {
+        key: String,
+        value: i64,
+        /// open-fast 输出的 round，格式为 number.proposer_id
+        #[structopt(long, parse(try_from_str = parse_round))]
+        fast_round: RoundNum,
+    },
+    /// 以 EPaxos 提交写入 key 的命令，输出其实例、seq 和依赖
+    Epaxos { key: String, value: i64 },
+    /// 按执行顺序输出 key 上已提交的 EPaxos 命令
+    EpaxosLog { key: String },
+    /// 从 Learner 读取 key/version 的值，可能落后于 Acceptor
+    Learned {
+        key: String,
+        #[structopt(long, default_value = "0")]
+        version: i64,
+        #[structopt(long)]
+        learner: String,
+    },
+    /// 持续输出 Learner 之后学到的、key 以 prefix 开头的值
+    Watch {
+        #[structopt(default_value = "")]
+        prefix: String,
+        #[structopt(long)]
+        learner: String,
+    },
+}
+
+fn parse_round(s: &str) -> Result<RoundNum> {
+    let (number, proposer_id) = s
+        .split_once('.')
+        .ok_or_else(|| anyhow::Error::msg(format!("invalid round: {}", s)))?;
+    Ok(RoundNum {
+        number: number.parse()?,
+        proposer_id: proposer_id.parse()?,
+    })
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn value(&self, key: &str, version: i64, value: Option<Value>) {
+        if self.json {
+            println!(
+                "{}",
+                json!({
+                    "key": key,
+                    "version": version,
+                    "value": value.map(|v| v.value),
+                })
+            );
+        } else {
+            match value {
+                Some(v) => println!("{}@{} = {}", key, version, v.value),
+                None => println!("{}@{} = <none>", key, version),
+            }
+        }
+    }
+
+    fn command(&self, command: &ECommand, fast: Option<bool>) {
+        let id = command.id.clone().unwrap_or_default();
+        let attrs = command.attributes.clone().unwrap_or_default();
+        let value = command.value.as_ref().map(|v| v.value);
+        let deps: Vec<String> = attrs
+            .deps
+            .iter()
+            .map(|d| format!("{}.{}", d.replica, d.slot))
+            .collect();
+        if self.json {
+            println!(
+                "{}",
+                json!({
+                    "id": format!("{}.{}", id.replica, id.slot),
+                    "key": command.key,
+                    "value": value,
+                    "seq": attrs.seq,
+                    "deps": deps,
+                    "fast": fast,
+                })
+            );
+        } else {
+            let path = match fast {
+                Some(true) => " (fast)",
+                Some(false) => " (slow)",
+                None => "",
+            };
+            println!(
+                "{}.{} {} = {} seq={} deps=[{}]{}",
+                id.replica,
+                id.slot,
+                command.key,
+                value
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "<none>".to_string()),
+                attrs.seq,
+                deps.join(","),
+                path
+            );
+        }
+    }
+
+    fn instances(&self, states: Vec<(String, Result<Vec<Instance>>)>) {
+        if self.json {
+            let mut list = vec![];
+            for (server, state) in states {
+                match state {
+                    Ok(instances) => {
+                        for inst in instances {
+                            let acc = inst.acceptor.unwrap_or_default();
+                            list.push(json!({
+                                "server": server,
+                                "key": inst.key,
+                                "round": round_json(&acc.round),
+                                "last_round": round_json(&acc.last_round),
+                                "value": acc.value.map(|v| v.value),
+                            }));
+                        }
+                    }
+                    Err(e) => list.push(json!({ "server": server, "error": e.to_string() })),
+                }
+            }
+            println!("{}", serde_json::Value::Array(list));
+        } else {
+            for (server, state) in states {
+                match state {
+                    Ok(instances) => {
+                        for inst in instances {
+                            let acc = inst.acceptor.unwrap_or_default();
+                            println!(
+                                "{} {}: round={} last_round={} value={}",
+                                server,
+                                inst.key.escape_debug(),
+                                round_text(&acc.round),
+                                round_text(&acc.last_round),
+                                acc.value
+                                    .map(|v| v.value.to_string())
+                                    .unwrap_or_else(|| "<none>".to_string()),
+                            );
+                        }
+                    }
+                    Err(e) => println!("{} error: {}", server, e),
+                }
+            }
+        }
+    }
+
+    /// 每个节点一个结果，T 为可直接转成 JSON 的值
+    fn per_server<T>(&self, results: Vec<(String, Result<T>)>)
+    where
+        T: Into<serde_json::Value>,
+    {
+        let list: Vec<_> = results
+            .into_iter()
+            .map(|(server, r)| match r {
+                Ok(v) => (server, Ok(v.into())),
+                Err(e) => (server, Err(e.to_string())),
+            })
+            .collect();
+        if self.json {
+            let list: Vec<_> = list
+                .into_iter()
+                .map(|(server, r)| match r {
+                    Ok(v) => json!({ "server": server, "result": v }),
+                    Err(e) => json!({ "server": server, "error": e }),
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(list));
+        } else {
+            for (server, r) in list {
+                match r {
+                    Ok(v) => println!("{} {}", server, v),
+                    Err(e) => println!("{} error: {}", server, e),
+                }
+            }
+        }
+    }
+
+    fn membership(&self, membership: &Membership) {
+        if self.json {
+            println!(
+                "{}",
+                json!({ "epoch": membership.epoch, "servers": membership.servers })
+            );
+        } else {
+            println!(
+                "epoch {}: {}",
+                membership.epoch,
+                membership.servers.join(",")
+            );
+        }
+    }
+
+    /// 就绪的节点同时构成 quorum 的 phase 1 和 phase 2 quorum 时才算健康
+    fn status(&self, servers: Vec<(String, &'static str)>, quorum: &dyn QuorumSystem) {
+        let ready: Vec<usize> = (0..servers.len())
+            .filter(|i| servers[*i].1 == "ready")
+            .collect();
+        let healthy = quorum.is_phase1_quorum(&ready) && quorum.is_phase2_quorum(&ready);
+        let (ready, quorum) = (
+            ready.len(),
+            std::cmp::max(quorum.phase1_size(), quorum.phase2_size()),
+        );
+        if self.json {
+            let list: Vec<_> = servers
+                .iter()
+                .map(|(server, status)| json!({ "server": server, "status": status }))
+                .collect();
+            println!(
+                "{}",
+                json!({ "servers": list, "ready": ready, "quorum": quorum, "healthy": healthy })
+            );
+        } else {
+            for (server, status) in &servers {
+                println!("{} {}", server, status);
+            }
+            println!(
+                "{}/{} ready, quorum {}: {}",
+                ready,
+                servers.len(),
+                quorum,
+                if healthy { "healthy" } else { "unavailable" }
+            );
+        }
+    }
+}
+
+fn round_json(round: &Option<RoundNum>) -> serde_json::Value {
+    let round = round.clone().unwrap_or_default();
+    json!({ "number": round.number, "proposer_id": round.proposer_id })
+}
+
+fn round_text(round: &Option<RoundNum>) -> String {
+    let round = round.clone().unwrap_or_default();
+    format!("{}.{}", round.number, round.proposer_id)
+}
+
+impl Opt {
+    /// 指定了 CA 或客户端证书时使用 TLS
+    fn tls(&self) -> Result<Option<ClientTlsConfig>> {
+        if self.ca.is_none() && self.cert.is_none() {
+            return Ok(None);
+        }
+        let config = TlsConfig {
+            cert: self.cert.clone(),
+            key: self.key.clone(),
+            ca: self.ca.clone(),
+            domain: self.domain.clone(),
+            ..Default::default()
+        };
+        Ok(Some(config.client_config()?))
+    }
+
+    /// 按全局选项创建提议用的 [`Client`]，尚未连接
+    fn client(&self, tls: Option<ClientTlsConfig>) -> Result<Client> {
+        let mut client = Client::new(self.servers.clone(), self.proposer_id);
+        if self.hlc_ballots {
+            client.set_ballot_scheme(BallotScheme::Hlc);
+        }
+        if let Some(path) = &self.ballot_file {
+            client.set_ballot_file(path)?;
+        }
+        if !self.zones.is_empty() {
+            client.set_zones(self.zones.clone(), self.local_zone.clone())?;
+        }
+        if let Some(policy) = self.zone_policy {
+            client.set_quorum(ZoneQuorum::new(self.zones.clone(), policy)?)?;
+        }
+        if let Some(data_shards) = self.erasure_data_shards {
+            client.set_erasure_coding(data_shards)?;
+        }
+        if !self.learners.is_empty() {
+            client.set_learners(self.learners.clone());
+        }
+        if !self.witnesses.is_empty() {
+            client.set_witnesses(self.witnesses.clone());
+        }
+        if let Some(tls) = tls {
+            client.set_tls(tls);
+        }
+        if let Some(token) = &self.token {
+            client.set_token(token.clone());
+        }
+        if let Some(token) = &self.node_token {
+            client.set_node_token(token.clone());
+        }
+        client.set_streaming(self.stream);
+        client.set_rpc_timeout(Duration::from_millis(self.rpc_timeout_ms));
+        Ok(client)
+    }
+}
+
+async fn connect(server: &str, tls: Option<&ClientTlsConfig>) -> Result<Channel> {
+    Ok(endpoint(server, tls)?.connect().await?)
+}
+
+/// 对每个节点的 [`AdminClient`] 执行 f
+async fn each_admin<F, Fut, T>(
+    servers: Vec<String>,
+    tls: Option<ClientTlsConfig>,
+    token: Option<String>,
+    f: F,
+) -> Vec<(String, Result<T>)>
+where
+    F: Fn(AdminClient<Channel>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let f = servers.into_iter().map(|server| {
+        let f = &f;
+        let tls = tls.clone();
+        let token = token.clone();
+        async move {
+            let r = match connect(&server, tls.as_ref()).await {
+                Ok(channel) => {
+                    let client = AdminClient::with_interceptor(channel, bearer_interceptor(token));
+                    f(client).await
+                }
+                Err(e) => Err(e),
+            };
+            (server, r)
+        }
     });
+    join_all(f).await
+}
+
+fn size_json(size: StorageSizeReply) -> serde_json::Value {
+    json!({ "instances": size.instances, "journal_bytes": size.journal_bytes })
+}
 
-    let response = client.prepare(request).await?;
+/// 节点状态：ready、not ready 或 down
+async fn probe(server: String, tls: Option<ClientTlsConfig>) -> (String, &'static str) {
+    let status = match connect(&server, tls.as_ref()).await {
+        Ok(channel) => match check_health(channel).await {
+            Ok(true) => "ready",
+            Ok(false) => "not ready",
+            Err(_) => "down",
+        },
+        Err(_) => "down",
+    };
+    (server, status)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opt = Opt::from_args();
+    tracing_subscriber::fmt()
+        .with_max_level(opt.log_level)
+        .with_writer(std::io::stderr)
+        .init();
+    let out = Output {
+        json: opt.output == "json",
+    };
+    let tls = opt.tls()?;
+    let token = opt.token.clone();
+    let admin_token = opt.admin_token.clone().or_else(|| token.clone());
+    let client = opt.client(tls.clone())?;
 
-    println!("RESPONSE={:?}", response);
+    match opt.cmd {
+        Command::Propose {
+            key,
+            value,
+            version,
+            round,
+        } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let mut prop = client.propose(
+                key.clone(),
+                Some(Value {
+                    value,
+                    ..Default::default()
+                }),
+            )?;
+            prop.set_version(version);
+            if let Some(round) = round {
+                prop.set_round(round);
+            }
+            if let Some(ms) = opt.timeout_ms {
+                prop.set_deadline(Duration::from_millis(ms));
+            }
+            let value = prop.run().await?;
+            out.value(&key, version, value);
+            client.flush_learners().await;
+        }
+        Command::Get {
+            key,
+            version,
+            round,
+        } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let mut prop = client.propose(key.clone(), None)?;
+            prop.set_version(version);
+            if let Some(round) = round {
+                prop.set_round(round);
+            }
+            // 读到值时再走一次 phase 2，确保该值已被多数派接受
+            let value = prop.run_phase1().await?;
+            if value.is_some() {
+                prop.run_phase2().await?;
+            }
+            out.value(&key, version, value);
+        }
+        Command::Phase1 {
+            key,
+            version,
+            round,
+        } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let mut prop = client.propose(key.clone(), None)?;
+            prop.set_version(version);
+            if let Some(round) = round {
+                prop.set_round(round);
+            }
+            let value = prop.run_phase1().await?;
+            out.value(&key, version, value);
+        }
+        Command::Dump { key, version } => {
+            let states = each_admin(opt.servers, tls, admin_token, |mut c| {
+                let key = instance_key(&key, version);
+                async move {
+                    match c.get_instance(GetInstanceRequest { key }).await {
+                        Ok(resp) => Ok(vec![resp.into_inner()]),
+                        Err(status) if status.code() == tonic::Code::NotFound => Ok(vec![]),
+                        Err(status) => Err(status.into()),
+                    }
+                }
+            })
+            .await;
+            out.instances(states);
+        }
+        Command::Keys { prefix } => {
+            let keys = each_admin(opt.servers, tls, admin_token, |mut c| {
+                let prefix = prefix.clone();
+                async move {
+                    Ok(c.list_keys(ListKeysRequest { prefix })
+                        .await?
+                        .into_inner()
+                        .keys)
+                }
+            })
+            .await;
+            out.per_server(keys);
+        }
+        Command::Export { prefix } => {
+            let states = each_admin(opt.servers, tls, admin_token, |mut c| {
+                let prefix = prefix.clone();
+                async move {
+                    let reply = c.export(ExportRequest { prefix }).await?;
+                    Ok(reply.into_inner().instances)
+                }
+            })
+            .await;
+            out.instances(states);
+        }
+        Command::Size => {
+            let sizes = each_admin(opt.servers, tls, admin_token, |mut c| async move {
+                let reply = c.storage_size(StorageSizeRequest {}).await?;
+                Ok(size_json(reply.into_inner()))
+            })
+            .await;
+            out.per_server(sizes);
+        }
+        Command::Forget { key, version } => {
+            let results = each_admin(opt.servers, tls, admin_token, |mut c| {
+                let key = instance_key(&key, version);
+                async move { Ok(c.forget(ForgetRequest { key }).await?.into_inner().existed) }
+            })
+            .await;
+            out.per_server(results);
+        }
+        Command::Status => {
+            let f = opt.servers.into_iter().map(|s| probe(s, tls.clone()));
+            out.status(join_all(f).await, client.quorum_system().as_ref());
+        }
+        Command::Reconfigure { members } => {
+            let mut client = client;
+            client.connect().await?;
+            let chosen = client.reconfigure(members.clone()).await?;
+            if chosen.servers != members {
+                warn!(
+                    epoch = chosen.epoch,
+                    "another membership change won this epoch"
+                );
+            }
+            out.membership(&chosen);
+        }
+        Command::OpenFast { key } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let fast = client.open_fast_round(key).await?;
+            let round = Some(fast.round);
+            if out.json {
+                println!(
+                    "{}",
+                    json!({ "key": fast.key, "round": round_json(&round) })
+                );
+            } else {
+                println!("{} fast round {}", fast.key, round_text(&round));
+            }
+        }
+        Command::FastPropose {
+            key,
+            value,
+            fast_round,
+        } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let fast = FastRound {
+                key: key.clone(),
+                round: fast_round,
+            };
+            let value = Value {
+                value,
+                ..Default::default()
+            };
+            let chosen = client.fast_propose(&fast, value).await?;
+            out.value(&key, 0, chosen);
+            client.flush_learners().await;
+        }
+        Command::Epaxos { key, value } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            let value = Value {
+                value,
+                ..Default::default()
+            };
+            let commit = client.epaxos_propose(key, value).await?;
+            out.command(&commit.command, Some(commit.fast));
+        }
+        Command::EpaxosLog { key } => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            for command in client.epaxos_log(&key).await? {
+                out.command(&command, None);
+            }
+        }
+        Command::Learned {
+            key,
+            version,
+            learner,
+        } => {
+            let channel = connect(&learner, tls.as_ref()).await?;
+            let mut client = LearnerClient::with_interceptor(channel, bearer_interceptor(token));
+            let request = LearnedRequest {
+                key: key.clone(),
+                version,
+            };
+            match client.get(request).await {
+                Ok(learned) => out.value(&key, version, learned.into_inner().value),
+                Err(status) if status.code() == tonic::Code::NotFound => {
+                    out.value(&key, version, None)
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
+        Command::Watch { prefix, learner } => {
+            let channel = connect(&learner, tls.as_ref()).await?;
+            let mut client = LearnerClient::with_interceptor(channel, bearer_interceptor(token));
+            let mut updates = client.watch(WatchRequest { prefix }).await?.into_inner();
+            while let Some(learned) = updates.message().await? {
+                out.value(&learned.key, learned.version, learned.value);
+            }
+        }
+        Command::Members => {
+            let mut client = client;
+            client.connect().await?;
+            client.refresh().await?;
+            out.membership(client.membership());
+        }
+    }
 
     Ok(())
 }
@@ -1,24 +1,292 @@
 extern crate rpaxos;
 
-use rpaxos::PaxosServer;
-use rpaxos::PaxosService;
+use rpaxos::{
+    admin_interceptor, anti_entropy, catch_up, interceptor, learner_interceptor, node_interceptor,
+    serve_metrics, watch_peers, AdminServer, EpaxosServer, FsyncMode, LearnerServer,
+    LearnerService, PaxosServer, PaxosService, PeerServer, ServerConfig, PAXOS_SERVICE_NAME,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
 use tonic::transport::Server;
+use tracing::{error, info, warn, Level};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "server", about = "rpaxos acceptor server")]
+struct Opt {
+    /// 配置文件路径（TOML，扩展名为 .yaml 或 .yml 时按 YAML 解析）
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// 监听地址，如 [::1]:11030
+    #[structopt(short, long)]
+    listen: Option<String>,
+    /// 本节点 ID
+    #[structopt(long)]
+    node_id: Option<i64>,
+    /// 其他节点地址，可重复指定
+    #[structopt(long = "peer")]
+    peers: Vec<String>,
+    /// 数据目录，不指定时只保存在内存中
+    #[structopt(long, parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+    /// 刷盘策略：always 或 never
+    #[structopt(long)]
+    fsync: Option<FsyncMode>,
+    /// 日志级别：error、warn、info、debug、trace
+    #[structopt(long)]
+    log_level: Option<String>,
+    /// Prometheus 指标的 HTTP 监听地址
+    #[structopt(long)]
+    metrics_listen: Option<String>,
+    /// 请求处理超时（毫秒），0 表示不限制
+    #[structopt(long)]
+    request_timeout_ms: Option<u64>,
+    /// anti-entropy 的间隔（毫秒），0 表示不运行
+    #[structopt(long)]
+    anti_entropy_ms: Option<u64>,
+    /// 作为 Learner 运行时从 Acceptor 补齐值的间隔（毫秒），0 表示不补齐
+    #[structopt(long)]
+    catch_up_ms: Option<u64>,
+    /// 作为 Learner 补齐时认定值已被选定所需的 Acceptor 数，0 表示多数派
+    #[structopt(long)]
+    catch_up_quorum: Option<usize>,
+    /// 作为见证者运行，只保存 ballot 和值的摘要
+    #[structopt(long)]
+    witness: bool,
+    /// 作为不参与投票的 Learner 运行
+    #[structopt(long)]
+    learner: bool,
+    /// 提供 Admin 服务，开启认证时只允许管理员调用
+    #[structopt(long)]
+    admin: bool,
+    /// 访问其他节点时出示的 bearer token
+    #[structopt(long)]
+    node_token: Option<String>,
+    /// Mencius 日志中 slot 所有者的 proposer_id，按 slot 轮转的顺序重复指定
+    #[structopt(long = "mencius-owner")]
+    mencius_owners: Vec<i64>,
+}
+
+impl Opt {
+    fn into_config(self) -> anyhow::Result<ServerConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::load(path)?,
+            None => ServerConfig::default(),
+        };
+        if let Some(listen) = self.listen {
+            config.listen = listen;
+        }
+        if let Some(id) = self.node_id {
+            config.node_id = id;
+        }
+        if !self.peers.is_empty() {
+            config.peers = self.peers;
+        }
+        if self.data_dir.is_some() {
+            config.data_dir = self.data_dir;
+        }
+        if self.witness {
+            config.witness = true;
+        }
+        if self.learner {
+            config.learner = true;
+        }
+        if self.admin {
+            config.admin = true;
+        }
+        if self.node_token.is_some() {
+            config.node_token = self.node_token;
+        }
+        if !self.mencius_owners.is_empty() {
+            config.mencius_owners = self.mencius_owners;
+        }
+        if let Some(fsync) = self.fsync {
+            config.fsync = fsync;
+        }
+        if let Some(level) = self.log_level {
+            config.log_level = level;
+        }
+        if self.metrics_listen.is_some() {
+            config.metrics_listen = self.metrics_listen;
+        }
+        if let Some(ms) = self.request_timeout_ms {
+            config.timeouts.request_ms = ms;
+        }
+        if let Some(ms) = self.anti_entropy_ms {
+            config.timeouts.anti_entropy_ms = ms;
+        }
+        if let Some(ms) = self.catch_up_ms {
+            config.timeouts.catch_up_ms = ms;
+        }
+        if let Some(quorum) = self.catch_up_quorum {
+            config.catch_up_quorum = quorum;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Hello, world!");
-    let addr = "[::1]:11030".parse().unwrap();
+    let config = Opt::from_args().into_config()?;
+    let level: Level = config.log_level.parse()?;
+    tracing_subscriber::fmt().with_max_level(level).init();
+    let addr = config.listen.parse()?;
+
+    if config.learner {
+        return serve_learner(config, addr).await;
+    }
 
-    println!("PaxosServer listening on: {}", addr);
+    let mut service = match &config.data_dir {
+        Some(dir) => PaxosService::open(dir, config.fsync)?,
+        None => PaxosService::default(),
+    };
+    if config.witness {
+        info!("running as witness, only value digests are stored");
+        service.set_witness(true);
+    }
+    service.set_mencius_owners(config.mencius_owners.clone());
+    if let Some(metrics_addr) = &config.metrics_listen {
+        let metrics_addr = metrics_addr.parse()?;
+        let registry = service.metrics().registry().clone();
+        info!("metrics listening on: http://{}/metrics", metrics_addr);
+        tokio::spawn(async move {
+            if let Err(e) = serve_metrics(metrics_addr, registry).await {
+                error!(error = %e, "metrics server exit");
+            }
+        });
+    }
+    // 存储已恢复，由 watch_peers 根据其他节点的可达情况报告就绪状态
+    let (mut reporter, health) = tonic_health::server::health_reporter();
+    reporter
+        .set_service_status(PAXOS_SERVICE_NAME, tonic_health::ServingStatus::NotServing)
+        .await;
+    let peer_tls = match &config.tls {
+        Some(tls) => Some(tls.client_config()?),
+        None => None,
+    };
+    let watcher = tokio::spawn(watch_peers(
+        reporter.clone(),
+        config.peers.clone(),
+        peer_tls.clone(),
+        config.timeouts.probe(),
+    ));
+    // 没有客户端请求时也从其他节点拉取缺少的值
+    if let Some(interval) = config.timeouts.anti_entropy() {
+        if !config.peers.is_empty() {
+            tokio::spawn(anti_entropy(
+                service.clone(),
+                config.peers.clone(),
+                peer_tls,
+                config.node_token.clone(),
+                interval,
+            ));
+        }
+    }
 
-    let service = PaxosService {
-        storage: Default::default(),
+    // 认证调用方，Paxos 请求中 ballot 的 proposer_id 须与调用方一致
+    if let Some(auth) = &config.auth {
+        info!(
+            subjects = auth.subjects.len(),
+            tokens = auth.tokens.len(),
+            admins = auth.admins.subjects.len() + auth.admins.tokens.len(),
+            nodes = auth.nodes.subjects.len() + auth.nodes.tokens.len(),
+            "caller authentication enabled"
+        );
+    }
+    let admin = match config.admin {
+        true => {
+            if config.auth.is_none() {
+                warn!("admin service enabled without authentication");
+            }
+            Some(AdminServer::with_interceptor(
+                service.admin(),
+                admin_interceptor(config.auth.clone()),
+            ))
+        }
+        false => None,
     };
+    let peer = PeerServer::with_interceptor(service.peer(), node_interceptor(config.auth.clone()));
+    let epaxos = EpaxosServer::with_interceptor(service.epaxos(), interceptor(config.auth.clone()));
+    let svc = PaxosServer::with_interceptor(service, interceptor(config.auth.clone()));
+
+    info!(
+        node_id = config.node_id,
+        peers = ?config.peers,
+        "PaxosServer listening on: {}",
+        addr
+    );
+
+    let mut builder = Server::builder().tcp_keepalive(config.timeouts.keepalive());
+    if let Some(tls) = &config.tls {
+        builder = builder.tls_config(tls.server_config()?)?;
+        info!(mutual = tls.client_ca.is_some(), "TLS enabled");
+    }
+    if let Some(timeout) = config.timeouts.request() {
+        builder.timeout(timeout);
+    }
+    builder
+        .add_service(health)
+        .add_service(svc)
+        .add_service(peer)
+        .add_optional_service(admin)
+        .add_service(epaxos)
+        .serve_with_shutdown(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+            // 先报告未就绪，让客户端不再选择本节点
+            watcher.abort();
+            reporter
+                .set_service_status(PAXOS_SERVICE_NAME, tonic_health::ServingStatus::NotServing)
+                .await;
+            info!("PaxosServer shutting down");
+        })
+        .await?;
+
+    info!("PaxosServer exit");
+    Ok(())
+}
 
-    let svc = PaxosServer::new(service);
+/// 作为 Learner 运行：只接收被选定的值，不参与投票，不保存 Acceptor 状态；
+/// 从 peers 中的 Acceptor 补齐推送丢失或重启前错过的值
+async fn serve_learner(
+    config: ServerConfig,
+    addr: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let service = LearnerService::default();
+    if let Some(interval) = config.timeouts.catch_up() {
+        if !config.peers.is_empty() {
+            let tls = match &config.tls {
+                Some(tls) => Some(tls.client_config()?),
+                None => None,
+            };
+            tokio::spawn(catch_up(
+                service.clone(),
+                config.peers.clone(),
+                config.catch_up_quorum()?,
+                tls,
+                config.node_token.clone(),
+                interval,
+            ));
+        }
+    }
+    let learner =
+        LearnerServer::with_interceptor(service, learner_interceptor(config.auth.clone()));
+    info!(
+        node_id = config.node_id,
+        "LearnerServer listening on: {}", addr
+    );
 
-    Server::builder().add_service(svc).serve(addr).await?;
+    let mut builder = Server::builder().tcp_keepalive(config.timeouts.keepalive());
+    if let Some(tls) = &config.tls {
+        builder = builder.tls_config(tls.server_config()?)?;
+        info!(mutual = tls.client_ca.is_some(), "TLS enabled");
+    }
+    builder
+        .add_service(learner)
+        .serve_with_shutdown(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
 
-    println!("PaxosServer exit");
+    info!("LearnerServer exit");
     Ok(())
 }
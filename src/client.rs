@@ -1,14 +1,107 @@
-use crate::{Acceptor, PaxosClient, PaxosInstanceId, Proposer, RoundNum, Value};
+use crate::auth;
+use crate::ballot::{BallotAllocator, BallotScheme};
+use crate::batch::Batcher;
+use crate::conn::{Connection, ConnectionManager, ServerHealth};
+use crate::epaxos::{self, ECommit};
+use crate::erasure::{self, Erasure};
+use crate::error::ProposeError;
+use crate::fast::{self, FastRound};
+use crate::learner::Learners;
+use crate::membership;
+use crate::metrics::ProposerMetrics;
+use crate::paxos::peer_client::PeerClient;
+use crate::quorum::{Majority, QuorumSystem};
+use crate::server::split_instance_key;
+use crate::stream::{AcceptorStream, Transport};
+#[cfg(test)]
+use crate::tls;
+use crate::trace;
+use crate::witness;
+use crate::{
+    Acceptor, ECommand, EInstanceId, EpaxosClient, FenceRequest, ListKeysRequest, Membership,
+    PaxosClient, PaxosInstanceId, Proposer, RoundNum, Value,
+};
 use anyhow::{Error, Result};
 use futures::future::join_all;
-use std::convert::TryFrom;
-use tonic::transport::{Channel, Endpoint};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::Request;
+use tracing::{debug, info, info_span, instrument, warn, Instrument};
 
 #[derive(Debug, Clone, Default)]
 pub struct Propose {
     proposer: Proposer,
     servers: Vec<String>,
     context: Vec<PaxosClient<Channel>>,
+    /// 与 context 一一对应，有双向流时走流
+    streams: Vec<Option<AcceptorStream>>,
+    /// 与 context 一一对应，Acceptor 在配置中的下标
+    indexes: Vec<usize>,
+    quorum: Option<Arc<dyn QuorumSystem>>,
+    metrics: Arc<ProposerMetrics>,
+    trace_id: String,
+    tls: Option<ClientTlsConfig>,
+    token: Option<String>,
+    rpc_timeout: Option<Duration>,
+    deadline: Option<Duration>,
+    ballots: Option<Arc<BallotAllocator>>,
+    /// 设置时 accept 只向每个 Acceptor 发送其对应的分片
+    erasure: Option<Arc<Erasure>>,
+    /// 值被选定后通知的 Learner
+    learners: Option<Learners>,
+}
+
+/// 成员变更被抢占后最多重试的次数
+const MAX_RETRIES: usize = 3;
+
+/// 默认的单个 RPC 超时
+const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_millis(3000);
+
+/// 通过 grpc-timeout header 把超时告诉服务端，单位毫秒
+pub(crate) fn grpc_timeout<T>(request: &mut Request<T>, timeout: Duration) {
+    // 协议规定最多 8 位数字
+    let ms = std::cmp::min(timeout.as_millis(), 99_999_999);
+    if let Ok(value) = format!("{}m", ms).parse() {
+        request.metadata_mut().insert("grpc-timeout", value);
+    }
+}
+
+/// 在 timeout 内完成 f，超时返回 None
+pub(crate) async fn within<T>(
+    timeout: Option<Duration>,
+    f: impl std::future::Future<Output = T>,
+) -> Option<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, f).await.ok(),
+        None => Some(f.await),
+    }
+}
+
+/// 按完成的先后把应答交给 on_reply，on_reply 返回 true 后不再等待，
+/// 剩下的请求在后台继续完成，较远的 Acceptor 仍会收到请求
+pub(crate) async fn until_enough<T, F>(calls: Vec<F>, mut on_reply: impl FnMut(usize, T) -> bool)
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut pending: FuturesUnordered<_> = calls
+        .into_iter()
+        .enumerate()
+        .map(|(i, f)| async move { (i, f.await) })
+        .collect();
+    while let Some((i, r)) = pending.next().await {
+        if on_reply(i, r) {
+            break;
+        }
+    }
+    if !pending.is_empty() {
+        tokio::spawn(pending.for_each(|_| async {}));
+    }
 }
 
 impl Propose {
@@ -21,163 +114,441 @@ impl Propose {
                     number: 0,
                     proposer_id: id,
                 }),
-                value: value,
+                value,
+                epoch: 0,
             },
+            trace_id: trace::new_trace_id(),
             ..Default::default()
         }
     }
 
+    pub(crate) fn key(&self) -> &str {
+        self.proposer
+            .id
+            .as_ref()
+            .map(|id| id.key.as_str())
+            .unwrap_or("")
+    }
+
+    pub(crate) fn version(&self) -> i64 {
+        self.proposer.id.as_ref().map(|id| id.version).unwrap_or(0)
+    }
+
+    /// round 的可读形式：number.proposer_id
+    fn ballot(&self) -> String {
+        let round = self.proposer.round.clone().unwrap_or_default();
+        format!("{}.{}", round.number, round.proposer_id)
+    }
+
+    /// 构造发往 Acceptor 的请求，并带上 trace id 和 token
+    fn request(&self) -> Request<Proposer> {
+        let mut request = Request::new(self.proposer.clone());
+        trace::inject(&mut request, &self.trace_id);
+        if let Some(token) = &self.token {
+            auth::bearer(&mut request, token);
+        }
+        if let Some(timeout) = self.rpc_timeout {
+            grpc_timeout(&mut request, timeout);
+        }
+        request
+    }
+
+    #[cfg(test)]
     async fn phase1(&mut self, svr: Option<Vec<i32>>) -> Result<Option<Value>> {
         let svr = if let Some(v) = svr {
             v
         } else {
             (0..self.servers.len() as i32).collect()
         };
+        self.indexes = svr.iter().map(|i| *i as usize).collect();
 
         // connect to server
         let mut f = vec![];
         for s in svr {
-            let addr = format!("http://{}", self.servers[s as usize].clone());
-            let dst = Endpoint::try_from(addr)?;
+            let dst = tls::endpoint(&self.servers[s as usize], self.tls.as_ref())?;
             let client = PaxosClient::connect(dst);
             f.push(client);
         }
         let clients = join_all(f).await;
         let mut acc = vec![];
-        for res in clients {
-            match res {
-                Ok(c) => acc.push(c),
-                Err(_) => {}
-            }
+        for c in clients.into_iter().flatten() {
+            acc.push(c);
         }
 
-        self.phase1_with_client(acc).await
+        Ok(self.phase1_with_client(acc).await?)
     }
 
+    #[cfg(test)]
     async fn phase1_with_client(
         &mut self,
         clients: Vec<PaxosClient<Channel>>,
-    ) -> Result<Option<Value>, Error> {
-        if self.context.len() == 0 {
-            return Err(Error::msg("not enough quorum"));
+    ) -> Result<Option<Value>, ProposeError> {
+        // 测试按指定的 Acceptor 逐步检查状态，等待所有应答
+        let transports = clients.into_iter().map(Transport::Unary).collect();
+        let replies = self.prepare_with_transports(transports, true).await?;
+        self.resolve_phase1(replies)
+    }
+
+    #[instrument(
+        name = "phase1",
+        skip(self, transports),
+        fields(key = %self.key(), version = self.version(), ballot = %self.ballot(), trace_id = %self.trace_id)
+    )]
+    async fn phase1_with_transports(
+        &mut self,
+        transports: Vec<Transport>,
+    ) -> Result<Option<Value>, ProposeError> {
+        let replies = self.prepare_with_transports(transports, false).await?;
+        self.resolve_phase1(replies)
+    }
+
+    /// 向每个 Acceptor 发送 prepare，返回原始应答；all 为 false 时应答足以构成 phase 1 quorum 后即返回
+    async fn prepare_with_transports(
+        &mut self,
+        transports: Vec<Transport>,
+        all: bool,
+    ) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        if self.context.is_empty() {
+            self.metrics.quorum_failures.inc();
+            warn!("no acceptor connected");
+            return Err(ProposeError::NoQuorum {
+                reachable: 0,
+                needed: self.quorum().phase1_size(),
+            });
         }
 
-        // send propose to server
-        let mut f = vec![];
-        for c in clients {
-            let mut client = c;
-            let r = client.prepare(self.proposer.clone()).await;
+        // 并发发送，所有 Acceptor 共用同一个截止时间；本地 zone 的节点通常最先应答，
+        // 应答构成 phase 1 quorum 后不再等待其余节点
+        let deadline = self.rpc_timeout;
+        let calls: Vec<_> = transports
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut client)| {
+                let request = self.request();
+                let metrics = self.metrics.clone();
+                async move {
+                    let start = Instant::now();
+                    let r = within(deadline, client.prepare(request)).await;
+                    metrics.observe("prepare", start);
+                    r
+                }
+                .instrument(info_span!("rpc", method = "prepare", acceptor = i))
+            })
+            .collect();
+        let quorum = self.quorum();
+        let mut replies = vec![];
+        let mut reachable = vec![];
+        let mut error = None;
+        until_enough(calls, |i, r| {
             match r {
-                Ok(resp) => {
-                    let acc = resp.get_ref();
-                    f.push(acc.clone());
+                Some(Ok(acc)) => {
+                    debug!(acceptor = i, ?acc, "prepare reply");
+                    reachable.push(self.index(i));
+                    replies.push((self.index(i), acc));
                 }
-                Err(e) => {
-                    return Err(Error::new(e));
+                Some(Err(e)) => {
+                    warn!(acceptor = i, error = %e, "prepare failed");
+                    error.get_or_insert(e);
                 }
+                None => warn!(acceptor = i, "prepare timed out"),
             }
+            !all && quorum.is_phase1_quorum(&reachable)
+        })
+        .await;
+        self.gathered_phase1(replies, error)
+    }
+
+    /// prepare 的应答不足 phase 1 quorum 时返回遇到的第一个错误，
+    /// 没有错误时由 [`Propose::check_phase1`] 报告 quorum 不足
+    pub(crate) fn gathered_phase1(
+        &self,
+        replies: Vec<(usize, Acceptor)>,
+        error: Option<ProposeError>,
+    ) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        let reachable: Vec<usize> = replies.iter().map(|(i, _)| *i).collect();
+        match error {
+            Some(e) if !self.quorum().is_phase1_quorum(&reachable) => Err(e),
+            _ => Ok(replies),
         }
+    }
 
-        // collected reply
-        let mut max_value = Acceptor {
-            round: Some(Default::default()),
-            last_round: Some(Default::default()),
-            value: None,
-        };
-        let round = self.proposer.round.clone().unwrap().number;
-        for acc in f {
-            let last_round = acc.clone().last_round.unwrap().number;
+    /// 只发送 prepare，应答构成 phase 1 quorum 后返回各 Acceptor 在本次 prepare 之前的状态
+    pub(crate) async fn prepare(&mut self) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        self.prepare_with_transports(self.transports(), false).await
+    }
+
+    /// 用标准的 Paxos 规则完成一个实例：prepare 应答中已有被接受的值时提交 round 最大的那个，
+    /// 否则提交自己的值；两者都没有时只读取，返回 None
+    pub(crate) async fn decide(mut self) -> Result<Option<Value>, ProposeError> {
+        // 只读取时先用 round 0 查看，没有已接受的值时不在 Acceptor 上留下任何状态
+        if self.proposer.value.is_none() && self.untouched().await? {
+            return Ok(None);
+        }
+        let replies = self.prepare().await?;
+        self.check_phase1(&replies)?;
+        if let Some(value) = adopt(&replies)? {
+            self.proposer.value = Some(value);
+        }
+        if self.proposer.value.is_none() {
+            return Ok(None);
+        }
+        let result = self.run_phase2().await;
+        self.finish(result)
+    }
+
+    /// 用 round 0 的 prepare 读取 phase 1 quorum 上的状态，Acceptor 不做承诺；
+    /// 都没有接受过值时返回 true，此时没有值被选定
+    async fn untouched(&mut self) -> Result<bool, ProposeError> {
+        let ballot = self.proposer.round.clone().unwrap_or_default();
+        self.set_ballot(RoundNum {
+            number: 0,
+            ..ballot.clone()
+        });
+        let replies = self.prepare().await;
+        self.set_ballot(ballot);
+        let replies = replies?;
+        self.check_phase1(&replies)?;
+        Ok(replies.iter().all(|(_, acc)| acc.value.is_none()))
+    }
+
+    /// 应答的 Acceptor 不足 phase 1 quorum 时返回 [`ProposeError::NoQuorum`]
+    pub(crate) fn check_phase1(&self, replies: &[(usize, Acceptor)]) -> Result<(), ProposeError> {
+        let quorum = self.quorum();
+        let indexes: Vec<usize> = replies.iter().map(|(i, _)| *i).collect();
+        if quorum.is_phase1_quorum(&indexes) {
+            return Ok(());
+        }
+        self.metrics.quorum_failures.inc();
+        debug!(?indexes, "prepare replies are not a quorum");
+        Err(ProposeError::NoQuorum {
+            reachable: indexes.len(),
+            needed: quorum.phase1_size(),
+        })
+    }
+
+    /// 根据 phase 1 quorum 的 prepare 应答，返回必须修复的值
+    ///
+    /// 与 [`Propose::decide`] 相同，采纳应答中 round 最大的已接受值，替换本次提议的值；
+    /// 都没有接受过值时返回 None，保留本次提议的值。
+    pub(crate) fn resolve_phase1(
+        &mut self,
+        replies: Vec<(usize, Acceptor)>,
+    ) -> Result<Option<Value>, ProposeError> {
+        self.check_phase1(&replies)?;
+        let round = self.proposer.round.clone().unwrap_or_default();
+        for (_, acc) in &replies {
+            let last_round = acc.last_round.clone().unwrap_or_default();
             if round < last_round {
-                return Err(Error::msg("last_round > round"));
+                debug!(?last_round, "preempted by a higher round");
+                return Err(ProposeError::Preempted { by: last_round });
             }
 
-            let value_round = acc.clone().round.unwrap().number;
+            let value_round = acc.round.clone().unwrap_or_default();
             if round == last_round && round > value_round {
-                return Err(Error::msg("round > value_round"));
-            }
-
-            if last_round >= max_value.clone().last_round.clone().unwrap().number {
-                max_value = acc;
+                debug!(?last_round, ?value_round, "round already prepared");
+                return Err(ProposeError::Preempted { by: last_round });
             }
         }
-        // round = value_round 修复
-        let last_round = max_value.clone().last_round.unwrap().number;
-        let value_round = max_value.clone().round.unwrap().number;
-        if round == last_round && round == value_round {
-            self.proposer.value = max_value.clone().value;
+        let value = adopt(&replies)?;
+        if value.is_some() {
+            self.proposer.value = value.clone();
         }
-        // round > last_round 更新
-        if round > last_round {
-            self.proposer.value = None;
-        }
-        let value = self.proposer.value.clone();
         Ok(value)
     }
 
+    #[cfg(test)]
     async fn phase2(&mut self, svr: Option<Vec<i32>>) -> Result<()> {
         let svr = if let Some(v) = svr {
             v
         } else {
             (0..self.servers.len() as i32).collect()
         };
+        self.indexes = svr.iter().map(|i| *i as usize).collect();
 
         // connect to server
         let mut f = vec![];
         for s in svr {
-            let addr = format!("http://{}", self.servers[s as usize].clone());
-            let dst = Endpoint::try_from(addr).unwrap();
+            let dst = tls::endpoint(&self.servers[s as usize], self.tls.as_ref()).unwrap();
             let client = PaxosClient::connect(dst);
             f.push(client);
         }
         let clients = join_all(f).await;
         let mut acc = vec![];
-        for res in clients {
-            match res {
-                Ok(c) => acc.push(c),
-                Err(_) => {}
-            }
+        for c in clients.into_iter().flatten() {
+            acc.push(c);
         }
 
-        self.phase2_with_client(acc).await
+        Ok(self.phase2_with_client(acc).await?)
     }
 
+    #[cfg(test)]
     async fn phase2_with_client(
         &mut self,
         clients: Vec<PaxosClient<Channel>>,
-    ) -> Result<(), Error> {
-        // send propose to server
-        let mut f = vec![];
-        for c in clients {
-            let mut client = c;
-            let r = client.accept(self.proposer.clone()).await;
+    ) -> Result<(), ProposeError> {
+        let transports = clients.into_iter().map(Transport::Unary).collect();
+        let replies = self.accept_with_transports(transports, true).await?;
+        self.resolve_phase2(replies)
+    }
+
+    #[instrument(
+        name = "phase2",
+        skip(self, transports),
+        fields(key = %self.key(), version = self.version(), ballot = %self.ballot(), trace_id = %self.trace_id)
+    )]
+    async fn phase2_with_transports(
+        &mut self,
+        transports: Vec<Transport>,
+    ) -> Result<(), ProposeError> {
+        let replies = self.accept_with_transports(transports, false).await?;
+        self.resolve_phase2(replies)
+    }
+
+    /// 向每个 Acceptor 发送 accept，返回原始应答；all 为 false 时应答足以构成 phase 2 quorum 后即返回
+    async fn accept_with_transports(
+        &mut self,
+        transports: Vec<Transport>,
+        all: bool,
+    ) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        let shards = match (&self.erasure, &self.proposer.value) {
+            (Some(erasure), Some(value)) => Some(erasure.encode(value)),
+            _ => None,
+        };
+        let deadline = self.rpc_timeout;
+        let calls: Vec<_> = transports
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut client)| {
+                let mut request = self.request();
+                if let Some(shard) = shards.as_ref().and_then(|s| s.get(self.index(i))) {
+                    request.get_mut().value = Some(shard.clone());
+                }
+                let metrics = self.metrics.clone();
+                async move {
+                    let start = Instant::now();
+                    let r = within(deadline, client.accept(request)).await;
+                    metrics.observe("accept", start);
+                    r
+                }
+                .instrument(info_span!("rpc", method = "accept", acceptor = i))
+            })
+            .collect();
+        let quorum = self.quorum();
+        let mut replies = vec![];
+        let mut accepted = vec![];
+        let mut timed_out = vec![];
+        let mut error = None;
+        until_enough(calls, |i, r| {
             match r {
-                Ok(resp) => {
-                    let acc = resp.get_ref();
-                    f.push(acc.clone());
+                Some(Ok(acc)) => {
+                    debug!(acceptor = i, ?acc, "accept reply");
+                    if self.accepted_by(&acc) {
+                        accepted.push(self.index(i));
+                    }
+                    replies.push((self.index(i), acc));
+                }
+                Some(Err(e)) => {
+                    warn!(acceptor = i, error = %e, "accept failed");
+                    error.get_or_insert(e);
                 }
-                Err(e) => {
-                    return Err(Error::new(e));
+                None => {
+                    warn!(acceptor = i, "accept timed out");
+                    timed_out.push(self.index(i));
                 }
             }
+            !all && quorum.is_phase2_quorum(&accepted)
+        })
+        .await;
+        self.gathered_phase2(replies, &timed_out, error)
+    }
+
+    /// accept 的应答不足 phase 2 quorum 时：超时的 Acceptor 加上接受了值的 Acceptor
+    /// 可能构成 quorum，结果未知，返回 [`ProposeError::Timeout`]；否则返回遇到的第一个错误
+    pub(crate) fn gathered_phase2(
+        &self,
+        replies: Vec<(usize, Acceptor)>,
+        timed_out: &[usize],
+        error: Option<ProposeError>,
+    ) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        let quorum = self.quorum();
+        let mut accepted: Vec<usize> = replies
+            .iter()
+            .filter(|(_, acc)| self.accepted_by(acc))
+            .map(|(i, _)| *i)
+            .collect();
+        if quorum.is_phase2_quorum(&accepted) {
+            return Ok(replies);
         }
+        accepted.extend_from_slice(timed_out);
+        if quorum.is_phase2_quorum(&accepted) {
+            return Err(ProposeError::Timeout);
+        }
+        let reachable: Vec<usize> = replies.iter().map(|(i, _)| *i).collect();
+        match error {
+            Some(e) if !quorum.is_phase2_quorum(&reachable) => Err(e),
+            _ => Ok(replies),
+        }
+    }
+
+    /// Acceptor 没有承诺更大的 round，即接受了本次 accept，或已在本次 round 中接受过值
+    fn accepted_by(&self, acc: &Acceptor) -> bool {
+        acc.last_round.clone().unwrap_or_default()
+            <= self.proposer.round.clone().unwrap_or_default()
+    }
+
+    /// 只发送 accept，等待所有 Acceptor 应答，返回各 Acceptor 在本次 accept 之前的状态
+    pub(crate) async fn accept(&mut self) -> Result<Vec<(usize, Acceptor)>, ProposeError> {
+        self.accept_with_transports(self.transports(), true).await
+    }
 
+    /// 根据 accept 应答判断值是否被 phase 2 quorum 接受
+    pub(crate) fn resolve_phase2(
+        &self,
+        replies: Vec<(usize, Acceptor)>,
+    ) -> Result<(), ProposeError> {
         // collected reply
-        let rnd = self.proposer.clone().round.clone().unwrap().number;
-        let quorum = self.servers.len() / 2 + 1;
-        let mut count = 0usize;
-        for acc in f {
+        let quorum = self.quorum();
+        let reachable: Vec<usize> = replies.iter().map(|(i, _)| *i).collect();
+        let mut accepted = vec![];
+        let mut by = RoundNum::default();
+        for (i, acc) in replies {
             // 有其他更大的 round 请求，本次请求失败
-            if acc.clone().last_round.unwrap().number <= rnd {
-                // 本次请求有效，记录有效节点数
-                count += 1;
-                if count >= quorum {
-                    // 多数派同意请求
+            if self.accepted_by(&acc) {
+                // 本次请求有效，记录有效节点
+                accepted.push(i);
+                if quorum.is_phase2_quorum(&accepted) {
                     return Ok(());
                 }
+            } else {
+                let last_round = acc.last_round.unwrap_or_default();
+                if last_round > by {
+                    by = last_round;
+                }
             }
         }
 
-        Err(Error::msg("not enough quorum"))
+        self.metrics.quorum_failures.inc();
+        debug!(?accepted, "accept rejected by quorum");
+        if !quorum.is_phase2_quorum(&reachable) {
+            Err(ProposeError::NoQuorum {
+                reachable: reachable.len(),
+                needed: quorum.phase2_size(),
+            })
+        } else {
+            Err(ProposeError::Preempted { by })
+        }
+    }
+
+    /// 未设置时为所有节点上的多数派
+    pub(crate) fn quorum(&self) -> Arc<dyn QuorumSystem> {
+        self.quorum
+            .clone()
+            .unwrap_or_else(|| Arc::new(Majority::new(self.servers.len())))
+    }
+
+    /// 第 i 个连接对应的 Acceptor 下标
+    fn index(&self, i: usize) -> usize {
+        self.indexes.get(i).copied().unwrap_or(i)
     }
 
     #[cfg(test)]
@@ -186,15 +557,138 @@ impl Propose {
         Ok(())
     }
 
-    pub async fn run(&mut self) -> Result<Option<Value>> {
-        let v = self.phase1_with_client(self.context.clone()).await?;
-        self.proposer.value = if let Some(_) = v.clone() {
-            v // 修复
-        } else {
-            self.proposer.value.clone() // 更新
+    pub(crate) fn set_ballot(&mut self, round: RoundNum) {
+        self.proposer.round = Some(round);
+    }
+
+    pub(crate) fn set_value(&mut self, value: Value) {
+        self.proposer.value = Some(value);
+    }
+
+    /// 请求中带上的配置 epoch，见 [`Client::reconfigure`]
+    pub(crate) fn set_epoch(&mut self, epoch: i64) {
+        self.proposer.epoch = epoch;
+    }
+
+    /// 设置 Paxos 实例的版本号
+    pub fn set_version(&mut self, version: i64) {
+        if let Some(id) = self.proposer.id.as_mut() {
+            id.version = version;
+        }
+    }
+
+    /// 设置本次提议使用的 round 编号
+    pub fn set_round(&mut self, number: i64) {
+        if let Some(round) = self.proposer.round.as_mut() {
+            round.number = number;
+        }
+    }
+
+    /// 只执行 phase 1，返回需要修复的值；没有需要修复的值时保留自己的值
+    pub async fn run_phase1(&mut self) -> Result<Option<Value>, ProposeError> {
+        self.phase1_with_transports(self.transports()).await
+    }
+
+    /// 处理批量 prepare 中本实例的应答，语义同 [`Propose::run_phase1`]
+    pub(crate) fn prepared(
+        &mut self,
+        replies: Vec<(usize, Acceptor)>,
+    ) -> Result<Option<Value>, ProposeError> {
+        self.resolve_phase1(replies)
+    }
+
+    pub(crate) fn proposer(&self) -> &Proposer {
+        &self.proposer
+    }
+
+    /// 本次提议使用的连接及其 Acceptor 下标，已按 zone 排序并排除了见证者
+    pub(crate) fn acceptors(&self) -> Vec<(usize, PaxosClient<Channel>)> {
+        self.context
+            .iter()
+            .enumerate()
+            .map(|(i, client)| (self.index(i), client.clone()))
+            .collect()
+    }
+
+    /// 只执行 phase 2，提交当前的值
+    pub async fn run_phase2(&mut self) -> Result<(), ProposeError> {
+        self.phase2_with_transports(self.transports()).await
+    }
+
+    #[instrument(
+        name = "propose",
+        skip(self),
+        fields(key = %self.key(), version = self.version(), ballot = %self.ballot(), trace_id = %self.trace_id)
+    )]
+    pub async fn run(&mut self) -> Result<Option<Value>, ProposeError> {
+        let deadline = self.deadline;
+        let metrics = self.metrics.clone();
+        let run = async {
+            let result = async {
+                self.run_phase1().await?;
+                self.run_phase2().await
+            }
+            .await;
+            self.finish(result)
+        };
+        match within(deadline, run).await {
+            Some(result) => result,
+            None => Err(unknown(&metrics)),
+        }
+    }
+
+    /// 整个提议的截止时间，超时返回 [`ProposeError::Timeout`]
+    pub fn set_deadline(&mut self, deadline: Duration) {
+        self.deadline = Some(deadline);
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// 单个 Prepare/Accept 的超时，同时作为 gRPC 超时发给 Acceptor
+    pub fn set_rpc_timeout(&mut self, timeout: Duration) {
+        self.rpc_timeout = Some(timeout);
+    }
+
+    /// 统计提议的结果，成功时返回被选定的值
+    pub(crate) fn finish(
+        &self,
+        result: Result<(), ProposeError>,
+    ) -> Result<Option<Value>, ProposeError> {
+        let label = match &result {
+            Ok(_) => "chosen",
+            Err(ProposeError::Timeout) => "unknown",
+            Err(_) => "failed",
         };
-        self.phase2_with_client(self.context.clone()).await?;
-        Ok(self.proposer.value.clone())
+        self.metrics.proposals.with_label_values(&[label]).inc();
+        // 下一个 ballot 越过抢占者的 round
+        if let (Err(ProposeError::Preempted { by }), Some(ballots)) = (&result, &self.ballots) {
+            ballots.observe(by);
+        }
+        if let (Ok(_), Some(learners), Some(value)) =
+            (&result, &self.learners, &self.proposer.value)
+        {
+            let round = self.proposer.round.clone().unwrap_or_default();
+            learners.publish(self.key().to_string(), self.version(), round, value.clone());
+        }
+        match &result {
+            Ok(_) => debug!(value = ?self.proposer.value, "value chosen"),
+            Err(e) => warn!(error = %e, "proposal failed"),
+        }
+        result.map(|_| self.proposer.value.clone())
+    }
+
+    /// 有双向流时走流，否则每次请求一个 unary 调用
+    fn transports(&self) -> Vec<Transport> {
+        self.context
+            .iter()
+            .enumerate()
+            .map(|(i, client)| match self.streams.get(i).cloned().flatten() {
+                Some(stream) => Transport::Stream(stream),
+                None => Transport::Unary(client.clone()),
+            })
+            .collect()
     }
 
     /// 临时函数，设置连接 [`Acceptor`] 的 [`PaxosClient`]
@@ -204,12 +698,76 @@ impl Propose {
     }
 }
 
+/// 把见证者的摘要或 RS-Paxos 的分片换成完整的值
+/// prepare 应答中 round 最大的已接受值，可能已被选定，新的提议必须沿用
+///
+/// 分片不足的值不可能已被选定，RS-Paxos 沿用 round 最大的可恢复的值。
+fn adopt(replies: &[(usize, Acceptor)]) -> Result<Option<Value>, ProposeError> {
+    let mut accepted: Vec<(RoundNum, Value)> = replies
+        .iter()
+        .filter_map(|(_, acc)| {
+            let round = acc.round.clone().unwrap_or_default();
+            acc.value.clone().map(|v| (round, v))
+        })
+        .collect();
+    accepted.sort_by(|(a, _), (b, _)| b.cmp(a));
+    for (_, value) in accepted {
+        if value.shard.is_some() && full_value(value.clone(), replies).is_err() {
+            continue;
+        }
+        return full_value(value, replies).map(Some);
+    }
+    Ok(None)
+}
+
+fn full_value(value: Value, replies: &[(usize, Acceptor)]) -> Result<Value, ProposeError> {
+    match &value.shard {
+        Some(shard) => erasure::rebuild(shard, replies).ok_or(ProposeError::ValueUnavailable),
+        None => witness::restore(value, replies),
+    }
+}
+
+/// 提议超过截止时间，统计并返回 [`ProposeError::Timeout`]
+pub(crate) fn unknown(metrics: &ProposerMetrics) -> ProposeError {
+    metrics.proposals.with_label_values(&["unknown"]).inc();
+    warn!("proposal deadline exceeded, outcome unknown");
+    ProposeError::Timeout
+}
+
 #[derive(Debug, Default)]
 pub struct Client {
     id: i64,
     servers: Vec<String>,
-    acceptors: Vec<PaxosClient<Channel>>,
+    conns: Option<ConnectionManager>,
+    #[cfg_attr(not(test), allow(dead_code))]
     propose: Propose,
+    metrics: Arc<ProposerMetrics>,
+    tls: Option<ClientTlsConfig>,
+    token: Option<String>,
+    /// 通知 Learner 时使用的节点 token，未设置时使用 token
+    node_token: Option<String>,
+    batch_window: Option<Duration>,
+    batcher: Option<Batcher>,
+    streaming: bool,
+    rpc_timeout: Option<Duration>,
+    propose_timeout: Option<Duration>,
+    ballots: Arc<BallotAllocator>,
+    ballot_scheme: BallotScheme,
+    /// 当前生效的配置，servers 与其一致
+    membership: Membership,
+    quorum: Option<Arc<dyn QuorumSystem>>,
+    /// 与 servers 一一对应，每个节点所在的 zone
+    zones: Vec<String>,
+    /// 本 Proposer 所在的 zone，其中的节点最先发送
+    local_zone: Option<String>,
+    /// 见证者在 servers 中的下标，只在完整的 Acceptor 不足 quorum 时使用
+    witnesses: Vec<usize>,
+    erasure: Option<Arc<Erasure>>,
+    /// 不参与投票的 Learner 的地址
+    learner_addrs: Vec<String>,
+    learners: Option<Learners>,
+    /// 本 Proposer 在每个 key 上最近发起的 EPaxos 实例
+    epaxos_last: Arc<Mutex<HashMap<String, EInstanceId>>>,
 }
 
 impl Client {
@@ -217,168 +775,881 @@ impl Client {
         Client {
             id,
             propose: Propose::new(servers.clone(), "key".to_string(), None, id),
+            rpc_timeout: Some(DEFAULT_RPC_TIMEOUT),
+            ballots: Arc::new(BallotAllocator::new(id)),
+            membership: Membership {
+                epoch: 0,
+                servers: servers.clone(),
+                zones: vec![],
+            },
             servers,
             ..Default::default()
         }
     }
 
+    /// 把已用过的 ballot 记录在 path 中，重启后不会重用
+    pub fn set_ballot_file(&mut self, path: &Path) -> Result<()> {
+        let ballots = BallotAllocator::open(path, self.id)?;
+        self.ballots = Arc::new(ballots.with_scheme(self.ballot_scheme));
+        Ok(())
+    }
+
+    /// 设置 ballot 编号的来源，默认从 0 开始计数
+    pub fn set_ballot_scheme(&mut self, scheme: BallotScheme) {
+        self.ballot_scheme = scheme;
+        self.ballots = Arc::new(self.ballots.with_scheme(scheme));
+    }
+
+    /// 连接所有节点，之后在后台维护连接；就绪的节点不足多数派时返回错误
     pub async fn connect(&mut self) -> Result<()> {
-        // connect to server
-        let mut f = vec![];
-        for s in self.servers.clone() {
-            let addr = format!("http://{}", s.clone());
-            let dst = Endpoint::try_from(addr).unwrap();
-            let client = PaxosClient::connect(dst);
-            f.push(client);
+        let conns = ConnectionManager::start(
+            self.servers.clone(),
+            self.tls.clone(),
+            self.streaming,
+            self.token.clone(),
+        )
+        .await;
+        if let Some(window) = self.batch_window {
+            self.batcher = Some(Batcher::new(
+                self.metrics.clone(),
+                self.token.clone(),
+                self.rpc_timeout,
+                window,
+            ));
         }
-        let results = join_all(f).await;
-        let quorum = self.servers.len() / 2 + 1;
-        let mut acceptors = vec![];
-        for acc in results {
-            match acc {
-                Ok(c) => {
-                    acceptors.push(c);
-                }
-                Err(_) => {}
-            }
+        if !self.learner_addrs.is_empty() {
+            self.learners = Some(Learners::new(
+                &self.learner_addrs,
+                self.tls.as_ref(),
+                self.node_token.clone().or_else(|| self.token.clone()),
+                self.rpc_timeout,
+            )?);
         }
-        self.acceptors = acceptors.clone();
-        if acceptors.len() >= quorum {
+        let reachable: Vec<usize> = conns.connected().iter().map(|c| c.index).collect();
+        self.conns = Some(conns);
+        let quorum = self.quorum_system();
+        if quorum.is_phase1_quorum(&reachable) && quorum.is_phase2_quorum(&reachable) {
             Ok(())
         } else {
-            Err(Error::msg("not enough quorum"))
+            Err(ProposeError::NoQuorum {
+                reachable: reachable.len(),
+                needed: std::cmp::max(quorum.phase1_size(), quorum.phase2_size()),
+            }
+            .into())
         }
     }
 
-    pub async fn run_propose(
-        &mut self,
-        key: String,
-        value: Option<Value>,
-    ) -> Result<Option<Value>> {
-        let mut prop = Propose::new(self.servers.clone(), key, value.clone(), self.id);
-        prop.set_context(self.acceptors.clone())?;
-        return prop.run().await;
+    /// 使用 quorum 判断 prepare 和 accept 是否成功，默认为多数派
+    ///
+    /// quorum 中的下标对应 servers 中的位置，覆盖的节点数必须与 servers 相同。
+    pub fn set_quorum(&mut self, quorum: impl QuorumSystem + 'static) -> Result<()> {
+        if quorum.acceptors() != self.servers.len() {
+            return Err(Error::msg(format!(
+                "quorum {:?} covers {} acceptors but there are {} servers",
+                quorum,
+                quorum.acceptors(),
+                self.servers.len()
+            )));
+        }
+        self.quorum = Some(Arc::new(quorum));
+        Ok(())
     }
 
-    #[cfg(test)]
-    async fn phase1(&mut self, svr: Option<Vec<i32>>) -> Result<Option<Value>> {
-        return self.propose.phase1(svr).await;
+    /// 为每个节点标注所在的 zone，与 servers 一一对应；local 中的节点最先收到请求
+    ///
+    /// 需要按 zone 计算 quorum 时另外通过 [`Client::set_quorum`] 设置 [`crate::ZoneQuorum`]。
+    pub fn set_zones(&mut self, zones: Vec<String>, local: Option<String>) -> Result<()> {
+        if zones.len() != self.servers.len() {
+            return Err(Error::msg(format!(
+                "{} zones for {} servers",
+                zones.len(),
+                self.servers.len()
+            )));
+        }
+        self.membership.zones = zones.clone();
+        self.zones = zones;
+        self.local_zone = local;
+        Ok(())
     }
 
-    #[cfg(test)]
-    async fn phase2(&mut self, svr: Option<Vec<i32>>) -> Result<()> {
-        return self.propose.phase2(svr).await;
+    /// 就绪的节点，本地 zone 中的排在前面
+    fn connected(&self) -> Vec<Connection> {
+        let mut conns = self
+            .conns
+            .as_ref()
+            .map(|c| c.connected())
+            .unwrap_or_default();
+        if let Some(local) = &self.local_zone {
+            conns.sort_by_key(|c| self.zones.get(c.index) != Some(local));
+        }
+        if !self.witnesses.is_empty() {
+            let full: Vec<usize> = conns
+                .iter()
+                .map(|c| c.index)
+                .filter(|i| !self.witnesses.contains(i))
+                .collect();
+            let quorum = self.quorum_system();
+            if quorum.is_phase1_quorum(&full) && quorum.is_phase2_quorum(&full) {
+                conns.retain(|c| !self.witnesses.contains(&c.index));
+            }
+        }
+        conns
     }
 
-    #[cfg(test)]
-    fn set_proposer(&mut self, proposer: Proposer) -> Result<()> {
-        self.propose.set_proposer(proposer)
+    /// 值被选定后通知这些 Learner，需在 [`Client::connect`] 之前设置
+    ///
+    /// Learner 不在 servers 中，不参与 prepare 和 accept，也不计入 quorum；通知失败只记录日志。
+    pub fn set_learners(&mut self, learners: Vec<String>) {
+        self.learner_addrs = learners;
     }
 
-    #[cfg(test)]
-    fn proposer(&mut self) -> Proposer {
-        self.propose.proposer.clone()
+    /// 等待已发出的 Learner 通知完成，例如在进程退出之前
+    pub async fn flush_learners(&self) {
+        if let Some(learners) = &self.learners {
+            learners.flush().await;
+        }
     }
-}
-
-#[cfg(test)]
-mod test {
-    // use super::*;
-    use crate::*;
-    use anyhow::Result;
-    use scopeguard::defer;
-    use std::thread::JoinHandle;
-    use tonic::transport::Server;
-    use tonic::Request;
-    use triggered::{Listener, Trigger};
 
-    #[tokio::main]
-    async fn serve(signal: Listener, address: &str) -> Result<(), tonic::transport::Error> {
-        let addr = address.parse().unwrap();
-
-        println!("PaxosServer listening on: {}", addr);
+    /// 开启 RS-Paxos：值被编码为与 Acceptor 数相同的分片，每个 Acceptor 只保存一个分片，
+    /// 任意 data_shards 个分片可以恢复值
+    ///
+    /// quorum 随之调整为 ⌈(n + data_shards) / 2⌉，保证任意两个 quorum 至少有 data_shards 个共同的 Acceptor。
+    /// 会替换已设置的 quorum，成员变更后需要重新设置；开启后不再合并批量提议。
+    pub fn set_erasure_coding(&mut self, data_shards: usize) -> Result<()> {
+        let erasure = Erasure::new(data_shards, self.servers.len())?;
+        self.quorum = Some(Arc::new(erasure.quorum()?));
+        self.erasure = Some(Arc::new(erasure));
+        Ok(())
+    }
 
-        let service = PaxosService {
-            storage: Default::default(),
-        };
+    /// 把 servers 中下标为 witnesses 的节点作为见证者
+    ///
+    /// 见证者只保存 ballot 和值的摘要，仍计入 quorum；完整的 Acceptor 足以构成 quorum 时不向见证者发送请求。
+    /// 任一 quorum 都须包含至少一个完整的 Acceptor，否则被选定的值可能无法恢复。
+    pub fn set_witnesses(&mut self, witnesses: Vec<usize>) {
+        self.witnesses = witnesses;
+    }
 
-        let svc = PaxosServer::new(service);
+    /// 当前使用的 quorum，没有设置时为多数派
+    pub fn quorum_system(&self) -> Arc<dyn QuorumSystem> {
+        self.quorum
+            .clone()
+            .unwrap_or_else(|| Arc::new(Majority::new(self.servers.len())))
+    }
 
-        Server::builder()
-            .add_service(svc)
-            .serve_with_shutdown(addr, async {
-                signal.await;
-            })
-            .await?;
+    /// 当前生效的配置
+    pub fn membership(&self) -> &Membership {
+        &self.membership
+    }
 
-        println!("PaxosServer {} exit.", addr);
-        Ok(())
+    /// 通过 Paxos 把成员变更为 servers，由当前配置的多数派选定 epoch + 1 的配置
+    ///
+    /// 新配置被选定后，先让两个配置中的 Acceptor 拒绝更早 epoch 的请求，再把旧配置中的每个实例复制到新配置，
+    /// 完成后才切换过去。返回该 epoch 被选定的配置；其他 Proposer 先完成了变更时返回的不是 servers。
+    /// 节点沿用在当前配置中的 zone，有新节点时新配置不区分 zone。
+    pub async fn reconfigure(&mut self, servers: Vec<String>) -> Result<Membership> {
+        self.refresh().await?;
+        let epoch = self.membership.epoch + 1;
+        let zones = servers
+            .iter()
+            .map(|s| self.zone_of(s))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        let proposal = Membership {
+            epoch,
+            servers,
+            zones,
+        };
+        // 当前的 quorum 无法用于新配置时不发起变更
+        self.successor(&proposal, true)?;
+        let key = membership::key(epoch);
+        match self.decide_membership(key, Some(proposal)).await? {
+            Some(chosen) => {
+                self.activate(&chosen).await?;
+                self.install(chosen.clone()).await?;
+                Ok(chosen)
+            }
+            None => Err(Error::msg("no membership chosen")),
+        }
     }
 
-    fn start_server(signal: Listener, address: String) -> JoinHandle<()> {
-        let handler = std::thread::spawn(move || {
-            let _ = serve(signal, address.as_str());
-        });
-        std::thread::sleep(std::time::Duration::from_millis(10));
-        handler
+    /// 依次读取之后的 epoch，切换到最新的配置；尚未激活的配置由本 Proposer 完成复制后再切换
+    pub async fn refresh(&mut self) -> Result<()> {
+        loop {
+            let epoch = self.membership.epoch + 1;
+            match self.decide_membership(membership::key(epoch), None).await? {
+                Some(next) => {
+                    let active = self
+                        .decide_membership(membership::active_key(epoch), None)
+                        .await?;
+                    if active.is_none() {
+                        self.activate(&next).await?;
+                    }
+                    self.install(next).await?
+                }
+                None => return Ok(()),
+            }
+        }
     }
 
-    const BASE_PORT: i32 = 11030;
+    /// 节点在当前配置中的 zone
+    fn zone_of(&self, server: &str) -> Option<String> {
+        let i = self.servers.iter().position(|s| s == server)?;
+        self.zones.get(i).cloned()
+    }
 
-    struct TestServer {
-        count: i32,
-        triggers: Vec<Trigger>,
+    /// 对一个成员变更实例执行一轮 Paxos，被抢占时换更大的 ballot 重试
+    async fn decide_membership(
+        &self,
+        key: String,
+        proposal: Option<Membership>,
+    ) -> Result<Option<Membership>, ProposeError> {
+        self.retry(|| {
+            let prop = self.instance_propose(key.clone(), None);
+            let proposal = proposal.clone();
+            async move { membership::decide(prop?, proposal).await }
+        })
+        .await
     }
 
-    impl TestServer {
-        pub(crate) fn new(count: i32) -> Self {
-            TestServer {
-                count,
-                triggers: Default::default(),
+    /// 被抢占时重新执行 f，最多重试 [`MAX_RETRIES`] 次
+    async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T, ProposeError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ProposeError>>,
+    {
+        let mut retries = 0;
+        loop {
+            match f().await {
+                Err(ProposeError::Preempted { .. }) if retries < MAX_RETRIES => {
+                    retries += 1;
+                    self.metrics.retries.inc();
+                }
+                result => return result,
             }
         }
+    }
 
-        pub(crate) fn start(&mut self) -> Result<()> {
-            for i in 0..self.count {
-                let port = BASE_PORT + i;
-                let addr = format!("[::1]:{}", port);
-                let (trigger, signal) = triggered::trigger();
-                start_server(signal, addr);
-                self.triggers.push(trigger);
+    /// 让 next 中的节点追上当前配置，完成后在当前配置中选定 next 的激活标记
+    ///
+    /// 两个配置中的 Acceptor 先记录 next 的 epoch，之后旧配置中的 Proposer 无法再选定值；
+    /// 再通过当前配置的 quorum 读出每个实例，写入 next。可以重复执行，中途失败时由之后的
+    /// [`Client::refresh`] 继续完成。
+    ///
+    /// Acceptor 只接受已接受过其配置实例的 epoch，新配置中先写入 next 本身再通知 epoch。
+    /// fence 和列出实例经由 Peer 服务，需要节点的身份，见 [`Client::set_node_token`]。
+    async fn activate(&self, next: &Membership) -> Result<()> {
+        let mut successor = self.successor(next, false)?;
+        successor.connect().await?;
+        self.fence(next.epoch).await?;
+        let config = membership::key(next.epoch);
+        let value = Value {
+            membership: Some(next.clone()),
+            ..Default::default()
+        };
+        successor.copy(&config, 0, value).await?;
+        successor.fence(next.epoch).await?;
+
+        let mut copied = 0;
+        for stored in self.list_keys().await? {
+            let (key, version) = split_instance_key(&stored);
+            // 激活标记在复制完成后才选定，next 本身已经写入
+            if key == membership::active_key(next.epoch) || key == config {
+                continue;
+            }
+            let value = self
+                .retry(|| {
+                    let prop = self.instance_propose(key.clone(), None);
+                    async move {
+                        let mut prop = prop?;
+                        prop.set_version(version);
+                        prop.set_epoch(next.epoch);
+                        prop.decide().await
+                    }
+                })
+                .await?;
+            if let Some(value) = value {
+                successor.copy(&key, version, value).await?;
+                copied += 1;
             }
-            Ok(())
         }
+        info!(epoch = next.epoch, copied, "new members caught up");
+        self.decide_membership(membership::active_key(next.epoch), Some(next.clone()))
+            .await?;
+        Ok(())
+    }
 
-        pub(crate) fn stop(&mut self) -> Result<()> {
-            for t in &self.triggers {
-                t.trigger();
-                std::thread::sleep(std::time::Duration::from_millis(10));
+    /// 成员变更时把旧配置中的值写入本配置
+    async fn copy(&self, key: &str, version: i64, value: Value) -> Result<(), ProposeError> {
+        self.retry(|| {
+            let prop = self.instance_propose(key.to_string(), Some(value.clone()));
+            async move {
+                let mut prop = prop?;
+                prop.set_version(version);
+                prop.run().await
             }
-            self.triggers.clear();
-            Ok(())
-        }
+        })
+        .await?;
+        Ok(())
     }
 
-    fn server_address(count: i32) -> Vec<String> {
-        let mut addrs = vec![];
-        for i in 0..count {
-            let port = BASE_PORT + i;
-            let addr = format!("[::1]:{}", port);
-            addrs.push(addr);
-        }
-        addrs
+    /// 本配置中所有可达的 Acceptor，包括见证者
+    fn all_connected(&self) -> Vec<Connection> {
+        self.conns
+            .as_ref()
+            .map(|c| c.connected())
+            .unwrap_or_default()
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
-    async fn test_single_propose_single_server() {
-        let (trigger, signal) = triggered::trigger();
-        defer! {
-            trigger.trigger();
-            std::thread::sleep(std::time::Duration::from_millis(10));
+    /// 发给 Peer 服务的请求，带上节点 token 和超时；没有设置节点 token 时使用 token
+    fn node_request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(token) = self.node_token.as_ref().or(self.token.as_ref()) {
+            auth::bearer(&mut request, token);
+        }
+        if let Some(timeout) = self.rpc_timeout {
+            grpc_timeout(&mut request, timeout);
         }
-        let _ = start_server(signal, "[::1]:11038".to_string());
+        request
+    }
 
-        let res = PaxosClient::connect("http://[::1]:11038").await;
+    /// 让本配置中的 Acceptor 拒绝 epoch 之前的请求，确认的 Acceptor 须同时构成 phase 1 和 phase 2 quorum
+    async fn fence(&self, epoch: i64) -> Result<(), ProposeError> {
+        let calls = self.all_connected().into_iter().map(|conn| {
+            let (index, mut client) = (conn.index, PeerClient::new(conn.channel));
+            let request = self.node_request(FenceRequest { epoch });
+            async move {
+                let r = within(self.rpc_timeout, client.fence(request)).await;
+                (index, r)
+            }
+        });
+        let mut fenced = vec![];
+        for (index, r) in join_all(calls).await {
+            match r {
+                Some(Ok(_)) => fenced.push(index),
+                Some(Err(e)) => warn!(acceptor = index, error = %e, "fence failed"),
+                None => warn!(acceptor = index, "fence timed out"),
+            }
+        }
+        let quorum = self.quorum_system();
+        if quorum.is_phase1_quorum(&fenced) && quorum.is_phase2_quorum(&fenced) {
+            return Ok(());
+        }
+        self.metrics.quorum_failures.inc();
+        Err(ProposeError::NoQuorum {
+            reachable: fenced.len(),
+            needed: std::cmp::max(quorum.phase1_size(), quorum.phase2_size()),
+        })
+    }
+
+    /// 本配置中的所有实例的存储 key；应答的 Acceptor 须构成 phase 1 quorum，才能包含每个已选定的实例
+    async fn list_keys(&self) -> Result<Vec<String>, ProposeError> {
+        let calls = self.all_connected().into_iter().map(|conn| {
+            let (index, mut client) = (conn.index, PeerClient::new(conn.channel));
+            let request = self.node_request(ListKeysRequest {
+                prefix: String::new(),
+            });
+            async move {
+                let r = within(self.rpc_timeout, client.list_keys(request)).await;
+                (index, r)
+            }
+        });
+        let mut listed = vec![];
+        let mut keys = vec![];
+        for (index, r) in join_all(calls).await {
+            match r {
+                Some(Ok(reply)) => {
+                    listed.push(index);
+                    keys.extend(reply.into_inner().keys);
+                }
+                Some(Err(e)) => warn!(acceptor = index, error = %e, "list keys failed"),
+                None => warn!(acceptor = index, "list keys timed out"),
+            }
+        }
+        let quorum = self.quorum_system();
+        if !quorum.is_phase1_quorum(&listed) {
+            self.metrics.quorum_failures.inc();
+            return Err(ProposeError::NoQuorum {
+                reachable: listed.len(),
+                needed: quorum.phase1_size(),
+            });
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// 为 membership 构造一个 Client，沿用本 Client 的设置，重新计算与节点列表相关的状态
+    ///
+    /// erasure 按新的节点数重新设置；自定义的 quorum 能按新的节点和 zone 重建时重建，
+    /// 否则 strict 时返回错误，不 strict 时退回多数派；见证者按地址对应到新的下标。
+    fn successor(&self, membership: &Membership, strict: bool) -> Result<Client> {
+        let servers = membership.servers.clone();
+        let mut next = Client {
+            id: self.id,
+            propose: Propose::new(servers.clone(), "key".to_string(), None, self.id),
+            servers: servers.clone(),
+            membership: membership.clone(),
+            zones: membership.zones.clone(),
+            local_zone: self.local_zone.clone(),
+            witnesses: self
+                .witnesses
+                .iter()
+                .filter_map(|i| self.servers.get(*i))
+                .filter_map(|addr| servers.iter().position(|s| s == addr))
+                .collect(),
+            metrics: self.metrics.clone(),
+            tls: self.tls.clone(),
+            token: self.token.clone(),
+            node_token: self.node_token.clone(),
+            batch_window: self.batch_window,
+            streaming: self.streaming,
+            rpc_timeout: self.rpc_timeout,
+            propose_timeout: self.propose_timeout,
+            ballots: self.ballots.clone(),
+            ballot_scheme: self.ballot_scheme,
+            learner_addrs: self.learner_addrs.clone(),
+            epaxos_last: self.epaxos_last.clone(),
+            ..Default::default()
+        };
+        next.propose.tls = self.tls.clone();
+        if let Some(erasure) = &self.erasure {
+            next.set_erasure_coding(erasure.data_shards())?;
+        } else if let Some(quorum) = &self.quorum {
+            match quorum
+                .resize(servers.len(), &membership.zones)
+                .filter(|quorum| quorum.acceptors() == servers.len())
+            {
+                Some(quorum) => next.quorum = Some(quorum),
+                None if strict => {
+                    return Err(Error::msg(format!(
+                        "quorum {:?} cannot be rebuilt for {} servers",
+                        quorum,
+                        servers.len()
+                    )))
+                }
+                None => warn!(?quorum, "quorum cannot be rebuilt, using majority"),
+            }
+        }
+        Ok(next)
+    }
+
+    /// 切换到新的配置：重建 quorum、erasure、zone 和见证者，重新连接其中的节点
+    async fn install(&mut self, membership: Membership) -> Result<()> {
+        info!(epoch = membership.epoch, servers = ?membership.servers, "membership changed");
+        *self = self.successor(&membership, false)?;
+        self.connect().await
+    }
+
+    /// 作为协调者为 key 打开一个 fast round：用新的 ballot 完成 prepare，
+    /// 之后客户端通过 [`Client::fast_propose`] 跳过 phase 1
+    pub async fn open_fast_round(&self, key: String) -> Result<FastRound, ProposeError> {
+        self.check_fast()?;
+        let mut prop = self.new_propose(key.clone(), None)?;
+        let round = prop.proposer().round.clone().unwrap_or_default();
+        let replies = prop.prepare().await?;
+        prop.check_phase1(&replies)?;
+        // 更大的 round 已经 prepare 过，fast round 中的 accept 都会被拒绝
+        let by = replies
+            .into_iter()
+            .filter_map(|(_, acc)| acc.last_round)
+            .find(|last_round| *last_round > round);
+        if let Some(by) = by {
+            self.ballots.observe(&by);
+            return Err(ProposeError::Preempted { by });
+        }
+        Ok(FastRound { key, round })
+    }
+
+    /// 在 fast round 中直接向 Acceptor 提交 value，被 fast quorum 接受时一个来回即被选定；
+    /// 冲突时换新的 ballot 执行 classic round。返回被选定的值
+    pub async fn fast_propose(
+        &self,
+        fast: &FastRound,
+        value: Value,
+    ) -> Result<Option<Value>, ProposeError> {
+        self.check_fast()?;
+        let mut prop = self.new_propose(fast.key.clone(), Some(value.clone()))?;
+        prop.set_ballot(fast.round.clone());
+        let replies = prop.accept().await?;
+        let n = self.servers.len();
+        let accepted = replies
+            .iter()
+            .filter(|(_, acc)| fast::accepted(&fast.round, acc))
+            .count();
+        if accepted >= fast::fast_quorum(n) {
+            return prop.finish(Ok(()));
+        }
+
+        debug!(
+            accepted,
+            "fast round collision, falling back to classic round"
+        );
+        self.metrics.retries.inc();
+        self.ballots.observe(&fast.round);
+        let prop = self.new_propose(fast.key.clone(), Some(value))?;
+        fast::recover(prop, &fast.round, n).await
+    }
+
+    /// fast quorum 按全部节点的多数派推导，设置了 quorum、见证者或纠删码时不可用
+    fn check_fast(&self) -> Result<(), ProposeError> {
+        let reason = if self.erasure.is_some() {
+            "erasure coding".to_string()
+        } else if !self.witnesses.is_empty() {
+            "witnesses".to_string()
+        } else if let Some(quorum) = &self.quorum {
+            format!("quorum {:?}", quorum)
+        } else {
+            return Ok(());
+        };
+        Err(ProposeError::Unsupported(format!(
+            "fast round with {}",
+            reason
+        )))
+    }
+
+    /// 以 EPaxos 提交写入 key 的命令，不经过 leader
+    ///
+    /// 与其他 Proposer 在同一 key 上没有并发的命令时，fast quorum 给出相同的依赖，一个来回即选定；
+    /// 有冲突时合并各 Acceptor 看到的依赖，再由多数派 accept。提交通知被多数派收到后才返回。
+    pub async fn epaxos_propose(&self, key: String, value: Value) -> Result<ECommit, ProposeError> {
+        let slot = self
+            .ballots
+            .next()
+            .map_err(|e| ProposeError::Ballot(e.to_string()))?
+            .number;
+        let id = EInstanceId {
+            replica: self.id,
+            slot,
+        };
+        // 本 Proposer 在同一 key 上的命令依次依赖，Acceptor 只需记录每个 Proposer 最新的实例
+        let previous = self
+            .epaxos_last
+            .lock()
+            .unwrap()
+            .insert(key.clone(), id.clone());
+        let command = epaxos::command(id, key, value, previous.into_iter().collect());
+        self.epaxos_leader().propose(command).await
+    }
+
+    /// key 上已提交的 EPaxos 命令，按执行顺序排列
+    ///
+    /// 从多数派读取；依赖的命令的发起者失联时，由本客户端以更大的 ballot 恢复后再排序。
+    pub async fn epaxos_log(&self, key: &str) -> Result<Vec<ECommand>, ProposeError> {
+        self.epaxos_leader().committed(key).await
+    }
+
+    fn epaxos_leader(&self) -> epaxos::Leader {
+        epaxos::Leader {
+            clients: self
+                .connected()
+                .into_iter()
+                .map(|c| (c.index, EpaxosClient::new(c.channel)))
+                .collect(),
+            n: self.servers.len(),
+            quorum: self.quorum_system(),
+            metrics: self.metrics.clone(),
+            ballots: self.ballots.clone(),
+            token: self.token.clone(),
+            rpc_timeout: self.rpc_timeout,
+        }
+    }
+
+    /// 提议并返回被选定的值，开启批量时与窗口内的其他提议合并发送
+    pub async fn run_propose(
+        &self,
+        key: String,
+        value: Option<Value>,
+    ) -> Result<Option<Value>, ProposeError> {
+        let mut prop = self.new_propose(key, value)?;
+        // 批量请求中的值无法按 Acceptor 分片
+        match (&self.batcher, &self.erasure) {
+            (Some(batcher), None) => batcher.run(prop).await,
+            _ => prop.run().await,
+        }
+    }
+
+    /// 创建一个使用已连接 [`Acceptor`] 和新 ballot 的 [`Propose`]，可在运行前调整版本和 round
+    pub fn propose(&self, key: String, value: Option<Value>) -> Result<Propose> {
+        Ok(self.new_propose(key, value)?)
+    }
+
+    /// 以 `\0` 开头的 key 保留给系统实例，返回 [`ProposeError::ReservedKey`]
+    pub(crate) fn new_propose(
+        &self,
+        key: String,
+        value: Option<Value>,
+    ) -> Result<Propose, ProposeError> {
+        if membership::is_reserved(&key) {
+            return Err(ProposeError::ReservedKey(key));
+        }
+        self.instance_propose(key, value)
+    }
+
+    /// 同 [`Client::new_propose`]，允许系统实例的 key
+    pub(crate) fn instance_propose(
+        &self,
+        key: String,
+        value: Option<Value>,
+    ) -> Result<Propose, ProposeError> {
+        let mut prop = Propose::new(self.servers.clone(), key, value, self.id);
+        prop.set_epoch(self.membership.epoch);
+        let ballot = self
+            .ballots
+            .next()
+            .map_err(|e| ProposeError::Ballot(e.to_string()))?;
+        prop.proposer.round = Some(ballot);
+        prop.ballots = Some(self.ballots.clone());
+        let conns = self.connected();
+        prop.streams = conns.iter().map(|c| c.stream.clone()).collect();
+        prop.indexes = conns.iter().map(|c| c.index).collect();
+        prop.context = conns.into_iter().map(|c| c.client).collect();
+        prop.quorum = self.quorum.clone();
+        prop.erasure = self.erasure.clone();
+        prop.learners = self.learners.clone();
+        prop.metrics = self.metrics.clone();
+        prop.tls = self.tls.clone();
+        prop.token = self.token.clone();
+        prop.rpc_timeout = self.rpc_timeout;
+        prop.deadline = self.propose_timeout;
+        Ok(prop)
+    }
+
+    /// 使用 TLS 连接 Acceptor，需在 [`Client::connect`] 之前设置
+    pub fn set_tls(&mut self, tls: ClientTlsConfig) {
+        self.propose.tls = Some(tls.clone());
+        self.tls = Some(tls);
+    }
+
+    /// 把 window 内并发的 [`Client::run_propose`] 合并为批量请求，需在 [`Client::connect`] 之前设置
+    pub fn set_batch_window(&mut self, window: Duration) {
+        self.batch_window = Some(window);
+    }
+
+    /// 与每个 Acceptor 保持一条双向流，Prepare/Accept 都经由流发送，需在 [`Client::connect`] 之前设置
+    pub fn set_streaming(&mut self, streaming: bool) {
+        self.streaming = streaming;
+    }
+
+    /// 单个 Prepare/Accept 的超时，默认 3 秒
+    pub fn set_rpc_timeout(&mut self, timeout: Duration) {
+        self.rpc_timeout = Some(timeout);
+    }
+
+    /// [`Client::run_propose`] 的截止时间，超时返回 [`ProposeError::Timeout`]；默认不限制
+    pub fn set_propose_timeout(&mut self, timeout: Duration) {
+        self.propose_timeout = Some(timeout);
+    }
+
+    /// 在每个请求中带上 bearer token，服务端据此认证本客户端的 proposer_id
+    pub fn set_token(&mut self, token: String) {
+        self.propose.token = Some(token.clone());
+        self.token = Some(token);
+    }
+
+    /// 通知 Learner 和成员变更时带上的 bearer token，须在 Learner 和 Acceptor 的 `auth.nodes` 中
+    pub fn set_node_token(&mut self, token: String) {
+        self.node_token = Some(token);
+    }
+
+    /// 每个节点的地址和当前状态，未连接时为空
+    pub fn health(&self) -> Vec<(String, ServerHealth)> {
+        self.conns.as_ref().map(|c| c.health()).unwrap_or_default()
+    }
+
+    /// 本客户端发起的所有提议的指标
+    pub fn metrics(&self) -> Arc<ProposerMetrics> {
+        self.metrics.clone()
+    }
+
+    #[cfg(test)]
+    async fn phase1(&mut self, svr: Option<Vec<i32>>) -> Result<Option<Value>> {
+        return self.propose.phase1(svr).await;
+    }
+
+    #[cfg(test)]
+    async fn phase2(&mut self, svr: Option<Vec<i32>>) -> Result<()> {
+        return self.propose.phase2(svr).await;
+    }
+
+    #[cfg(test)]
+    fn set_proposer(&mut self, proposer: Proposer) -> Result<()> {
+        self.propose.set_proposer(proposer)
+    }
+
+    #[cfg(test)]
+    fn acceptors(&self) -> Vec<PaxosClient<Channel>> {
+        let conns = self
+            .conns
+            .as_ref()
+            .map(|c| c.connected())
+            .unwrap_or_default();
+        conns.into_iter().map(|c| c.client).collect()
+    }
+
+    #[cfg(test)]
+    fn proposer(&mut self) -> Proposer {
+        self.propose.proposer.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // use super::*;
+    use crate::testing::{spawn, start_server};
+    use crate::*;
+    use anyhow::Result;
+    use scopeguard::defer;
+    use tonic::transport::Server;
+    use tonic::Request;
+    use triggered::Trigger;
+
+    /// Accept 永远不返回的 Acceptor
+    struct HungAcceptor(PaxosService);
+
+    #[tonic::async_trait]
+    impl crate::paxos::paxos_server::Paxos for HungAcceptor {
+        async fn prepare(
+            &self,
+            request: Request<Proposer>,
+        ) -> Result<tonic::Response<Acceptor>, tonic::Status> {
+            self.0.prepare(request).await
+        }
+
+        async fn accept(
+            &self,
+            _request: Request<Proposer>,
+        ) -> Result<tonic::Response<Acceptor>, tonic::Status> {
+            futures::future::pending().await
+        }
+
+        async fn prepare_batch(
+            &self,
+            request: Request<ProposerBatch>,
+        ) -> Result<tonic::Response<AcceptorBatch>, tonic::Status> {
+            self.0.prepare_batch(request).await
+        }
+
+        async fn accept_batch(
+            &self,
+            _request: Request<ProposerBatch>,
+        ) -> Result<tonic::Response<AcceptorBatch>, tonic::Status> {
+            futures::future::pending().await
+        }
+
+        type StreamStream = <PaxosService as crate::paxos::paxos_server::Paxos>::StreamStream;
+
+        async fn stream(
+            &self,
+            request: Request<tonic::Streaming<StreamRequest>>,
+        ) -> Result<tonic::Response<Self::StreamStream>, tonic::Status> {
+            self.0.stream(request).await
+        }
+    }
+
+    /// 等待后台完成的请求计入指标，最多等待一秒
+    async fn settled(count: impl Fn() -> u64, expected: u64) {
+        for _ in 0..100 {
+            if count() == expected {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(count(), expected);
+    }
+
+    fn start_hung(address: String) -> Trigger {
+        spawn(address, |addr, signal| {
+            Server::builder()
+                .add_service(PaxosServer::new(HungAcceptor(PaxosService::default())))
+                .serve_with_shutdown(addr, signal)
+        })
+    }
+
+    /// 启动一个健康检查报告未就绪的服务端
+    fn start_not_ready(address: String) -> Trigger {
+        spawn(address, |addr, signal| async move {
+            let (mut reporter, health) = tonic_health::server::health_reporter();
+            reporter
+                .set_service_status(PAXOS_SERVICE_NAME, tonic_health::ServingStatus::NotServing)
+                .await;
+            Server::builder()
+                .add_service(health)
+                .add_service(PaxosServer::new(PaxosService::default()))
+                .serve_with_shutdown(addr, signal)
+                .await
+        })
+    }
+
+    fn start_tls(
+        address: String,
+        tls: tonic::transport::ServerTlsConfig,
+        auth: Option<AuthConfig>,
+    ) -> Trigger {
+        spawn(address, |addr, signal| async move {
+            let svc = PaxosServer::with_interceptor(PaxosService::default(), interceptor(auth));
+            Server::builder()
+                .tls_config(tls)?
+                .add_service(svc)
+                .serve_with_shutdown(addr, signal)
+                .await
+        })
+    }
+
+    const BASE_PORT: i32 = 11030;
+
+    struct TestServer {
+        count: i32,
+        triggers: Vec<Trigger>,
+    }
+
+    impl TestServer {
+        pub(crate) fn new(count: i32) -> Self {
+            TestServer {
+                count,
+                triggers: Default::default(),
+            }
+        }
+
+        pub(crate) fn start(&mut self) -> Result<()> {
+            for i in 0..self.count {
+                let port = BASE_PORT + i;
+                let addr = format!("[::1]:{}", port);
+                self.triggers
+                    .push(start_server(addr, PaxosService::default()));
+            }
+            Ok(())
+        }
+
+        pub(crate) fn stop(&mut self) -> Result<()> {
+            for t in &self.triggers {
+                t.trigger();
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            self.triggers.clear();
+            Ok(())
+        }
+    }
+
+    fn server_address(count: i32) -> Vec<String> {
+        let mut addrs = vec![];
+        for i in 0..count {
+            let port = BASE_PORT + i;
+            let addr = format!("[::1]:{}", port);
+            addrs.push(addr);
+        }
+        addrs
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_single_propose_single_server() {
+        let trigger = start_server("[::1]:11038".to_string(), PaxosService::default());
+        defer! {
+            trigger.trigger();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let res = PaxosClient::connect("http://[::1]:11038").await;
         assert!(res.is_ok(), "{}", res.unwrap_err().to_string());
         let mut client = res.unwrap();
 
@@ -393,6 +1664,7 @@ mod test {
                 proposer_id: 1,
             }),
             value: None,
+            epoch: 0,
         });
 
         let res = client.prepare(request).await;
@@ -418,7 +1690,11 @@ mod test {
                 number: 1,
                 proposer_id: 1,
             }),
-            value: Some(Value { value: 11 }),
+            value: Some(Value {
+                value: 11,
+                ..Default::default()
+            }),
+            epoch: 0,
         });
 
         let res = client.accept(request).await;
@@ -447,6 +1723,7 @@ mod test {
                 proposer_id: 1,
             }),
             value: None,
+            epoch: 0,
         });
 
         let res = client.prepare(request).await;
@@ -464,7 +1741,10 @@ mod test {
                     number: 1,
                     proposer_id: 1,
                 }),
-                value: Some(Value { value: 11 }),
+                value: Some(Value {
+                    value: 11,
+                    ..Default::default()
+                }),
             }
         );
     }
@@ -492,6 +1772,7 @@ mod test {
                 proposer_id: 0,
             }),
             value: None,
+            epoch: 0,
         };
         client.set_proposer(prop.clone()).unwrap();
         let res = phase1(&mut client).await;
@@ -500,7 +1781,10 @@ mod test {
         assert!(value.is_none());
         {
             let mut p = prop.clone();
-            p.value = Some(Value { value: 11 });
+            p.value = Some(Value {
+                value: 11,
+                ..Default::default()
+            });
             client.set_proposer(p).unwrap();
             assert!(phase2(&mut client).await.is_ok());
         }
@@ -516,10 +1800,19 @@ mod test {
         let res = phase1(&mut client).await;
         assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
         let value = res.unwrap();
-        assert_eq!(value, Some(Value { value: 11 }));
+        assert_eq!(
+            value,
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
         {
             let mut p = prop.clone();
-            p.value = Some(Value { value: 03 });
+            p.value = Some(Value {
+                value: 3,
+                ..Default::default()
+            });
             client.set_proposer(p).unwrap();
             assert!(phase2(&mut client).await.is_ok());
         }
@@ -537,7 +1830,10 @@ mod test {
         assert!(res.is_err());
         {
             let mut p = prop.clone();
-            p.value = Some(Value { value: 04 });
+            p.value = Some(Value {
+                value: 4,
+                ..Default::default()
+            });
             client.set_proposer(p).unwrap();
             let res = phase2(&mut client).await;
             assert!(res.is_err(), "{}", res.err().unwrap().to_string());
@@ -554,12 +1850,22 @@ mod test {
         client.set_proposer(prop.clone()).unwrap();
         let res = phase1(&mut client).await;
         assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
+        // 沿用 round 5 已接受的值
         let value = res.unwrap();
-        assert_eq!(value, None);
+        assert_eq!(
+            value,
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
         // last_round = 6 && value_round = 5
         {
             let mut p = prop.clone();
-            p.value = Some(Value { value: 05 });
+            p.value = Some(Value {
+                value: 5,
+                ..Default::default()
+            });
             // round = 6
             client.set_proposer(p).unwrap();
             let res = phase1(&mut client).await;
@@ -568,19 +1874,19 @@ mod test {
     }
 
     async fn phase1(client: &mut Client) -> Result<Option<Value>> {
-        client.propose.set_context(client.acceptors.clone())?;
-        client
+        client.propose.set_context(client.acceptors())?;
+        Ok(client
             .propose
-            .phase1_with_client(client.acceptors.clone())
-            .await
+            .phase1_with_client(client.acceptors())
+            .await?)
     }
 
     async fn phase2(client: &mut Client) -> Result<()> {
-        // client.propose.set_context(client.acceptors.clone())?;
-        client
+        // client.propose.set_context(client.acceptors())?;
+        Ok(client
             .propose
-            .phase2_with_client(client.acceptors.clone())
-            .await
+            .phase2_with_client(client.acceptors())
+            .await?)
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
@@ -593,6 +1899,8 @@ mod test {
         let servers = server_address(3);
         let mut client = Client::new(servers, 0);
         assert!(client.connect().await.is_ok());
+        // round 0 只属于 Mencius slot 的所有者
+        client.propose.set_round(1);
         let res = phase1(&mut client).await;
         assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
         let value = res.unwrap();
@@ -623,6 +1931,7 @@ mod test {
                 proposer_id: alice_id,
             }),
             value: None,
+            epoch: 0,
         };
         alice.set_proposer(prop).unwrap();
         let res = phase1(&mut alice).await;
@@ -638,7 +1947,11 @@ mod test {
                 number: 1,
                 proposer_id: alice_id,
             }),
-            value: Some(Value { value: 3 }),
+            value: Some(Value {
+                value: 3,
+                ..Default::default()
+            }),
+            epoch: 0,
         };
         alice.set_proposer(prop).unwrap();
         let res = phase2(&mut alice).await;
@@ -658,12 +1971,20 @@ mod test {
                 proposer_id: bob_id,
             }),
             value: None,
+            epoch: 0,
         };
         bob.set_proposer(prop).unwrap();
         let res = phase1(&mut bob).await;
         assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
+        // alice 已经接受的值可能已被选定，bob 必须沿用
         let value = res.unwrap();
-        assert_eq!(value, None);
+        assert_eq!(
+            value,
+            Some(Value {
+                value: 3,
+                ..Default::default()
+            })
+        );
         let prop = Proposer {
             id: Some(PaxosInstanceId {
                 key: "sh".to_string(),
@@ -673,12 +1994,12 @@ mod test {
                 number: 2,
                 proposer_id: bob_id,
             }),
-            value: Some(Value { value: 4 }),
+            value,
+            epoch: 0,
         };
         bob.set_proposer(prop).unwrap();
         let res = phase2(&mut bob).await;
         assert!(res.is_ok());
-        // assert_eq!(bob.proposer.value, Some(Value { value: 3 }));
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
@@ -704,6 +2025,7 @@ mod test {
                 proposer_id: alice_id,
             }),
             value: None,
+            epoch: 0,
         };
         alice.set_proposer(alice_prop.clone()).unwrap();
         let res = phase1(&mut alice).await;
@@ -726,6 +2048,7 @@ mod test {
                 proposer_id: bob_id,
             }),
             value: None,
+            epoch: 0,
         };
         bob.set_proposer(bob_prop.clone()).unwrap();
         let res = phase1(&mut bob).await;
@@ -734,17 +2057,29 @@ mod test {
         assert!(value.is_none());
 
         // alice proceed phase 2, failed;
-        alice_prop.value = Some(Value { value: 3 });
+        alice_prop.value = Some(Value {
+            value: 3,
+            ..Default::default()
+        });
         alice.set_proposer(alice_prop).unwrap();
         let res = phase2(&mut alice).await;
         assert!(res.is_err());
 
         // bob proceed phase 2, succeed;
-        bob_prop.value = Some(Value { value: 11 });
+        bob_prop.value = Some(Value {
+            value: 11,
+            ..Default::default()
+        });
         bob.set_proposer(bob_prop).unwrap();
         let res = phase2(&mut bob).await;
         assert!(res.is_ok());
-        assert_eq!(bob.proposer().value, Some(Value { value: 11 }));
+        assert_eq!(
+            bob.proposer().value,
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
@@ -759,7 +2094,7 @@ mod test {
         let alice_id = 11i64;
         let mut alice = Client::new(servers.clone(), alice_id);
         assert!(alice.connect().await.is_ok());
-        assert!(alice.propose.set_context(alice.acceptors.clone()).is_ok());
+        assert!(alice.propose.set_context(alice.acceptors()).is_ok());
         let mut alice_prop = Proposer {
             id: Some(PaxosInstanceId {
                 key: "sh".to_string(),
@@ -770,6 +2105,7 @@ mod test {
                 proposer_id: alice_id,
             }),
             value: None,
+            epoch: 0,
         };
         alice.set_proposer(alice_prop.clone()).unwrap();
         let res = alice.phase1(Some(vec![0, 1])).await;
@@ -781,7 +2117,7 @@ mod test {
         let bob_id = 88i64;
         let mut bob = Client::new(servers, bob_id);
         assert!(bob.connect().await.is_ok());
-        assert!(bob.propose.set_context(bob.acceptors.clone()).is_ok());
+        assert!(bob.propose.set_context(bob.acceptors()).is_ok());
         let mut bob_prop = Proposer {
             id: Some(PaxosInstanceId {
                 key: "sh".to_string(),
@@ -792,6 +2128,7 @@ mod test {
                 proposer_id: bob_id,
             }),
             value: None,
+            epoch: 0,
         };
         bob.set_proposer(bob_prop.clone()).unwrap();
         let res = bob.phase1(Some(vec![1, 2])).await;
@@ -800,17 +2137,29 @@ mod test {
         assert!(value.is_none());
 
         // alice proceed phase 2, failed;
-        alice_prop.value = Some(Value { value: 3 });
+        alice_prop.value = Some(Value {
+            value: 3,
+            ..Default::default()
+        });
         alice.set_proposer(alice_prop).unwrap();
         let res = alice.phase2(Some(vec![0, 1])).await;
         assert!(res.is_err());
 
         // bob proceed phase 2, succeed;
-        bob_prop.value = Some(Value { value: 11 });
+        bob_prop.value = Some(Value {
+            value: 11,
+            ..Default::default()
+        });
         bob.set_proposer(bob_prop).unwrap();
         let res = bob.phase2(Some(vec![1, 2])).await;
         assert!(res.is_ok());
-        assert_eq!(bob.proposer().value, Some(Value { value: 11 }));
+        assert_eq!(
+            bob.proposer().value,
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
 
         // alice propose with round=3
         alice_prop = Proposer {
@@ -823,14 +2172,22 @@ mod test {
                 proposer_id: alice_id,
             }),
             value: None,
+            epoch: 0,
         };
         alice.set_proposer(alice_prop.clone()).unwrap();
         let res = alice.phase1(Some(vec![0, 1])).await;
         assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
+        // 节点 1 上有 bob 被选定的值，alice 必须沿用
         let value = res.unwrap();
-        assert_eq!(value, None);
+        assert_eq!(
+            value,
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
         // alice proceed phase 2, succeed;
-        alice_prop.value = Some(Value { value: 3 });
+        alice_prop.value = value;
         alice.set_proposer(alice_prop).unwrap();
         let res = alice.phase2(Some(vec![0, 1])).await;
         assert!(res.is_ok());
@@ -849,8 +2206,790 @@ mod test {
         let mut alice = Client::new(servers.clone(), alice_id);
         assert!(alice.connect().await.is_ok());
         let res = alice
-            .run_propose("sh".to_string(), Some(Value { value: 11 }))
+            .run_propose(
+                "sh".to_string(),
+                Some(Value {
+                    value: 11,
+                    ..Default::default()
+                }),
+            )
             .await;
         assert!(res.is_ok());
+        let metrics = alice.metrics();
+        assert_eq!(metrics.proposals.with_label_values(&["chosen"]).get(), 1);
+        let accepts = || {
+            metrics
+                .latency
+                .with_label_values(&["accept"])
+                .get_sample_count()
+        };
+        settled(accepts, 3).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_propose_phases() {
+        let mut server = TestServer::new(3);
+        assert!(server.start().is_ok());
+        defer! {
+            let _ = server.stop();
+        }
+        let servers = server_address(3);
+        let mut alice = Client::new(servers, 11);
+        assert!(alice.connect().await.is_ok());
+
+        // alice 分别执行 phase 1 和 phase 2
+        let mut prop = alice
+            .propose(
+                "sz".to_string(),
+                Some(Value {
+                    value: 3,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        prop.set_version(2);
+        prop.set_round(1);
+        // 第三个 Acceptor 的请求可能仍在后台进行，等它完成后再进入下一步
+        let metrics = alice.metrics();
+        let sent = |method| {
+            metrics
+                .latency
+                .with_label_values(&[method])
+                .get_sample_count()
+        };
+        assert_eq!(prop.run_phase1().await.unwrap(), None);
+        settled(|| sent("prepare"), 3).await;
+        assert!(prop.run_phase2().await.is_ok());
+        settled(|| sent("accept"), 3).await;
+
+        // 相同 round 再次执行 phase 1，得到已接受的值
+        let mut prop = alice.propose("sz".to_string(), None).unwrap();
+        prop.set_version(2);
+        prop.set_round(1);
+        assert_eq!(
+            prop.run_phase1().await.unwrap(),
+            Some(Value {
+                value: 3,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_connect_skips_not_ready() {
+        let mut triggers = vec![];
+        let mut servers = vec![];
+        for i in 0..3 {
+            let addr = format!("[::1]:{}", 11040 + i);
+            if i == 0 {
+                triggers.push(start_not_ready(addr.clone()));
+            } else {
+                triggers.push(start_server(addr.clone(), PaxosService::default()));
+            }
+            servers.push(addr);
+        }
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut client = Client::new(servers, 1);
+        assert!(client.connect().await.is_ok());
+        assert_eq!(client.acceptors().len(), 2);
+    }
+
+    /// 在 port 开始的 3 个端口上启动 TLS 服务端，返回地址
+    fn start_tls_servers(
+        port: u16,
+        tls: &TlsConfig,
+        auth: Option<AuthConfig>,
+    ) -> (Vec<String>, Vec<triggered::Trigger>) {
+        let mut triggers = vec![];
+        let mut servers = vec![];
+        for i in 0..3 {
+            let addr = format!("[::1]:{}", port + i);
+            let tls = tls.server_config().unwrap();
+            triggers.push(start_tls(addr.clone(), tls, auth.clone()));
+            servers.push(addr);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        (servers, triggers)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_mutual_tls() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server_tls, client_tls) = crate::tls::testing::generate(dir.path(), "alice");
+        let (servers, triggers) = start_tls_servers(11050, &server_tls, None);
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // 没有 TLS 无法连接
+        let mut plain = Client::new(servers.clone(), 2);
+        assert!(plain.connect().await.is_err());
+
+        // 只校验服务端证书，不出示客户端证书，会被服务端拒绝
+        let mut anonymous = Client::new(servers.clone(), 3);
+        let mut tls = client_tls.clone();
+        tls.cert = None;
+        tls.key = None;
+        anonymous.set_tls(tls.client_config().unwrap());
+        let connected = anonymous.connect().await.is_ok();
+        if connected {
+            assert!(anonymous
+                .run_propose(
+                    "tls".to_string(),
+                    Some(Value {
+                        value: 3,
+                        ..Default::default()
+                    })
+                )
+                .await
+                .is_err());
+        }
+
+        let mut alice = Client::new(servers, 1);
+        alice.set_tls(client_tls.client_config().unwrap());
+        assert!(alice.connect().await.is_ok());
+        let mut prop = alice
+            .propose(
+                "tls".to_string(),
+                Some(Value {
+                    value: 11,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        prop.set_round(1);
+        assert_eq!(
+            prop.run().await.unwrap(),
+            Some(Value {
+                value: 11,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_authenticated_proposer() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server_tls, client_tls) = crate::tls::testing::generate(dir.path(), "alice");
+        let mut auth = AuthConfig::default();
+        auth.subjects.insert("alice".to_string(), 1);
+        let (servers, triggers) = start_tls_servers(11060, &server_tls, Some(auth));
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // alice 的证书对应 proposer 1
+        let mut alice = Client::new(servers.clone(), 1);
+        alice.set_tls(client_tls.client_config().unwrap());
+        alice.connect().await.unwrap();
+        let mut prop = alice
+            .propose(
+                "auth".to_string(),
+                Some(Value {
+                    value: 1,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        prop.set_round(1);
+        assert_eq!(
+            prop.run().await.unwrap(),
+            Some(Value {
+                value: 1,
+                ..Default::default()
+            })
+        );
+
+        // 拿着 alice 的证书冒充 proposer 3
+        let mut mallory = Client::new(servers.clone(), 3);
+        mallory.set_tls(client_tls.client_config().unwrap());
+        mallory.connect().await.unwrap();
+        let mut prop = mallory
+            .propose(
+                "auth".to_string(),
+                Some(Value {
+                    value: 3,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        prop.set_round(2);
+        assert!(prop.run().await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_batch_propose() {
+        let mut server = TestServer::new(3);
+        assert!(server.start().is_ok());
+        defer! {
+            let _ = server.stop();
+        }
+        let mut alice = Client::new(server_address(3), 12);
+        alice.set_batch_window(std::time::Duration::from_millis(50));
+        assert!(alice.connect().await.is_ok());
+
+        let keys: Vec<_> = (0..10).map(|i| format!("batch{}", i)).collect();
+        let f = keys.iter().enumerate().map(|(i, key)| {
+            alice.run_propose(
+                key.clone(),
+                Some(Value {
+                    value: i as i64,
+                    ..Default::default()
+                }),
+            )
+        });
+        let results = futures::future::join_all(f).await;
+        for (i, r) in results.into_iter().enumerate() {
+            assert_eq!(
+                r.unwrap(),
+                Some(Value {
+                    value: i as i64,
+                    ..Default::default()
+                })
+            );
+        }
+
+        // 10 个提议合并为一个批次，每个 Acceptor 各收到一次 PrepareBatch 和 AcceptBatch
+        let metrics = alice.metrics();
+        assert_eq!(metrics.proposals.with_label_values(&["chosen"]).get(), 10);
+        for method in &["prepare_batch", "accept_batch"] {
+            assert_eq!(
+                metrics
+                    .latency
+                    .with_label_values(&[method])
+                    .get_sample_count(),
+                3
+            );
+        }
+
+        // 同一实例上的提议无论是否在同一批次中，都得到先被选定的值
+        let value = |v| {
+            Some(Value {
+                value: v,
+                ..Default::default()
+            })
+        };
+        let (first, second) = futures::future::join(
+            alice.run_propose("dup".to_string(), value(1)),
+            alice.run_propose("dup".to_string(), value(2)),
+        )
+        .await;
+        assert_eq!(first.unwrap(), value(1));
+        assert_eq!(second.unwrap(), value(1));
+        let third = alice.run_propose("dup".to_string(), value(3)).await;
+        assert_eq!(third.unwrap(), value(1));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_stream_propose() {
+        let mut server = TestServer::new(3);
+        assert!(server.start().is_ok());
+        defer! {
+            let _ = server.stop();
+        }
+        let mut alice = Client::new(server_address(3), 13);
+        alice.set_streaming(true);
+        assert!(alice.connect().await.is_ok());
+
+        // 多个实例同时在同一组流上进行
+        let keys: Vec<_> = (0..10).map(|i| format!("stream{}", i)).collect();
+        let f = keys.iter().enumerate().map(|(i, key)| {
+            alice.run_propose(
+                key.clone(),
+                Some(Value {
+                    value: i as i64,
+                    ..Default::default()
+                }),
+            )
+        });
+        let results = futures::future::join_all(f).await;
+        for (i, r) in results.into_iter().enumerate() {
+            assert_eq!(
+                r.unwrap(),
+                Some(Value {
+                    value: i as i64,
+                    ..Default::default()
+                })
+            );
+        }
+        let metrics = alice.metrics();
+        assert_eq!(metrics.proposals.with_label_values(&["chosen"]).get(), 10);
+        assert_eq!(
+            metrics
+                .latency
+                .with_label_values(&["prepare"])
+                .get_sample_count(),
+            30
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_acceptor_rejoins() {
+        let servers: Vec<_> = (11070..11073).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for addr in &servers[..2] {
+            triggers.push(start_server(addr.clone(), PaxosService::default()));
+        }
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut client = Client::new(servers.clone(), 14);
+        assert!(client.connect().await.is_ok());
+        assert_eq!(client.acceptors().len(), 2);
+        assert_eq!(client.health()[2].1, ServerHealth::Down);
+
+        // 第三个节点上线后，后台重连把它加回多数派
+        let trigger = start_server(servers[2].clone(), PaxosService::default());
+        let mut rejoined = false;
+        for _ in 0..30 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if client.acceptors().len() == 3 {
+                rejoined = true;
+                break;
+            }
+        }
+        assert!(rejoined);
+        assert!(client
+            .health()
+            .iter()
+            .all(|(_, health)| *health == ServerHealth::Ready));
+
+        // 下线后不再参与提议
+        trigger.trigger();
+        let mut dropped = false;
+        for _ in 0..30 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if client.acceptors().len() == 2 {
+                dropped = true;
+                break;
+            }
+        }
+        assert!(dropped);
+        let res = client
+            .run_propose(
+                "rejoin".to_string(),
+                Some(Value {
+                    value: 7,
+                    ..Default::default()
+                }),
+            )
+            .await;
+        assert_eq!(
+            res.unwrap(),
+            Some(Value {
+                value: 7,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_propose_deadline() {
+        let servers: Vec<_> = (11080..11083).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for addr in &servers {
+            triggers.push(start_hung(addr.clone()));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // 单个 RPC 超时：accept 已发出，结果未知
+        let mut alice = Client::new(servers.clone(), 15);
+        alice.set_rpc_timeout(std::time::Duration::from_millis(100));
+        assert!(alice.connect().await.is_ok());
+        let err = alice
+            .run_propose(
+                "hung".to_string(),
+                Some(Value {
+                    value: 1,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err, ProposeError::Timeout);
+
+        // 整个提议超时
+        let mut bob = Client::new(servers, 16);
+        bob.set_propose_timeout(std::time::Duration::from_millis(200));
+        assert!(bob.connect().await.is_ok());
+        let start = std::time::Instant::now();
+        let err = bob
+            .run_propose(
+                "hung2".to_string(),
+                Some(Value {
+                    value: 2,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err, ProposeError::Timeout);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        let metrics = bob.metrics();
+        assert_eq!(metrics.proposals.with_label_values(&["unknown"]).get(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_one_acceptor_hung() {
+        let servers: Vec<_> = (11210..11213).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for (i, addr) in servers.iter().enumerate() {
+            if i == 2 {
+                triggers.push(start_hung(addr.clone()));
+            } else {
+                triggers.push(start_server(addr.clone(), PaxosService::default()));
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let v = |value| Value {
+            value,
+            ..Default::default()
+        };
+
+        // 另外两个 Acceptor 构成 quorum，挂住的 Acceptor 不影响结果
+        let mut alice = Client::new(servers.clone(), 17);
+        alice.set_rpc_timeout(std::time::Duration::from_millis(200));
+        assert!(alice.connect().await.is_ok());
+        let start = std::time::Instant::now();
+        let chosen = alice.run_propose("hung".to_string(), Some(v(1))).await;
+        assert_eq!(chosen, Ok(Some(v(1))));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        let mut bob = Client::new(servers, 18);
+        bob.set_rpc_timeout(std::time::Duration::from_millis(200));
+        bob.set_batch_window(std::time::Duration::from_millis(10));
+        assert!(bob.connect().await.is_ok());
+        let chosen = bob.run_propose("hung2".to_string(), Some(v(2))).await;
+        assert_eq!(chosen, Ok(Some(v(2))));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_reconfigure() {
+        let servers: Vec<_> = (11090..11094).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for addr in &servers {
+            triggers.push(start_server(addr.clone(), PaxosService::default()));
+        }
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // 变更前选定的值只在前两个节点上，第三个节点没有
+        let before = Some(Value {
+            value: 5,
+            ..Default::default()
+        });
+        let mut writer = Client::new(servers[..2].to_vec(), 27);
+        assert!(writer.connect().await.is_ok());
+        let res = writer
+            .run_propose("before".to_string(), before.clone())
+            .await;
+        assert_eq!(res.unwrap(), before);
+
+        // 系统实例的 key 不能直接提议
+        let res = writer.run_propose("\0x".to_string(), None).await;
+        assert_eq!(res, Err(ProposeError::ReservedKey("\0x".to_string())));
+
+        // 用第四个节点替换第一个
+        let mut alice = Client::new(servers[..3].to_vec(), 23);
+        assert!(alice.connect().await.is_ok());
+        let chosen = alice.reconfigure(servers[1..].to_vec()).await.unwrap();
+        assert_eq!(chosen.epoch, 1);
+        assert_eq!(chosen.servers, servers[1..].to_vec());
+        assert_eq!(alice.membership(), &chosen);
+
+        // 旧配置中的请求被拒绝，refresh 之后恢复
+        let mut carol = Client::new(servers[..3].to_vec(), 28);
+        assert!(carol.connect().await.is_ok());
+        let res = carol.run_propose("stale".to_string(), None).await;
+        assert_eq!(res, Err(ProposeError::StaleEpoch { current: 1 }));
+        carol.refresh().await.unwrap();
+        assert_eq!(carol.membership(), &chosen);
+        assert!(carol.run_propose("stale".to_string(), None).await.is_ok());
+
+        // 使用旧配置启动的 Proposer 从日志中学到新配置
+        let mut bob = Client::new(servers[..3].to_vec(), 24);
+        assert!(bob.connect().await.is_ok());
+        bob.refresh().await.unwrap();
+        assert_eq!(bob.membership(), &chosen);
+        assert_eq!(bob.acceptors().len(), 3);
+
+        // 只剩第三、四个节点时，变更前选定的值仍可读到，说明新节点已追上
+        triggers[0].trigger();
+        triggers[1].trigger();
+        let prop = alice.new_propose("before".to_string(), None).unwrap();
+        assert_eq!(prop.decide().await.unwrap(), before);
+
+        let value = Some(Value {
+            value: 8,
+            ..Default::default()
+        });
+        let res = alice.run_propose("reconf".to_string(), value.clone()).await;
+        assert_eq!(res.unwrap(), value);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_zone_aware_propose() {
+        let servers: Vec<_> = (11100..11103).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for addr in &servers {
+            triggers.push(start_server(addr.clone(), PaxosService::default()));
+        }
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let zones: Vec<String> = ["b", "a", "a"].iter().map(|z| z.to_string()).collect();
+        let mut alice = Client::new(servers, 25);
+        assert!(alice.set_zones(vec!["a".to_string()], None).is_err());
+        alice
+            .set_zones(zones.clone(), Some("a".to_string()))
+            .unwrap();
+        // quorum 覆盖的节点数必须与 servers 相同
+        assert!(alice
+            .set_quorum(Weighted::new(vec![2, 1]).unwrap())
+            .is_err());
+        assert!(alice.set_quorum(Grid::new(2, 2).unwrap()).is_err());
+        alice
+            .set_quorum(ZoneQuorum::new(zones, ZonePolicy::MajorityOfZones).unwrap())
+            .unwrap();
+        assert!(alice.connect().await.is_ok());
+
+        // 本地 zone 中的节点排在前面
+        let prop = alice.propose("zone".to_string(), None).unwrap();
+        assert_eq!(prop.indexes, vec![1, 2, 0]);
+
+        let value = Some(Value {
+            value: 9,
+            ..Default::default()
+        });
+        let res = alice.run_propose("zone".to_string(), value.clone()).await;
+        assert_eq!(res.unwrap(), value);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_slow_remote_zone() {
+        let servers: Vec<_> = (11220..11223).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for (i, addr) in servers.iter().enumerate() {
+            if i == 2 {
+                triggers.push(start_hung(addr.clone()));
+            } else {
+                triggers.push(start_server(addr.clone(), PaxosService::default()));
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // 远端 zone 的节点一直不应答 accept，且没有设置 RPC 超时；
+        // 本地 zone 的两个节点已构成 quorum，不需要等待它
+        let zones: Vec<String> = ["a", "a", "b"].iter().map(|z| z.to_string()).collect();
+        let mut alice = Client::new(servers, 29);
+        alice.set_zones(zones, Some("a".to_string())).unwrap();
+        assert!(alice.connect().await.is_ok());
+        let value = Some(Value {
+            value: 10,
+            ..Default::default()
+        });
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            alice.run_propose("remote".to_string(), value.clone()),
+        )
+        .await;
+        assert_eq!(res.unwrap().unwrap(), value);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    pub(super) async fn test_fast_propose() {
+        let servers: Vec<_> = (11110..11113).map(|p| format!("[::1]:{}", p)).collect();
+        let mut triggers = vec![];
+        for addr in &servers {
+            triggers.push(start_server(addr.clone(), PaxosService::default()));
+        }
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut alice = Client::new(servers, 26);
+        assert!(alice.connect().await.is_ok());
+        let fast = alice.open_fast_round("fast".to_string()).await.unwrap();
+
+        // 一个来回即被选定，不发送 prepare
+        let one = Value {
+            value: 1,
+            ..Default::default()
+        };
+        let res = alice.fast_propose(&fast, one.clone()).await;
+        assert_eq!(res.unwrap(), Some(one.clone()));
+        let metrics = alice.metrics();
+        let prepares = || {
+            metrics
+                .latency
+                .with_label_values(&["prepare"])
+                .get_sample_count()
+        };
+        settled(prepares, 3).await;
+
+        // 同一 fast round 中的第二个值发生冲突，classic round 恢复出已选定的值
+        let two = Value {
+            value: 2,
+            ..Default::default()
+        };
+        let res = alice.fast_propose(&fast, two).await;
+        assert_eq!(res.unwrap(), Some(one.clone()));
+        assert_eq!(metrics.retries.get(), 1);
+
+        // fast quorum 只按多数派推导，其他 quorum 配置下拒绝使用
+        alice.set_witnesses(vec![2]);
+        let res = alice.fast_propose(&fast, one).await;
+        assert!(matches!(res, Err(ProposeError::Unsupported(_))));
+        alice.set_witnesses(vec![]);
+        alice.set_quorum(Flexible::new(3, 3, 1).unwrap()).unwrap();
+        let res = alice.open_fast_round("fast".to_string()).await;
+        assert!(matches!(res, Err(ProposeError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_propose_error_kinds() {
+        let servers = server_address(3);
+        let mut prop = Propose::new(
+            servers,
+            "err".to_string(),
+            Some(Value {
+                value: 1,
+                ..Default::default()
+            }),
+            1,
+        );
+        let round = |number, proposer_id| RoundNum {
+            number,
+            proposer_id,
+        };
+        let reply = |i: usize, last_round: RoundNum| {
+            let acc = Acceptor {
+                round: Some(round(0, 0)),
+                last_round: Some(last_round),
+                value: None,
+            };
+            (i, acc)
+        };
+
+        // 有 Acceptor 已承诺更大的 round
+        let replies = vec![reply(0, round(0, 0)), reply(1, round(3, 2))];
+        let err = prop.resolve_phase1(replies).unwrap_err();
+        assert_eq!(err, ProposeError::Preempted { by: round(3, 2) });
+        // number 相同时 proposer_id 更大的 round 更大
+        let replies = vec![reply(0, round(0, 0)), reply(1, round(0, 2))];
+        let err = prop.resolve_phase1(replies).unwrap_err();
+        assert_eq!(err, ProposeError::Preempted { by: round(0, 2) });
+
+        // 应答不足多数派
+        let err = prop
+            .resolve_phase1(vec![reply(0, round(0, 1))])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ProposeError::NoQuorum {
+                reachable: 1,
+                needed: 2
+            }
+        );
+        let err = prop
+            .resolve_phase2(vec![reply(0, round(0, 1))])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ProposeError::NoQuorum {
+                reachable: 1,
+                needed: 2
+            }
+        );
+
+        // 多数派中有更大的 round 拒绝了 accept
+        let replies = vec![
+            reply(0, round(0, 1)),
+            reply(1, round(2, 3)),
+            reply(2, round(5, 4)),
+        ];
+        let err = prop.resolve_phase2(replies).unwrap_err();
+        assert_eq!(err, ProposeError::Preempted { by: round(5, 4) });
+    }
+
+    #[test]
+    fn test_fresh_ballot_per_proposal() {
+        let alice = Client::new(server_address(3), 21);
+        let prop = alice.propose("ballot".to_string(), None).unwrap();
+        assert_eq!(prop.proposer().round.as_ref().unwrap().number, 1);
+
+        // 被抢占后，下一个提议越过抢占者的 round
+        let by = RoundNum {
+            number: 9,
+            proposer_id: 22,
+        };
+        let _ = prop.finish(Err(ProposeError::Preempted { by }));
+        let prop = alice.propose("ballot".to_string(), None).unwrap();
+        let round = prop.proposer().round.clone().unwrap();
+        assert_eq!(round.number, 10);
+        assert_eq!(round.proposer_id, 21);
+    }
+
+    #[test]
+    fn test_flexible_quorum() {
+        let mut prop = Propose::new(server_address(3), "fq".to_string(), None, 1);
+        prop.quorum = Some(std::sync::Arc::new(Flexible::new(3, 3, 1).unwrap()));
+        let acc = Acceptor {
+            round: Some(RoundNum::default()),
+            last_round: Some(RoundNum::default()),
+            value: None,
+        };
+
+        // 一个 Acceptor 接受即可，但 prepare 需要全部三个
+        assert!(prop.resolve_phase2(vec![(2, acc.clone())]).is_ok());
+        let replies = vec![(0, acc.clone()), (1, acc.clone())];
+        assert!(prop.resolve_phase1(replies).is_err());
+        let replies = vec![(0, acc.clone()), (1, acc.clone()), (2, acc)];
+        assert!(prop.resolve_phase1(replies).is_ok());
     }
 }
@@ -0,0 +1,319 @@
+use crate::auth::AuthConfig;
+use crate::quorum::{Flexible, Majority, QuorumSystem};
+use crate::storage::FsyncMode;
+use crate::tls::TlsConfig;
+use anyhow::{Error, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 服务端配置，可由 TOML 或 YAML 文件加载，命令行参数覆盖
+///
+/// ```toml
+/// listen = "[::1]:11030"
+/// node_id = 1
+/// peers = ["[::1]:11031", "[::1]:11032"]
+/// data_dir = "/var/lib/rpaxos/1"
+/// fsync = "always"
+/// log_level = "info"
+/// metrics_listen = "127.0.0.1:9030"
+/// witness = false
+/// learner = false
+/// catch_up_quorum = 2
+/// admin = false
+/// mencius_owners = [1, 2, 3]
+///
+/// [timeouts]
+/// request_ms = 3000
+/// keepalive_ms = 60000
+/// probe_ms = 1000
+/// anti_entropy_ms = 10000
+/// catch_up_ms = 5000
+///
+/// [tls]
+/// cert = "node1.pem"
+/// key = "node1.key"
+/// ca = "ca.pem"
+/// client_ca = "ca.pem"
+///
+/// [auth.subjects]
+/// "proposer-1" = 1
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub listen: String,
+    /// 本节点 ID，集群内唯一
+    pub node_id: i64,
+    /// 其他节点的地址；作为 Learner 运行时为从中补齐被选定的值的 Acceptor
+    pub peers: Vec<String>,
+    /// 数据目录，未设置时只保存在内存中
+    pub data_dir: Option<PathBuf>,
+    pub fsync: FsyncMode,
+    pub log_level: String,
+    /// Prometheus 指标的 HTTP 监听地址，未设置时不提供
+    pub metrics_listen: Option<String>,
+    pub timeouts: Timeouts,
+    /// 未设置时使用明文连接
+    pub tls: Option<TlsConfig>,
+    /// 未设置时不认证调用方，ballot 中的 proposer_id 由客户端自行决定
+    pub auth: Option<AuthConfig>,
+    /// 访问其他节点时出示的 bearer token，须在对方的 `auth.nodes` 中；
+    /// 使用双向 TLS 时也可以只靠本节点证书的 CN
+    pub node_token: Option<String>,
+    /// 作为见证者运行，只保存 ballot 和值的摘要
+    pub witness: bool,
+    /// 作为不参与投票的 Learner 运行，只接收被选定的值
+    pub learner: bool,
+    /// 作为 Learner 补齐时，同一个值须被这么多个 peers 接受才认为已被选定，即 phase 2 quorum 的大小；
+    /// 0 表示 peers 的多数派。使用 Flexible Paxos 或纠删码的集群须设为其 phase 2 quorum 的大小
+    pub catch_up_quorum: usize,
+    /// 提供 Admin 服务，其中的 Forget 会破坏安全性，默认关闭；开启认证时须用 `auth.admins` 中的身份调用
+    pub admin: bool,
+    /// Mencius 日志中 slot 的所有者的 proposer_id，第 s 个 slot 属于第 s % n 个；
+    /// 只有所有者可以在自己的 slot 中用 round 0 跳过 phase 1，为空时不接受 round 0 的 accept
+    pub mencius_owners: Vec<i64>,
+}
+
+/// 超时设置，单位毫秒，0 表示不设置
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// 单个请求的处理超时
+    pub request_ms: u64,
+    /// TCP keepalive 间隔
+    pub keepalive_ms: u64,
+    /// 探测其他节点以更新就绪状态的间隔
+    pub probe_ms: u64,
+    /// 与其他节点比较实例状态、拉取缺少的值的间隔，默认为 0，即不运行 anti-entropy
+    pub anti_entropy_ms: u64,
+    /// 作为 Learner 运行时从 Acceptor 补齐被选定的值的间隔，0 表示不补齐
+    pub catch_up_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: "[::1]:11030".to_string(),
+            node_id: 0,
+            peers: vec![],
+            data_dir: None,
+            fsync: FsyncMode::default(),
+            log_level: "info".to_string(),
+            metrics_listen: None,
+            timeouts: Timeouts::default(),
+            tls: None,
+            auth: None,
+            node_token: None,
+            witness: false,
+            learner: false,
+            catch_up_quorum: 0,
+            admin: false,
+            mencius_owners: vec![],
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            request_ms: 3000,
+            keepalive_ms: 0,
+            probe_ms: 1000,
+            anti_entropy_ms: 0,
+            catch_up_ms: 5000,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// 按扩展名选择格式，`.yaml` 和 `.yml` 为 YAML，其余按 TOML 解析
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::msg(format!("read {}: {}", path.display(), e)))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml(&content),
+            _ => Self::from_toml(&content),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let config: ServerConfig = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let config: ServerConfig = serde_yaml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 检查配置是否可用
+    pub fn validate(&self) -> Result<()> {
+        self.listen
+            .parse::<std::net::SocketAddr>()
+            .map_err(|e| Error::msg(format!("invalid listen address {}: {}", self.listen, e)))?;
+        if let Some(addr) = &self.metrics_listen {
+            addr.parse::<std::net::SocketAddr>().map_err(|e| {
+                Error::msg(format!("invalid metrics_listen address {}: {}", addr, e))
+            })?;
+        }
+        match self.log_level.as_str() {
+            "error" | "warn" | "info" | "debug" | "trace" => {}
+            level => return Err(Error::msg(format!("unknown log level: {}", level))),
+        }
+        if self.peers.contains(&self.listen) {
+            return Err(Error::msg("peers must not contain the listen address"));
+        }
+        if self.catch_up_quorum > self.peers.len() {
+            return Err(Error::msg(format!(
+                "catch_up_quorum {} exceeds {} peers",
+                self.catch_up_quorum,
+                self.peers.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// 作为 Learner 补齐时判断值是否已被选定的 quorum
+    pub fn catch_up_quorum(&self) -> Result<Arc<dyn QuorumSystem>> {
+        let n = self.peers.len();
+        Ok(match self.catch_up_quorum {
+            0 => Arc::new(Majority::new(n)),
+            q => Arc::new(Flexible::new(n, n + 1 - q, q)?),
+        })
+    }
+}
+
+impl Timeouts {
+    pub fn request(&self) -> Option<Duration> {
+        millis(self.request_ms)
+    }
+
+    pub fn keepalive(&self) -> Option<Duration> {
+        millis(self.keepalive_ms)
+    }
+
+    pub fn anti_entropy(&self) -> Option<Duration> {
+        millis(self.anti_entropy_ms)
+    }
+
+    pub fn catch_up(&self) -> Option<Duration> {
+        millis(self.catch_up_ms)
+    }
+
+    pub fn probe(&self) -> Duration {
+        millis(self.probe_ms).unwrap_or_else(|| Duration::from_millis(1000))
+    }
+}
+
+fn millis(ms: u64) -> Option<Duration> {
+    if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let config = ServerConfig::from_toml(
+            r#"
+            listen = "127.0.0.1:11031"
+            node_id = 2
+            peers = ["127.0.0.1:11030", "127.0.0.1:11032"]
+            data_dir = "/tmp/rpaxos/2"
+            fsync = "never"
+            metrics_listen = "127.0.0.1:9031"
+            node_token = "n0de"
+            mencius_owners = [7, 8]
+            catch_up_quorum = 1
+
+            [timeouts]
+            request_ms = 500
+            catch_up_ms = 200
+
+            [tls]
+            cert = "node2.pem"
+            key = "node2.key"
+            client_ca = "ca.pem"
+
+            [auth.tokens]
+            "s3cr3t" = 7
+
+            [auth.nodes]
+            subjects = ["node-1"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.listen, "127.0.0.1:11031");
+        assert_eq!(config.node_id, 2);
+        assert_eq!(config.peers.len(), 2);
+        assert_eq!(config.data_dir, Some(PathBuf::from("/tmp/rpaxos/2")));
+        assert_eq!(config.fsync, FsyncMode::Never);
+        assert_eq!(config.log_level, "info");
+        assert_eq!(config.catch_up_quorum().unwrap().phase2_size(), 1);
+        assert_eq!(config.metrics_listen, Some("127.0.0.1:9031".to_string()));
+        assert_eq!(config.timeouts.request(), Some(Duration::from_millis(500)));
+        assert_eq!(config.timeouts.keepalive(), None);
+        assert_eq!(config.timeouts.anti_entropy(), None);
+        assert_eq!(config.timeouts.catch_up(), Some(Duration::from_millis(200)));
+        let tls = config.tls.unwrap();
+        assert_eq!(tls.cert, Some(PathBuf::from("node2.pem")));
+        assert_eq!(tls.ca, None);
+        assert_eq!(tls.client_ca, Some(PathBuf::from("ca.pem")));
+        assert_eq!(config.node_token, Some("n0de".to_string()));
+        let auth = config.auth.unwrap();
+        assert_eq!(auth.tokens.get("s3cr3t"), Some(&7));
+        assert_eq!(auth.nodes.subjects, vec!["node-1".to_string()]);
+        assert!(!config.admin);
+        assert_eq!(config.mencius_owners, vec![7, 8]);
+
+        assert!(ServerConfig::from_toml("listen = \"nowhere\"").is_err());
+        assert!(ServerConfig::from_toml("log_level = \"loud\"").is_err());
+        assert!(ServerConfig::from_toml("unknown = 1").is_err());
+        assert!(ServerConfig::from_toml("catch_up_quorum = 1").is_err());
+        let majority = ServerConfig {
+            peers: config.peers,
+            ..Default::default()
+        };
+        assert_eq!(majority.catch_up_quorum().unwrap().phase2_size(), 2);
+    }
+
+    #[test]
+    fn test_load_yaml_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.yaml");
+        std::fs::write(
+            &path,
+            r#"
+listen: "127.0.0.1:11031"
+node_id: 2
+peers: ["127.0.0.1:11030", "127.0.0.1:11032"]
+fsync: never
+timeouts:
+  request_ms: 500
+auth:
+  tokens:
+    s3cr3t: 7
+"#,
+        )
+        .unwrap();
+        let config = ServerConfig::load(&path).unwrap();
+        assert_eq!(config.listen, "127.0.0.1:11031");
+        assert_eq!(config.node_id, 2);
+        assert_eq!(config.peers.len(), 2);
+        assert_eq!(config.fsync, FsyncMode::Never);
+        assert_eq!(config.timeouts.request(), Some(Duration::from_millis(500)));
+        assert_eq!(config.auth.unwrap().tokens.get("s3cr3t"), Some(&7));
+
+        assert!(ServerConfig::from_yaml("unknown: 1").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::health;
+use crate::stream::AcceptorStream;
+use crate::tls;
+use crate::PaxosClient;
+use anyhow::{Error, Result};
+use futures::future::join_all;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tonic::transport::{Channel, ClientTlsConfig};
+use tracing::{debug, info, warn};
+
+/// 已连接节点的健康检查间隔
+const PROBE_INTERVAL: Duration = Duration::from_millis(1000);
+/// 重连的初始等待时间，每次失败翻倍
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_millis(5000);
+/// 建立连接的最长等待时间
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// 节点的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerHealth {
+    /// 已连接且 Acceptor 就绪
+    Ready,
+    /// 已连接但 Acceptor 未就绪
+    NotReady,
+    /// 无法连接
+    Down,
+}
+
+/// 一个就绪节点的连接，开启双向流时带有该节点上的流
+#[derive(Debug, Clone)]
+pub(crate) struct Connection {
+    /// 节点在配置中的下标
+    pub(crate) index: usize,
+    pub(crate) channel: Channel,
+    pub(crate) client: PaxosClient<Channel>,
+    pub(crate) stream: Option<AcceptorStream>,
+}
+
+#[derive(Debug)]
+struct ServerState {
+    channel: Option<Channel>,
+    stream: Option<AcceptorStream>,
+    health: ServerHealth,
+}
+
+#[derive(Debug)]
+struct Server {
+    address: String,
+    state: Mutex<ServerState>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    servers: Vec<Server>,
+    tls: Option<ClientTlsConfig>,
+    /// 为每个就绪节点打开双向流
+    streaming: bool,
+    /// 打开双向流时使用的 bearer token
+    token: Option<String>,
+}
+
+/// 为每个节点维护一个连接，在后台探测健康状态，断开后按退避时间重连
+///
+/// 所有克隆都被丢弃后后台任务随之退出。
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionManager {
+    inner: Arc<Inner>,
+}
+
+impl ConnectionManager {
+    /// 对所有节点探测一次后启动后台任务，必须在 tokio runtime 中调用
+    pub(crate) async fn start(
+        servers: Vec<String>,
+        tls: Option<ClientTlsConfig>,
+        streaming: bool,
+        token: Option<String>,
+    ) -> Self {
+        let servers = servers
+            .into_iter()
+            .map(|address| Server {
+                address,
+                state: Mutex::new(ServerState {
+                    channel: None,
+                    stream: None,
+                    health: ServerHealth::Down,
+                }),
+            })
+            .collect();
+        let inner = Arc::new(Inner {
+            servers,
+            tls,
+            streaming,
+            token,
+        });
+        let healthy = join_all((0..inner.servers.len()).map(|i| inner.probe(i))).await;
+        for (i, healthy) in healthy.into_iter().enumerate() {
+            tokio::spawn(watch(Arc::downgrade(&inner), i, healthy));
+        }
+        ConnectionManager { inner }
+    }
+
+    /// 当前就绪的节点
+    pub(crate) fn connected(&self) -> Vec<Connection> {
+        self.inner
+            .servers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, server)| {
+                let state = server.state.lock().unwrap();
+                match (&state.channel, state.health) {
+                    (Some(channel), ServerHealth::Ready) => Some(Connection {
+                        index,
+                        channel: channel.clone(),
+                        client: PaxosClient::new(channel.clone()),
+                        stream: state.stream.clone(),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// 每个节点的地址和状态
+    pub(crate) fn health(&self) -> Vec<(String, ServerHealth)> {
+        self.inner
+            .servers
+            .iter()
+            .map(|server| {
+                let health = server.state.lock().unwrap().health;
+                (server.address.clone(), health)
+            })
+            .collect()
+    }
+}
+
+/// 节点就绪时按固定间隔检查，否则按退避时间重连
+async fn watch(inner: Weak<Inner>, i: usize, mut healthy: bool) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let delay = if healthy {
+            backoff = INITIAL_BACKOFF;
+            PROBE_INTERVAL
+        } else {
+            let delay = backoff;
+            backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+            delay
+        };
+        tokio::time::sleep(delay).await;
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        healthy = inner.probe(i).await;
+    }
+}
+
+impl Inner {
+    async fn connect(&self, address: &str) -> Result<Channel> {
+        let dst = tls::endpoint(address, self.tls.as_ref())?;
+        match tokio::time::timeout(CONNECT_TIMEOUT, dst.connect()).await {
+            Ok(channel) => Ok(channel?),
+            Err(_) => Err(Error::msg("connect timeout")),
+        }
+    }
+
+    /// 探测一个节点并更新状态，返回节点是否就绪
+    async fn probe(&self, i: usize) -> bool {
+        let server = &self.servers[i];
+        let existing = server.state.lock().unwrap().channel.clone();
+        let channel = match existing {
+            Some(channel) => Ok(channel),
+            None => self.connect(&server.address).await,
+        };
+        let result = match channel {
+            Ok(channel) => health::check_health(channel.clone())
+                .await
+                .map(|ready| (channel, ready)),
+            Err(e) => Err(e),
+        };
+
+        let (channel, health) = match result {
+            Ok((channel, true)) => (Some(channel), ServerHealth::Ready),
+            Ok((channel, false)) => (Some(channel), ServerHealth::NotReady),
+            Err(e) => {
+                debug!(server = %server.address, error = %e, "probe failed");
+                (None, ServerHealth::Down)
+            }
+        };
+
+        // 就绪节点上的流已关闭时重新打开
+        let reopen = {
+            let state = server.state.lock().unwrap();
+            state.stream.as_ref().map(|s| s.is_closed()).unwrap_or(true)
+        };
+        let stream = match (&channel, health) {
+            (Some(channel), ServerHealth::Ready) if self.streaming && reopen => {
+                let client = PaxosClient::new(channel.clone());
+                match AcceptorStream::open(client, self.token.as_deref()).await {
+                    Ok(stream) => Some(Some(stream)),
+                    Err(e) => {
+                        warn!(server = %server.address, error = %e, "open stream failed");
+                        return self.update(server, None, Some(None), ServerHealth::Down);
+                    }
+                }
+            }
+            (_, ServerHealth::Ready) => None,
+            _ => Some(None),
+        };
+        self.update(server, channel, stream, health)
+    }
+
+    /// stream 为 None 时保留原来的流
+    fn update(
+        &self,
+        server: &Server,
+        channel: Option<Channel>,
+        stream: Option<Option<AcceptorStream>>,
+        health: ServerHealth,
+    ) -> bool {
+        let mut state = server.state.lock().unwrap();
+        if state.health != health {
+            match health {
+                ServerHealth::Ready => info!(server = %server.address, "acceptor ready"),
+                _ => warn!(server = %server.address, ?health, "acceptor unavailable"),
+            }
+        }
+        state.channel = channel;
+        if let Some(stream) = stream {
+            state.stream = stream;
+        }
+        state.health = health;
+        health == ServerHealth::Ready
+    }
+}
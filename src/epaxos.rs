@@ -0,0 +1,1101 @@
+use crate::auth;
+use crate::ballot::BallotAllocator;
+use crate::client::{grpc_timeout, within};
+use crate::error::ProposeError;
+use crate::metrics::ProposerMetrics;
+use crate::paxos::epaxos_client::EpaxosClient;
+use crate::paxos::epaxos_server::Epaxos;
+use crate::quorum::QuorumSystem;
+use crate::server::{internal, PaxosService};
+use crate::{
+    EAttributes, ECommand, ECommittedReply, ECommittedRequest, EInstance, EInstanceId,
+    EPrepareReply, EPrepareRequest, EReply, EStatus, RoundNum, Value,
+};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status};
+use tracing::{debug, info, warn};
+
+/// 读取时发现未提交的依赖，先等待发起者完成提交，超过该时间仍未提交时再恢复
+const RECOVER_AFTER: Duration = Duration::from_millis(100);
+
+/// EPaxos 的 fast quorum：F + ⌊(F+1)/2⌋，F = ⌊(n-1)/2⌋ 为可以容忍的故障数
+///
+/// 不小于多数派，任意两个 fast quorum 相交，同一实例不会有两组属性都凑齐 fast quorum。
+/// 与 Fast Paxos 的 ⌈3n/4⌉ 不同，n = 3 时为 2，一个节点故障时仍然可以一个来回提交。
+pub(crate) fn fast_quorum(n: usize) -> usize {
+    let f = n.saturating_sub(1) / 2;
+    (f + f.div_ceil(2)).max(n / 2 + 1)
+}
+
+/// Acceptor 上的 EPaxos 状态
+///
+/// 与 Paxos 实例一样按 key 组织：同一 key 上的命令相互冲突，
+/// 每个 key 只记录各 Proposer 最新的实例，新命令依赖它们即可，更早的实例由依赖的传递性覆盖。
+/// 每次修改先写入日志再生效，重启后由 [`State::restore`] 恢复。
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    instances: HashMap<(i64, i64), EInstance>,
+    /// key -> replica -> slot
+    latest: HashMap<String, HashMap<i64, i64>>,
+}
+
+fn id_of(command: &ECommand) -> (i64, i64) {
+    command
+        .id
+        .as_ref()
+        .map(|id| (id.replica, id.slot))
+        .unwrap_or_default()
+}
+
+fn ballot_of(command: &ECommand) -> RoundNum {
+    command.ballot.clone().unwrap_or_default()
+}
+
+/// ballot 0 的请求只能由实例的发起者发出，恢复时使用的 ballot 必须属于调用方
+fn actor(command: &ECommand) -> i64 {
+    let ballot = ballot_of(command);
+    match ballot.number {
+        0 => id_of(command).0,
+        _ => ballot.proposer_id,
+    }
+}
+
+fn accepted(attributes: EAttributes) -> EReply {
+    EReply {
+        attributes: Some(attributes),
+        ok: true,
+        promised: None,
+    }
+}
+
+fn rejected(instance: &EInstance) -> EReply {
+    EReply {
+        attributes: None,
+        ok: false,
+        promised: instance.promised.clone(),
+    }
+}
+
+impl State {
+    /// 按日志中的顺序恢复，同一实例以最后一条记录为准
+    pub(crate) fn restore(records: Vec<EInstance>) -> Self {
+        let mut state = State::default();
+        for instance in records {
+            state.apply(instance);
+        }
+        state
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub(crate) fn instances(&self) -> impl Iterator<Item = &EInstance> {
+        self.instances.values()
+    }
+
+    fn get(&self, (replica, slot): (i64, i64)) -> EInstance {
+        self.instances
+            .get(&(replica, slot))
+            .cloned()
+            .unwrap_or_else(|| EInstance {
+                id: Some(EInstanceId { replica, slot }),
+                ..Default::default()
+            })
+    }
+
+    /// 合并本地看到的冲突命令，seq 大于所有冲突命令，deps 包含它们
+    ///
+    /// 已提交的实例，以及在不小于该 ballot 时 accept 的实例保持不变；其余情况下 ballot 小于承诺过的 ballot 时拒绝。
+    fn pre_accept(&self, mut command: ECommand) -> (EReply, Option<EInstance>) {
+        let id = id_of(&command);
+        let ballot = ballot_of(&command);
+        let mut instance = self.get(id);
+        if let Some(existing) = &instance.command {
+            let status = instance.status();
+            if status == EStatus::Committed
+                || (status == EStatus::Accepted && ballot_of(existing) >= ballot)
+            {
+                return (
+                    accepted(existing.attributes.clone().unwrap_or_default()),
+                    None,
+                );
+            }
+        }
+        if instance.promised.clone().unwrap_or_default() > ballot {
+            return (rejected(&instance), None);
+        }
+
+        let attrs = command.attributes.get_or_insert_with(Default::default);
+        if let Some(latest) = self.latest.get(&command.key) {
+            for (replica, slot) in latest {
+                if (*replica, *slot) == id {
+                    continue;
+                }
+                let seq = self.instances[&(*replica, *slot)]
+                    .command
+                    .as_ref()
+                    .and_then(|c| c.attributes.as_ref())
+                    .map_or(0, |a| a.seq);
+                attrs.seq = attrs.seq.max(seq + 1);
+                let dep = EInstanceId {
+                    replica: *replica,
+                    slot: *slot,
+                };
+                if !attrs.deps.contains(&dep) {
+                    attrs.deps.push(dep);
+                }
+            }
+        }
+        attrs.seq = attrs.seq.max(1);
+        attrs.deps.sort_by_key(|d| (d.replica, d.slot));
+        let reply = accepted(attrs.clone());
+        instance.promised = Some(ballot);
+        instance.command = Some(command);
+        instance.set_status(EStatus::PreAccepted);
+        (reply, Some(instance))
+    }
+
+    /// 记录待 accept 或已选定的属性，已提交的实例保持不变
+    ///
+    /// 提交的属性已经被选定，不受承诺过的 ballot 限制。
+    fn accept(&self, command: ECommand, status: EStatus) -> (EReply, Option<EInstance>) {
+        let mut instance = self.get(id_of(&command));
+        if instance.status() == EStatus::Committed {
+            if let Some(existing) = &instance.command {
+                return (
+                    accepted(existing.attributes.clone().unwrap_or_default()),
+                    None,
+                );
+            }
+        }
+        let ballot = ballot_of(&command);
+        let promised = instance.promised.clone().unwrap_or_default();
+        if status == EStatus::Accepted && promised > ballot {
+            return (rejected(&instance), None);
+        }
+        let reply = accepted(command.attributes.clone().unwrap_or_default());
+        instance.promised = Some(promised.max(ballot));
+        instance.command = Some(command);
+        instance.set_status(status);
+        (reply, Some(instance))
+    }
+
+    /// 承诺不再接受小于 ballot 的请求，返回实例的状态
+    fn prepare(&self, id: (i64, i64), ballot: RoundNum) -> (EPrepareReply, Option<EInstance>) {
+        let mut instance = self.get(id);
+        let promised = instance.promised.clone().unwrap_or_default();
+        if ballot <= promised {
+            let reply = EPrepareReply {
+                ok: false,
+                promised: Some(promised),
+                instance: None,
+            };
+            return (reply, None);
+        }
+        instance.promised = Some(ballot.clone());
+        let reply = EPrepareReply {
+            ok: true,
+            promised: Some(ballot),
+            instance: Some(instance.clone()),
+        };
+        (reply, Some(instance))
+    }
+
+    /// 保存已写入日志的实例状态
+    fn apply(&mut self, instance: EInstance) {
+        let id = instance
+            .id
+            .as_ref()
+            .map(|id| (id.replica, id.slot))
+            .unwrap_or_default();
+        if let Some(command) = &instance.command {
+            let latest = self.latest.entry(command.key.clone()).or_default();
+            let slot = latest.entry(id.0).or_insert(id.1);
+            *slot = (*slot).max(id.1);
+        }
+        self.instances.insert(id, instance);
+    }
+
+    fn committed(&self, key: &str) -> Vec<ECommand> {
+        self.instances
+            .values()
+            .filter(|i| i.status() == EStatus::Committed)
+            .filter_map(|i| i.command.clone())
+            .filter(|c| c.key == key)
+            .collect()
+    }
+}
+
+/// EPaxos 的 Acceptor 端，由 [`PaxosService::epaxos`] 创建，与其共享指标和日志
+#[derive(Debug, Clone)]
+pub struct EpaxosService {
+    service: PaxosService,
+}
+
+impl EpaxosService {
+    pub(crate) fn new(service: PaxosService) -> Self {
+        EpaxosService { service }
+    }
+
+    /// 在状态锁内算出新的状态，写入日志后才生效
+    #[allow(clippy::result_large_err)]
+    fn handle<R>(
+        &self,
+        method: &str,
+        f: impl FnOnce(&State) -> (R, Option<EInstance>),
+    ) -> Result<R, Status> {
+        let start = Instant::now();
+        let reply = {
+            let mut state = self.service.epaxos.lock().unwrap();
+            let (reply, changed) = f(&state);
+            if let Some(instance) = changed {
+                self.service.persist_epaxos(&instance).map_err(internal)?;
+                state.apply(instance);
+            }
+            reply
+        };
+        self.service.maybe_compact().map_err(internal)?;
+        self.service.metrics().observe(method, start);
+        Ok(reply)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handle_command(
+        &self,
+        method: &str,
+        request: Request<ECommand>,
+        status: EStatus,
+    ) -> Result<Response<EReply>, Status> {
+        if let Some(status) = auth::deny_replica(request.metadata(), actor(request.get_ref())) {
+            return Err(status);
+        }
+        let command = request.into_inner();
+        debug!(method, key = %command.key, id = ?command.id, ballot = ?command.ballot, "epaxos request");
+        let reply = self.handle(method, |state| match status {
+            EStatus::PreAccepted => state.pre_accept(command),
+            _ => state.accept(command, status),
+        })?;
+        Ok(Response::new(reply))
+    }
+}
+
+#[tonic::async_trait]
+impl Epaxos for EpaxosService {
+    async fn pre_accept(&self, request: Request<ECommand>) -> Result<Response<EReply>, Status> {
+        self.handle_command("pre_accept", request, EStatus::PreAccepted)
+    }
+
+    async fn accept(&self, request: Request<ECommand>) -> Result<Response<EReply>, Status> {
+        self.handle_command("epaxos_accept", request, EStatus::Accepted)
+    }
+
+    async fn commit(&self, request: Request<ECommand>) -> Result<Response<EReply>, Status> {
+        self.handle_command("commit", request, EStatus::Committed)
+    }
+
+    async fn prepare(
+        &self,
+        request: Request<EPrepareRequest>,
+    ) -> Result<Response<EPrepareReply>, Status> {
+        let ballot = request.get_ref().ballot.clone().unwrap_or_default();
+        if let Some(status) = auth::deny_replica(request.metadata(), ballot.proposer_id) {
+            return Err(status);
+        }
+        let id = request
+            .into_inner()
+            .id
+            .map(|id| (id.replica, id.slot))
+            .unwrap_or_default();
+        debug!(?id, ?ballot, "epaxos prepare");
+        let reply = self.handle("epaxos_prepare", |state| state.prepare(id, ballot))?;
+        Ok(Response::new(reply))
+    }
+
+    async fn committed(
+        &self,
+        request: Request<ECommittedRequest>,
+    ) -> Result<Response<ECommittedReply>, Status> {
+        let commands = self
+            .service
+            .epaxos
+            .lock()
+            .unwrap()
+            .committed(&request.get_ref().key);
+        Ok(Response::new(ECommittedReply { commands }))
+    }
+}
+
+/// 已提交的命令，fast 表示在一个来回内选定
+#[derive(Debug, Clone, PartialEq)]
+pub struct ECommit {
+    pub command: ECommand,
+    pub fast: bool,
+}
+
+/// 命令的发起者，向所有已连接的 Acceptor 广播，也负责恢复发起者失联的实例
+pub(crate) struct Leader {
+    pub(crate) clients: Vec<(usize, EpaxosClient<Channel>)>,
+    pub(crate) n: usize,
+    pub(crate) quorum: Arc<dyn QuorumSystem>,
+    pub(crate) metrics: Arc<ProposerMetrics>,
+    pub(crate) ballots: Arc<BallotAllocator>,
+    pub(crate) token: Option<String>,
+    pub(crate) rpc_timeout: Option<Duration>,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    PreAccept,
+    Accept,
+    Commit,
+}
+
+/// 出现次数最多的属性及其次数
+fn most_common<'a>(attrs: &[&'a EAttributes]) -> Option<(&'a EAttributes, usize)> {
+    attrs
+        .iter()
+        .map(|a| (*a, attrs.iter().filter(|b| **b == *a).count()))
+        .max_by_key(|(_, votes)| *votes)
+}
+
+/// 合并各 Acceptor 给出的属性：seq 取最大值，deps 取并集
+fn merge(attrs: &[&EAttributes]) -> EAttributes {
+    let mut merged = EAttributes::default();
+    for attrs in attrs {
+        merged.seq = merged.seq.max(attrs.seq);
+        for dep in &attrs.deps {
+            if !merged.deps.contains(dep) {
+                merged.deps.push(dep.clone());
+            }
+        }
+    }
+    merged.deps.sort_by_key(|d| (d.replica, d.slot));
+    merged
+}
+
+impl Leader {
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(token) = &self.token {
+            auth::bearer(&mut request, token);
+        }
+        if let Some(timeout) = self.rpc_timeout {
+            grpc_timeout(&mut request, timeout);
+        }
+        request
+    }
+
+    /// 并行发送给所有 Acceptor，返回成功的应答
+    async fn call_all<T, F, Fut>(&self, method: &'static str, call: F) -> Vec<(usize, T)>
+    where
+        F: Fn(EpaxosClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let f = self.clients.iter().map(|(index, client)| {
+            let call = call(client.clone());
+            async move {
+                let start = Instant::now();
+                let r = within(self.rpc_timeout, call)
+                    .await
+                    .map(|r| r.map_err(ProposeError::from))
+                    .unwrap_or(Err(ProposeError::Timeout));
+                self.metrics.observe(method, start);
+                match r {
+                    Ok(reply) => Some((*index, reply.into_inner())),
+                    Err(e) => {
+                        warn!(acceptor = index, error = %e, method, "epaxos request failed");
+                        None
+                    }
+                }
+            }
+        });
+        join_all(f).await.into_iter().flatten().collect()
+    }
+
+    async fn broadcast(&self, phase: Phase, command: &ECommand) -> Vec<(usize, EReply)> {
+        let request = || self.request(command.clone());
+        match phase {
+            Phase::PreAccept => {
+                self.call_all("pre_accept", |mut client| {
+                    let request = request();
+                    async move { client.pre_accept(request).await }
+                })
+                .await
+            }
+            Phase::Accept => {
+                self.call_all("epaxos_accept", |mut client| {
+                    let request = request();
+                    async move { client.accept(request).await }
+                })
+                .await
+            }
+            Phase::Commit => {
+                self.call_all("commit", |mut client| {
+                    let request = request();
+                    async move { client.commit(request).await }
+                })
+                .await
+            }
+        }
+    }
+
+    /// 接受请求的 Acceptor 给出的属性，不足 quorum 时返回错误
+    fn check_quorum(
+        &self,
+        replies: Vec<(usize, EReply)>,
+    ) -> Result<Vec<(usize, EAttributes)>, ProposeError> {
+        let mut by: Option<RoundNum> = None;
+        let mut ok = vec![];
+        for (index, reply) in replies {
+            if reply.ok {
+                ok.push((index, reply.attributes.unwrap_or_default()));
+            } else {
+                by = by.max(reply.promised);
+            }
+        }
+        let reachable: Vec<usize> = ok.iter().map(|(i, _)| *i).collect();
+        if self.quorum.is_phase2_quorum(&reachable) {
+            return Ok(ok);
+        }
+        if let Some(by) = by {
+            // 其他 Proposer 正在恢复该实例
+            self.ballots.observe(&by);
+            return Err(ProposeError::Preempted { by });
+        }
+        self.metrics.quorum_failures.inc();
+        Err(ProposeError::NoQuorum {
+            reachable: reachable.len(),
+            needed: self.quorum.phase2_size(),
+        })
+    }
+
+    /// fast quorum 给出相同的属性时直接提交；否则合并 quorum 给出的属性，由 quorum accept 后提交
+    pub(crate) async fn propose(&self, mut command: ECommand) -> Result<ECommit, ProposeError> {
+        let replies = self.check_quorum(self.broadcast(Phase::PreAccept, &command).await)?;
+        let attrs: Vec<&EAttributes> = replies.iter().map(|(_, attrs)| attrs).collect();
+        let fast = match most_common(&attrs) {
+            Some((common, votes)) if votes >= fast_quorum(self.n) => Some(common.clone()),
+            _ => None,
+        };
+        let is_fast = fast.is_some();
+        command.attributes = Some(fast.unwrap_or_else(|| merge(&attrs)));
+        if !is_fast {
+            debug!(key = %command.key, id = ?command.id, "conflict, taking slow path");
+        }
+        let command = self.commit(command, is_fast).await?;
+        self.metrics.proposals.with_label_values(&["chosen"]).inc();
+        Ok(ECommit {
+            command,
+            fast: is_fast,
+        })
+    }
+
+    /// 慢路径上先由 quorum accept，再通知提交；提交通知被 quorum 收到后才返回，
+    /// 之后从任意 phase 1 quorum 都能读到该命令
+    async fn commit(&self, command: ECommand, fast: bool) -> Result<ECommand, ProposeError> {
+        if !fast {
+            self.check_quorum(self.broadcast(Phase::Accept, &command).await)?;
+        }
+        self.check_quorum(self.broadcast(Phase::Commit, &command).await)?;
+        debug!(key = %command.key, id = ?command.id, fast, "command committed");
+        Ok(command)
+    }
+
+    /// 以新的 ballot 恢复实例 id（explicit prepare），返回提交的命令
+    ///
+    /// 在回复的 Acceptor 中：
+    /// - 已有提交的属性时直接沿用；
+    /// - 有 accept 过的属性时，沿用 ballot 最大的一个，它可能已被 quorum 接受；
+    /// - 只有发起者 pre-accept 的结果时，若某组属性已凑齐 fast quorum，它可能已经走快路径提交，沿用它；
+    ///   加上没有回复的 Acceptor 才可能凑齐时无法判断，返回 NoQuorum 等待更多节点恢复；
+    /// - 否则命令没有被选定，以新的 ballot 重新 pre-accept，没有任何 Acceptor 见过命令时提交空操作。
+    ///
+    /// 后三种情况都经过 quorum accept 后才提交。
+    pub(crate) async fn recover(
+        &self,
+        id: EInstanceId,
+        key: &str,
+    ) -> Result<ECommand, ProposeError> {
+        let ballot = self
+            .ballots
+            .next()
+            .map_err(|e| ProposeError::Ballot(e.to_string()))?;
+        info!(?id, key, ?ballot, "recovering epaxos instance");
+        let replies = self
+            .call_all("epaxos_prepare", |mut client| {
+                let request = self.request(EPrepareRequest {
+                    id: Some(id.clone()),
+                    ballot: Some(ballot.clone()),
+                });
+                async move { client.prepare(request).await }
+            })
+            .await;
+
+        let mut by: Option<RoundNum> = None;
+        let mut instances = vec![];
+        for (index, reply) in replies {
+            match reply.instance {
+                Some(instance) if reply.ok => instances.push((index, instance)),
+                _ => by = by.max(reply.promised),
+            }
+        }
+        let reachable: Vec<usize> = instances.iter().map(|(i, _)| *i).collect();
+        if !self.quorum.is_phase1_quorum(&reachable) {
+            if let Some(by) = by {
+                self.ballots.observe(&by);
+                return Err(ProposeError::Preempted { by });
+            }
+            self.metrics.quorum_failures.inc();
+            return Err(ProposeError::NoQuorum {
+                reachable: reachable.len(),
+                needed: self.quorum.phase1_size(),
+            });
+        }
+
+        let commands: Vec<(EStatus, &ECommand)> = instances
+            .iter()
+            .filter_map(|(_, i)| Some((i.status(), i.command.as_ref()?)))
+            .collect();
+        if let Some((_, command)) = commands.iter().find(|(s, _)| *s == EStatus::Committed) {
+            let mut command = (*command).clone();
+            command.ballot = Some(ballot);
+            return self.commit(command, true).await;
+        }
+        let accepted = commands
+            .iter()
+            .filter(|(s, _)| *s == EStatus::Accepted)
+            .max_by_key(|(_, c)| ballot_of(c));
+        let mut command = match accepted {
+            Some((_, command)) => (*command).clone(),
+            None => {
+                self.pre_accepted(&commands, instances.len(), id, key, &ballot)
+                    .await?
+            }
+        };
+        command.ballot = Some(ballot);
+        self.commit(command, false).await
+    }
+
+    /// 只有 pre-accept 结果时，选出恢复实例要 accept 的命令
+    async fn pre_accepted(
+        &self,
+        commands: &[(EStatus, &ECommand)],
+        replied: usize,
+        id: EInstanceId,
+        key: &str,
+        ballot: &RoundNum,
+    ) -> Result<ECommand, ProposeError> {
+        // 之前的恢复者重新 pre-accept 过，说明命令没有走快路径提交
+        let reproposed = commands
+            .iter()
+            .any(|(_, c)| ballot_of(c) > RoundNum::default());
+        let attrs: Vec<&EAttributes> = commands
+            .iter()
+            .filter_map(|(_, c)| c.attributes.as_ref())
+            .collect();
+        let fast = fast_quorum(self.n);
+        let unknown = self.n.saturating_sub(replied);
+        match most_common(&attrs) {
+            Some((common, votes)) if !reproposed && votes >= fast => {
+                let (_, command) = commands
+                    .iter()
+                    .find(|(_, c)| c.attributes.as_ref() == Some(common))
+                    .unwrap();
+                return Ok((*command).clone());
+            }
+            Some((_, votes)) if !reproposed && votes + unknown >= fast => {
+                self.metrics.quorum_failures.inc();
+                return Err(ProposeError::NoQuorum {
+                    reachable: replied,
+                    needed: self.n,
+                });
+            }
+            _ => {}
+        }
+
+        // 优先保留真实的命令，之前的恢复者可能在部分 Acceptor 上 pre-accept 了空操作
+        let mut command = commands
+            .iter()
+            .map(|(_, c)| (*c).clone())
+            .find(|c| !c.value.as_ref().is_some_and(|v| v.noop))
+            .or_else(|| commands.first().map(|(_, c)| (*c).clone()))
+            .unwrap_or_else(|| noop(id, key));
+        command.ballot = Some(ballot.clone());
+        let replies = self.check_quorum(self.broadcast(Phase::PreAccept, &command).await)?;
+        let attrs: Vec<&EAttributes> = replies.iter().map(|(_, attrs)| attrs).collect();
+        command.attributes = Some(merge(&attrs));
+        Ok(command)
+    }
+
+    /// 从 phase 1 quorum 读取 key 上已提交的命令，与提交时确认的 quorum 相交
+    async fn read(
+        &self,
+        key: &str,
+        commands: &mut HashMap<(i64, i64), ECommand>,
+    ) -> Result<(), ProposeError> {
+        let replies = self
+            .call_all("committed", |mut client| {
+                let request = self.request(ECommittedRequest {
+                    key: key.to_string(),
+                });
+                async move { client.committed(request).await }
+            })
+            .await;
+        let reachable: Vec<usize> = replies.iter().map(|(i, _)| *i).collect();
+        if !self.quorum.is_phase1_quorum(&reachable) {
+            self.metrics.quorum_failures.inc();
+            return Err(ProposeError::NoQuorum {
+                reachable: reachable.len(),
+                needed: self.quorum.phase1_size(),
+            });
+        }
+        for (_, reply) in replies {
+            for command in reply.commands {
+                commands.entry(id_of(&command)).or_insert(command);
+            }
+        }
+        Ok(())
+    }
+
+    /// key 上已提交的命令，按执行顺序排列
+    ///
+    /// 有依赖还没有提交时先等待发起者完成，仍未提交的由 [`Leader::recover`] 恢复，
+    /// 所有依赖都提交后才排序，之后提交的命令不会排到已返回的命令之前。空操作不会返回。
+    pub(crate) async fn committed(&self, key: &str) -> Result<Vec<ECommand>, ProposeError> {
+        let mut commands = HashMap::new();
+        self.read(key, &mut commands).await?;
+        let mut waited = false;
+        loop {
+            let missing = missing(&commands);
+            if missing.is_empty() {
+                break;
+            }
+            if !waited {
+                waited = true;
+                tokio::time::sleep(RECOVER_AFTER).await;
+                self.read(key, &mut commands).await?;
+                continue;
+            }
+            for id in missing {
+                let command = self.recover(id, key).await?;
+                commands.insert(id_of(&command), command);
+            }
+        }
+
+        let commands: Vec<ECommand> = commands.into_values().collect();
+        let order = execution_order(&commands);
+        let mut by_id: HashMap<(i64, i64), ECommand> =
+            commands.into_iter().map(|c| (id_of(&c), c)).collect();
+        Ok(order
+            .into_iter()
+            .filter_map(|id| by_id.remove(&(id.replica, id.slot)))
+            .filter(|c| !c.value.as_ref().is_some_and(|v| v.noop))
+            .collect())
+    }
+}
+
+/// 被依赖但还没有读到提交的实例
+fn missing(commands: &HashMap<(i64, i64), ECommand>) -> Vec<EInstanceId> {
+    let mut missing: Vec<EInstanceId> = commands
+        .values()
+        .flat_map(|c| c.attributes.iter().flat_map(|a| a.deps.iter()))
+        .filter(|d| !commands.contains_key(&(d.replica, d.slot)))
+        .cloned()
+        .collect();
+    missing.sort_by_key(|d| (d.replica, d.slot));
+    missing.dedup();
+    missing
+}
+
+/// 写入 key 的命令
+pub(crate) fn command(
+    id: EInstanceId,
+    key: String,
+    value: Value,
+    deps: Vec<EInstanceId>,
+) -> ECommand {
+    ECommand {
+        id: Some(id),
+        key,
+        value: Some(value),
+        attributes: Some(EAttributes { seq: 0, deps }),
+        ballot: None,
+    }
+}
+
+/// 恢复时没有 Acceptor 见过命令的内容，用空操作占住实例，执行时跳过
+fn noop(id: EInstanceId, key: &str) -> ECommand {
+    let value = Value {
+        noop: true,
+        ..Default::default()
+    };
+    command(id, key.to_string(), value, vec![])
+}
+
+/// 已提交命令的执行顺序
+///
+/// 依赖图中的强连通分量按拓扑顺序执行，被依赖的先执行；分量内部按 seq 排序，
+/// seq 相同时按实例 id。依赖了 commands 之外的实例的命令，以及依赖它们的命令，
+/// 要等这些依赖提交后才能执行，不会出现在结果中。
+pub fn execution_order(commands: &[ECommand]) -> Vec<EInstanceId> {
+    let index: HashMap<(i64, i64), usize> = commands
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (id_of(c), i))
+        .collect();
+    let deps = |c: &ECommand| -> Vec<(i64, i64)> {
+        c.attributes
+            .iter()
+            .flat_map(|a| a.deps.iter())
+            .map(|d| (d.replica, d.slot))
+            .collect()
+    };
+    let edges: Vec<Vec<usize>> = commands
+        .iter()
+        .map(|c| {
+            deps(c)
+                .iter()
+                .filter_map(|d| index.get(d).copied())
+                .collect()
+        })
+        .collect();
+
+    let mut tarjan = Tarjan {
+        edges: &edges,
+        next: 0,
+        index: vec![None; commands.len()],
+        low: vec![0; commands.len()],
+        stack: vec![],
+        on_stack: vec![false; commands.len()],
+        components: vec![],
+    };
+    for v in 0..commands.len() {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+
+    let key = |i: &usize| {
+        let (replica, slot) = id_of(&commands[*i]);
+        let seq = commands[*i].attributes.as_ref().map(|a| a.seq).unwrap_or(0);
+        (seq, replica, slot)
+    };
+    // Tarjan 先输出没有未处理依赖的分量，正好是执行顺序；
+    // 分量依赖的其他分量都已处理过，其中有被阻塞的，该分量也被阻塞
+    let mut blocked = vec![false; commands.len()];
+    let mut order = vec![];
+    for mut component in tarjan.components {
+        let stuck = component.iter().any(|&v| {
+            deps(&commands[v])
+                .iter()
+                .any(|d| index.get(d).is_none_or(|&w| blocked[w]))
+        });
+        if stuck {
+            for &v in &component {
+                blocked[v] = true;
+            }
+            continue;
+        }
+        component.sort_by_key(key);
+        order.extend(component.into_iter().map(|i| {
+            let (replica, slot) = id_of(&commands[i]);
+            EInstanceId { replica, slot }
+        }));
+    }
+    order
+}
+
+struct Tarjan<'a> {
+    edges: &'a [Vec<usize>],
+    next: usize,
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, v: usize) {
+        self.index[v] = Some(self.next);
+        self.low[v] = self.next;
+        self.next += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+        for &w in &self.edges[v] {
+            match self.index[w] {
+                None => {
+                    self.visit(w);
+                    self.low[v] = self.low[v].min(self.low[w]);
+                }
+                Some(i) if self.on_stack[w] => self.low[v] = self.low[v].min(i),
+                _ => {}
+            }
+        }
+        if Some(self.low[v]) == self.index[v] {
+            let mut component = vec![];
+            while let Some(w) = self.stack.pop() {
+                self.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{start_server, value};
+    use crate::*;
+    use scopeguard::defer;
+
+    fn committed(replica: i64, slot: i64, seq: i64, deps: &[(i64, i64)]) -> ECommand {
+        let deps = deps
+            .iter()
+            .map(|(replica, slot)| EInstanceId {
+                replica: *replica,
+                slot: *slot,
+            })
+            .collect();
+        let mut command = command(
+            EInstanceId { replica, slot },
+            "k".to_string(),
+            value(0),
+            deps,
+        );
+        command.attributes.as_mut().unwrap().seq = seq;
+        command
+    }
+
+    fn values(log: Vec<ECommand>) -> Vec<i64> {
+        log.into_iter().map(|c| c.value.unwrap().value).collect()
+    }
+
+    #[test]
+    fn test_fast_quorum() {
+        let sizes: Vec<usize> = (1..8).map(fast_quorum).collect();
+        assert_eq!(sizes, vec![1, 2, 2, 3, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_execution_order() {
+        // 1.1 <- 2.1 <-> 3.1，2.1 和 3.1 互相依赖，按 seq 排序；
+        // 4.1 依赖还没有提交的 5.5，4.1 和依赖它的 6.1 都不能执行
+        let commands = vec![
+            committed(3, 1, 2, &[(2, 1)]),
+            committed(2, 1, 3, &[(1, 1), (3, 1)]),
+            committed(1, 1, 1, &[]),
+            committed(4, 1, 9, &[(5, 5)]),
+            committed(6, 1, 10, &[(4, 1), (1, 1)]),
+        ];
+        let order: Vec<(i64, i64)> = execution_order(&commands)
+            .into_iter()
+            .map(|id| (id.replica, id.slot))
+            .collect();
+        assert_eq!(order, vec![(1, 1), (3, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn test_instance_ballots() {
+        let mut state = State::default();
+        let apply = |state: &mut State, (reply, changed): (EReply, Option<EInstance>)| {
+            if let Some(instance) = changed {
+                state.apply(instance);
+            }
+            reply
+        };
+        let command = committed(1, 1, 0, &[]);
+        let reply = state.pre_accept(command.clone());
+        assert!(apply(&mut state, reply).ok);
+
+        // 恢复者承诺 ballot 1 之后，发起者在 ballot 0 的请求被拒绝
+        let ballot = RoundNum {
+            number: 1,
+            proposer_id: 2,
+        };
+        let (reply, changed) = state.prepare((1, 1), ballot.clone());
+        assert!(reply.ok);
+        assert_eq!(reply.instance.unwrap().command.unwrap().key, "k");
+        state.apply(changed.unwrap());
+        let reply = state.accept(command.clone(), EStatus::Accepted);
+        assert!(!apply(&mut state, reply).ok);
+        assert!(!state.prepare((1, 1), ballot.clone()).0.ok);
+
+        let mut recovered = command.clone();
+        recovered.ballot = Some(ballot);
+        let reply = state.accept(recovered.clone(), EStatus::Accepted);
+        assert!(apply(&mut state, reply).ok);
+        // 已选定的属性不受承诺限制，提交后不再改变
+        let reply = state.accept(command.clone(), EStatus::Committed);
+        assert!(apply(&mut state, reply).ok);
+        let chosen = command.attributes.clone();
+        let mut other = command;
+        other.attributes.as_mut().unwrap().seq = 7;
+        let reply = state.pre_accept(other);
+        assert_eq!(apply(&mut state, reply).attributes, chosen);
+        assert_eq!(state.committed("k").len(), 1);
+
+        // 从日志恢复得到相同的状态
+        let restored = State::restore(state.instances().cloned().collect());
+        assert_eq!(restored.committed("k"), state.committed("k"));
+        assert_eq!(restored.latest, state.latest);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_epaxos_propose() {
+        let servers: Vec<_> = (11140..11143).map(|p| format!("[::1]:{}", p)).collect();
+        let triggers: Vec<_> = servers
+            .iter()
+            .map(|s| start_server(s.clone(), PaxosService::default()))
+            .collect();
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut alice = Client::new(servers.clone(), 31);
+        assert!(alice.connect().await.is_ok());
+        let mut bob = Client::new(servers.clone(), 32);
+        assert!(bob.connect().await.is_ok());
+
+        // 不同 key 上的命令互不冲突，一个来回即选定
+        assert!(
+            alice
+                .epaxos_propose("a".to_string(), value(1))
+                .await
+                .unwrap()
+                .fast
+        );
+        assert!(
+            bob.epaxos_propose("b".to_string(), value(2))
+                .await
+                .unwrap()
+                .fast
+        );
+        // 依次写入同一 key 时各 Acceptor 看到相同的依赖
+        let second = alice
+            .epaxos_propose("b".to_string(), value(3))
+            .await
+            .unwrap();
+        assert!(second.fast);
+        assert_eq!(second.command.attributes.unwrap().seq, 2);
+
+        // 只有第一个 Acceptor 看到了并发的 98.1，另外两个给出相同的属性，凑齐 fast quorum
+        let mut clients = vec![];
+        for server in &servers[..2] {
+            clients.push(
+                EpaxosClient::connect(format!("http://{}", server))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let mut concurrent = committed(98, 1, 0, &[]);
+        concurrent.key = "c".to_string();
+        clients[0].pre_accept(concurrent).await.unwrap();
+        let commit = bob.epaxos_propose("c".to_string(), value(4)).await.unwrap();
+        assert!(commit.fast);
+        assert!(commit.command.attributes.unwrap().deps.is_empty());
+
+        // 前两个 Acceptor 各自看到不同的并发命令，三个应答各不相同，需要走慢路径
+        let mut concurrent = committed(99, 1, 0, &[]);
+        concurrent.key = "c".to_string();
+        clients[1].pre_accept(concurrent).await.unwrap();
+        let commit = bob.epaxos_propose("c".to_string(), value(5)).await.unwrap();
+        assert!(!commit.fast);
+        let attrs = commit.command.attributes.clone().unwrap();
+        let deps: Vec<(i64, i64)> = attrs.deps.iter().map(|d| (d.replica, d.slot)).collect();
+        assert_eq!(deps, vec![(32, 2), (98, 1), (99, 1)]);
+
+        // 98.1 和 99.1 的发起者已经失联，读取时恢复它们，不同客户端读到相同的顺序
+        let log = alice.epaxos_log("c").await.unwrap();
+        let ids: Vec<i64> = log.iter().map(|c| c.id.clone().unwrap().replica).collect();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[0], 32);
+        assert_eq!(values(bob.epaxos_log("c").await.unwrap()), values(log));
+        assert_eq!(values(alice.epaxos_log("b").await.unwrap()), vec![2, 3]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_epaxos_recovery() {
+        let servers: Vec<_> = (11230..11233).map(|p| format!("[::1]:{}", p)).collect();
+        let dirs: Vec<_> = servers
+            .iter()
+            .map(|_| tempfile::tempdir().unwrap())
+            .collect();
+        let start = |i: usize| {
+            let service = PaxosService::open(dirs[i].path(), FsyncMode::Never).unwrap();
+            start_server(servers[i].clone(), service)
+        };
+        let triggers = std::sync::Mutex::new((0..3).map(start).collect::<Vec<_>>());
+        defer! {
+            for t in triggers.lock().unwrap().iter() {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut alice = Client::new(servers.clone(), 33);
+        assert!(alice.connect().await.is_ok());
+        let mut bob = Client::new(servers.clone(), 34);
+        assert!(bob.connect().await.is_ok());
+
+        // 77.1 的发起者在所有 Acceptor pre-accept 之后、提交之前崩溃
+        let orphan = command(
+            EInstanceId {
+                replica: 77,
+                slot: 1,
+            },
+            "d".to_string(),
+            value(7),
+            vec![],
+        );
+        for server in &servers {
+            let mut client = EpaxosClient::connect(format!("http://{}", server))
+                .await
+                .unwrap();
+            client.pre_accept(orphan.clone()).await.unwrap();
+        }
+        assert!(
+            bob.epaxos_propose("d".to_string(), value(8))
+                .await
+                .unwrap()
+                .fast
+        );
+
+        // 三个节点中坏一个，剩下两个仍是 fast quorum
+        triggers.lock().unwrap()[2].trigger();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(
+            alice
+                .epaxos_propose("e".to_string(), value(1))
+                .await
+                .unwrap()
+                .fast
+        );
+        // 77.1 凑齐过 fast quorum，恢复时沿用其属性，排在依赖它的命令之前
+        assert_eq!(values(alice.epaxos_log("d").await.unwrap()), vec![7, 8]);
+
+        // 全部重启后从日志恢复已提交的命令
+        for t in triggers.lock().unwrap().iter() {
+            t.trigger();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        *triggers.lock().unwrap() = (0..3).map(start).collect();
+        let mut carol = Client::new(servers.clone(), 35);
+        assert!(carol.connect().await.is_ok());
+        assert_eq!(values(carol.epaxos_log("d").await.unwrap()), vec![7, 8]);
+        assert_eq!(values(carol.epaxos_log("e").await.unwrap()), vec![1]);
+    }
+}
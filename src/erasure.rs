@@ -0,0 +1,193 @@
+use crate::quorum::Flexible;
+use crate::witness;
+use crate::{Acceptor, Shard, Value};
+use anyhow::{Error, Result};
+use prost::Message;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+/// RS-Paxos 使用的纠删码，第 i 个 Acceptor 保存第 i 个分片
+#[derive(Debug, Clone)]
+pub(crate) struct Erasure {
+    data_shards: usize,
+    total_shards: usize,
+}
+
+impl Erasure {
+    /// 需要至少一个校验分片，分片总数即 Acceptor 数
+    pub(crate) fn new(data_shards: usize, total_shards: usize) -> Result<Self> {
+        if data_shards == 0 || data_shards >= total_shards || total_shards > 256 {
+            return Err(Error::msg(format!(
+                "invalid erasure coding: {} data shards of {}",
+                data_shards, total_shards
+            )));
+        }
+        Ok(Erasure {
+            data_shards,
+            total_shards,
+        })
+    }
+
+    /// 恢复值需要的分片数
+    pub(crate) fn data_shards(&self) -> usize {
+        self.data_shards
+    }
+
+    /// 任意 phase 1 quorum 与 phase 2 quorum 至少有 data_shards 个共同的 Acceptor，
+    /// 被选定的值总能从 prepare 应答中恢复
+    pub(crate) fn quorum(&self) -> Result<Flexible> {
+        let q = (self.total_shards + self.data_shards).div_ceil(2);
+        Flexible::new(self.total_shards, q, q)
+    }
+
+    /// 把 value 编码为 total_shards 个只含分片的值
+    pub(crate) fn encode(&self, value: &Value) -> Vec<Value> {
+        let mut buf = vec![];
+        // 写入 Vec 不会失败
+        let _ = value.encode(&mut buf);
+        let length = buf.len();
+        let size = length.div_ceil(self.data_shards).max(1);
+        buf.resize(size * self.data_shards, 0);
+        let mut shards: Vec<Vec<u8>> = buf.chunks(size).map(|c| c.to_vec()).collect();
+        shards.resize(self.total_shards, vec![0; size]);
+        // 参数已在 new 中检查过
+        let rs = ReedSolomon::new(self.data_shards, self.total_shards - self.data_shards).unwrap();
+        rs.encode(&mut shards).unwrap();
+
+        let digest = witness::digest(value);
+        shards
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| Value {
+                shard: Some(Shard {
+                    index: index as u32,
+                    data_shards: self.data_shards as u32,
+                    total_shards: self.total_shards as u32,
+                    length: length as u64,
+                    digest,
+                    data,
+                }),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// 用 replies 中与 shard 属于同一个值的分片恢复完整的值，分片不足时返回 None
+pub(crate) fn rebuild(shard: &Shard, replies: &[(usize, Acceptor)]) -> Option<Value> {
+    let data_shards = shard.data_shards as usize;
+    let total_shards = shard.total_shards as usize;
+    let rs = ReedSolomon::new(data_shards, total_shards.checked_sub(data_shards)?).ok()?;
+    let mut shards: Vec<Option<Vec<u8>>> = vec![None; total_shards];
+    for s in replies
+        .iter()
+        .filter_map(|(_, acc)| acc.value.as_ref().and_then(|v| v.shard.as_ref()))
+        .filter(|s| s.digest == shard.digest && (s.index as usize) < total_shards)
+    {
+        shards[s.index as usize] = Some(s.data.clone());
+    }
+    if shards.iter().flatten().count() < data_shards {
+        return None;
+    }
+    rs.reconstruct_data(&mut shards).ok()?;
+    let mut buf: Vec<u8> = shards
+        .into_iter()
+        .take(data_shards)
+        .flat_map(|s| s.unwrap_or_default())
+        .collect();
+    buf.truncate(shard.length as usize);
+    let value = Value::decode(buf.as_slice()).ok()?;
+    // 摘要不一致说明混入了损坏的分片
+    (witness::digest(&value) == shard.digest).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::start_server;
+    use crate::*;
+    use scopeguard::defer;
+
+    fn large(n: usize) -> Value {
+        Value {
+            data: (0..n).map(|i| i as u8).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_erasure_coding() {
+        let erasure = Erasure::new(3, 5).unwrap();
+        let quorum = erasure.quorum().unwrap();
+        assert_eq!(quorum.phase1_size(), 4);
+        assert!(Erasure::new(5, 5).is_err());
+
+        let value = large(1000);
+        let shards = erasure.encode(&value);
+        assert_eq!(shards.len(), 5);
+        assert!(shards[0].shard.as_ref().unwrap().data.len() < 400);
+        let replies: Vec<(usize, Acceptor)> = shards
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                (
+                    i,
+                    Acceptor {
+                        value: Some(v),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let shard = replies[4].1.value.clone().unwrap().shard.unwrap();
+        // 任意 3 个分片可以恢复
+        assert_eq!(rebuild(&shard, &replies[2..]), Some(value));
+        assert_eq!(rebuild(&shard, &replies[3..]), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_rs_paxos() {
+        let servers: Vec<_> = (11160..11165).map(|p| format!("[::1]:{}", p)).collect();
+        let nodes: Vec<_> = servers
+            .iter()
+            .map(|s| {
+                let service = PaxosService::default();
+                (start_server(s.clone(), service.clone()), service)
+            })
+            .collect();
+        defer! {
+            for (t, _) in &nodes {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut client = Client::new(servers.clone(), 35);
+        assert!(client.connect().await.is_ok());
+        client.set_erasure_coding(3).unwrap();
+        let value = large(3000);
+        let chosen = client
+            .run_propose("big".to_string(), Some(value.clone()))
+            .await;
+        assert_eq!(chosen, Ok(Some(value.clone())));
+        // 每个保存了值的 Acceptor 只保存约 1/3 的数据，至少 phase 2 quorum 个 Acceptor 已保存
+        let mut stored = 0;
+        for (_, service) in &nodes {
+            let storage = service.storage.lock().unwrap();
+            if let Some(value) = storage.get("big").and_then(|acc| acc.value.clone()) {
+                assert!(value.data.is_empty());
+                assert!(value.shard.unwrap().data.len() <= 1001);
+                stored += 1;
+            }
+        }
+        assert!(stored >= 4);
+
+        // 一个 Acceptor 不可用时仍能从剩下的分片恢复
+        nodes[0].0.trigger();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut reader = Client::new(servers.clone(), 36);
+        assert!(reader.connect().await.is_ok());
+        reader.set_erasure_coding(3).unwrap();
+        let prop = reader.new_propose("big".to_string(), None).unwrap();
+        assert_eq!(prop.decide().await, Ok(Some(value)));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::paxos::RoundNum;
+use std::fmt;
+
+/// Acceptor 拒绝旧配置中的请求时，错误信息的前缀，之后是 Acceptor 已知的 epoch
+pub(crate) const STALE_EPOCH: &str = "stale epoch, current ";
+
+/// 提议失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProposeError {
+    /// 可用的 Acceptor 不足多数派，等节点恢复后再重试
+    NoQuorum { reachable: usize, needed: usize },
+    /// 有 Acceptor 已经承诺了不小于本次的 round，需要换更大的 round 重试
+    Preempted { by: RoundNum },
+    /// 截止时间内没有完成；accept 可能已经被多数派接受，结果未知
+    Timeout,
+    /// 与 Acceptor 的通信失败
+    Transport(String),
+    /// 无法分配新的 ballot，例如 ballot 文件写入失败
+    Ballot(String),
+    /// 需要修复的值只有见证者的摘要，保存完整值的 Acceptor 都不可达
+    ValueUnavailable,
+    /// 成员已经变更到 current，需要先 [`crate::Client::refresh`] 再重试
+    StaleEpoch { current: i64 },
+    /// 以 `\0` 开头的 key 保留给系统实例
+    ReservedKey(String),
+    /// 当前配置不支持该操作，例如 fast round 与自定义 quorum、见证者或纠删码同时使用
+    Unsupported(String),
+}
+
+impl fmt::Display for ProposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProposeError::NoQuorum { reachable, needed } => write!(
+                f,
+                "not enough acceptors: {} reachable, {} needed",
+                reachable, needed
+            ),
+            ProposeError::Preempted { by } => {
+                write!(f, "preempted by round {}.{}", by.number, by.proposer_id)
+            }
+            ProposeError::Timeout => write!(f, "proposal outcome unknown: deadline exceeded"),
+            ProposeError::Transport(e) => write!(f, "transport error: {}", e),
+            ProposeError::Ballot(e) => write!(f, "ballot allocation failed: {}", e),
+            ProposeError::ValueUnavailable => {
+                write!(f, "accepted value is only held by unreachable acceptors")
+            }
+            ProposeError::StaleEpoch { current } => {
+                write!(f, "membership changed to epoch {}", current)
+            }
+            ProposeError::ReservedKey(key) => write!(f, "key {:?} is reserved", key),
+            ProposeError::Unsupported(e) => write!(f, "unsupported: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProposeError {}
+
+impl ProposeError {
+    /// 双向流应答中的错误信息
+    pub(crate) fn from_message(message: String) -> Self {
+        match stale_epoch(&message) {
+            Some(current) => ProposeError::StaleEpoch { current },
+            None => ProposeError::Transport(message),
+        }
+    }
+}
+
+fn stale_epoch(message: &str) -> Option<i64> {
+    message.strip_prefix(STALE_EPOCH)?.parse().ok()
+}
+
+impl From<tonic::Status> for ProposeError {
+    fn from(status: tonic::Status) -> Self {
+        if status.code() == tonic::Code::FailedPrecondition {
+            if let Some(current) = stale_epoch(status.message()) {
+                return ProposeError::StaleEpoch { current };
+            }
+        }
+        ProposeError::Transport(format!("{:?}: {}", status.code(), status.message()))
+    }
+}
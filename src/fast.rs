@@ -0,0 +1,96 @@
+use crate::client::Propose;
+use crate::error::ProposeError;
+use crate::witness;
+use crate::{Acceptor, RoundNum, Value};
+use tracing::debug;
+
+/// 协调者为一个 key 打开的 fast round
+///
+/// 打开后各客户端用同一个 round 直接向 Acceptor 发送 accept，Acceptor 接受先到的值。
+/// 开启认证时 Acceptor 会拒绝 proposer_id 与调用方不一致的请求，fast round 只能由协调者本身使用。
+#[derive(Debug, Clone, PartialEq)]
+pub struct FastRound {
+    pub key: String,
+    pub round: RoundNum,
+}
+
+/// n 个 Acceptor 时 fast quorum 的大小，即 ⌈3n/4⌉
+pub(crate) fn fast_quorum(n: usize) -> usize {
+    (3 * n).div_ceil(4)
+}
+
+/// accept 之前的状态表明该 Acceptor 接受了本次 fast round 中的值
+pub(crate) fn accepted(round: &RoundNum, before: &Acceptor) -> bool {
+    let last_round = before.last_round.clone().unwrap_or_default();
+    let value_round = before.round.clone().unwrap_or_default();
+    last_round <= *round && value_round < *round
+}
+
+/// fast round 冲突后用 classic round 恢复，返回被选定的值
+///
+/// 有更晚的 classic round 接受过值时沿用其中 round 最大的值；
+/// 否则在 fast round 的投票中选择可能已被 fast quorum 选定的值，没有时提交 prop 自己的值。
+pub(crate) async fn recover(
+    mut prop: Propose,
+    fast: &RoundNum,
+    n: usize,
+) -> Result<Option<Value>, ProposeError> {
+    let replies = prop.prepare().await?;
+    prop.check_phase1(&replies)?;
+
+    let votes: Vec<(RoundNum, Value)> = replies
+        .iter()
+        .filter_map(|(_, acc)| {
+            let round = acc.round.clone().unwrap_or_default();
+            acc.value.clone().map(|v| (round, v))
+        })
+        .collect();
+    let highest = votes.iter().map(|(round, _)| round.clone()).max();
+    let value = match highest {
+        Some(round) if round > *fast => {
+            votes.into_iter().find(|(r, _)| *r == round).map(|(_, v)| v)
+        }
+        Some(round) if round == *fast => {
+            // 在 replies 之外的 Acceptor 全部投给同一个值时仍可能达到 fast quorum
+            let needed = (replies.len() + fast_quorum(n)).saturating_sub(n);
+            let fast_votes: Vec<Value> = votes
+                .into_iter()
+                .filter(|(r, _)| *r == round)
+                .map(|(_, v)| v)
+                .collect();
+            // 见证者的投票只有摘要，按摘要比较
+            fast_votes
+                .iter()
+                .find(|v| {
+                    let d = witness::digest(v);
+                    fast_votes
+                        .iter()
+                        .filter(|w| witness::digest(w) == d)
+                        .count()
+                        >= needed
+                })
+                .cloned()
+        }
+        _ => None,
+    };
+    if let Some(value) = value {
+        let value = witness::restore(value, &replies)?;
+        debug!(?value, "recovered value from fast round");
+        prop.set_value(value);
+    }
+
+    let result = prop.run_phase2().await;
+    prop.finish(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_quorum() {
+        assert_eq!(fast_quorum(3), 3);
+        assert_eq!(fast_quorum(4), 3);
+        assert_eq!(fast_quorum(5), 4);
+    }
+}
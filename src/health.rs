@@ -0,0 +1,172 @@
+use crate::tls;
+use anyhow::{Error, Result};
+use futures::future::join_all;
+use std::time::Duration;
+use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::Code;
+use tonic_health::proto::health_check_response::ServingStatus;
+use tonic_health::proto::health_client::HealthClient;
+use tonic_health::proto::HealthCheckRequest;
+use tonic_health::server::HealthReporter;
+use tracing::{info, warn};
+
+/// Acceptor 在 grpc.health.v1 中注册的服务名
+pub const PAXOS_SERVICE_NAME: &str = "paxos.Paxos";
+
+/// 探测一个节点时，连接或健康检查的最长等待时间
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 查询节点上 Acceptor 是否就绪
+///
+/// 没有实现健康检查服务的节点视为就绪，以兼容旧版本的服务端。
+/// 超过 [`PROBE_TIMEOUT`] 没有应答时返回错误，调用方视为节点不可用。
+pub async fn check_health(channel: Channel) -> Result<bool> {
+    let mut client = HealthClient::new(channel);
+    let request = HealthCheckRequest {
+        service: PAXOS_SERVICE_NAME.to_string(),
+    };
+    let result = match tokio::time::timeout(PROBE_TIMEOUT, client.check(request)).await {
+        Ok(result) => result,
+        Err(_) => return Err(Error::msg("health check timed out")),
+    };
+    match result {
+        Ok(resp) => Ok(resp.get_ref().status == ServingStatus::Serving as i32),
+        Err(status) if status.code() == Code::Unimplemented => Ok(true),
+        Err(status) if status.code() == Code::NotFound => Ok(false),
+        Err(status) => Err(status.into()),
+    }
+}
+
+/// 节点自身加上可达的其他节点构成多数派时，才算知道集群
+fn knows_quorum(reachable: usize, peers: usize) -> bool {
+    let total = peers + 1;
+    let quorum = total / 2 + 1;
+    reachable + 1 >= quorum
+}
+
+async fn reachable(peer: &str, tls: Option<&ClientTlsConfig>) -> bool {
+    let dst = match tls::endpoint(peer, tls) {
+        Ok(dst) => dst,
+        Err(_) => return false,
+    };
+    matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, dst.connect()).await,
+        Ok(Ok(_))
+    )
+}
+
+/// 持续探测其他节点，更新本节点 Acceptor 的就绪状态
+///
+/// 调用前存储必须已经恢复完成。能连上多数派中的其他节点时报告 SERVING，
+/// 否则报告 NOT_SERVING；没有配置其他节点时视为单节点集群。
+pub async fn watch_peers(
+    mut reporter: HealthReporter,
+    peers: Vec<String>,
+    tls: Option<ClientTlsConfig>,
+    interval: Duration,
+) {
+    let mut ready = None;
+    loop {
+        let probes = peers.iter().map(|p| reachable(p, tls.as_ref()));
+        let count = join_all(probes).await.into_iter().filter(|ok| *ok).count();
+        let now = knows_quorum(count, peers.len());
+        if ready != Some(now) {
+            let status = if now {
+                info!(reachable = count, peers = peers.len(), "acceptor ready");
+                tonic_health::ServingStatus::Serving
+            } else {
+                warn!(reachable = count, peers = peers.len(), "acceptor not ready");
+                tonic_health::ServingStatus::NotServing
+            };
+            reporter
+                .set_service_status(PAXOS_SERVICE_NAME, status)
+                .await;
+            ready = Some(now);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{spawn, start_server};
+    use crate::*;
+    use futures::Stream;
+    use scopeguard::defer;
+    use std::pin::Pin;
+    use tokio::time::Instant;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status};
+    use tonic_health::proto::health_server::{Health, HealthServer};
+    use tonic_health::proto::HealthCheckResponse;
+
+    /// 接受连接但健康检查永远不应答的节点
+    struct HungHealth;
+
+    #[tonic::async_trait]
+    impl Health for HungHealth {
+        async fn check(
+            &self,
+            _request: Request<HealthCheckRequest>,
+        ) -> Result<Response<HealthCheckResponse>, Status> {
+            futures::future::pending().await
+        }
+
+        type WatchStream = Pin<
+            Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send + Sync + 'static>,
+        >;
+
+        async fn watch(
+            &self,
+            _request: Request<HealthCheckRequest>,
+        ) -> Result<Response<Self::WatchStream>, Status> {
+            futures::future::pending().await
+        }
+    }
+
+    fn start_hung(address: String) -> triggered::Trigger {
+        spawn(address, |addr, signal| {
+            Server::builder()
+                .add_service(HealthServer::new(HungHealth))
+                .add_service(PaxosServer::new(PaxosService::default()))
+                .serve_with_shutdown(addr, signal)
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_hung_health_check() {
+        let servers: Vec<_> = (11190..11193).map(|p| format!("[::1]:{}", p)).collect();
+        let triggers: Vec<_> = servers
+            .iter()
+            .enumerate()
+            .map(|(i, s)| match i {
+                2 => start_hung(s.clone()),
+                // 没有健康检查服务的节点视为就绪
+                _ => start_server(s.clone(), PaxosService::default()),
+            })
+            .collect();
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let start = Instant::now();
+        let mut client = Client::new(servers, 1);
+        assert!(client.connect().await.is_ok());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(client.health()[2].1, ServerHealth::Down);
+        assert_ne!(client.health()[0].1, ServerHealth::Down);
+    }
+
+    #[test]
+    fn test_knows_quorum() {
+        assert!(knows_quorum(0, 0));
+        assert!(!knows_quorum(0, 2));
+        assert!(knows_quorum(1, 2));
+        assert!(!knows_quorum(1, 4));
+        assert!(knows_quorum(2, 4));
+    }
+}
@@ -0,0 +1,471 @@
+use crate::auth::{self, bearer_interceptor};
+use crate::client::grpc_timeout;
+use crate::paxos::learner_client::LearnerClient;
+use crate::paxos::learner_server::Learner;
+use crate::paxos::peer_client::PeerClient;
+use crate::quorum::QuorumSystem;
+use crate::server::{instance_key, split_instance_key};
+use crate::tls;
+use crate::{
+    DigestsRequest, GetInstanceRequest, LearnReply, Learned, LearnedRequest, RoundNum, Value,
+    WatchRequest,
+};
+use anyhow::{Error, Result};
+use futures::channel::mpsc;
+use futures::{SinkExt, Stream};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::{Request, Response, Status};
+use tracing::{debug, info, warn};
+
+/// 订阅者落后超过这么多条时丢弃其中最早的
+const WATCH_BUFFER: usize = 1024;
+
+/// 不参与投票的 Learner
+///
+/// 值由 Proposer 推送，推送丢失或 Learner 宕机期间错过的值由 [`catch_up`] 从 Acceptor 补齐。
+/// 状态只保存在内存中，重启后同样由 catch-up 重新学到。
+#[derive(Debug, Clone)]
+pub struct LearnerService {
+    learned: Arc<Mutex<HashMap<String, Learned>>>,
+    updates: broadcast::Sender<Learned>,
+}
+
+impl Default for LearnerService {
+    fn default() -> Self {
+        let (updates, _) = broadcast::channel(WATCH_BUFFER);
+        LearnerService {
+            learned: Default::default(),
+            updates,
+        }
+    }
+}
+
+fn round_of(learned: &Learned) -> RoundNum {
+    learned.round.clone().unwrap_or_default()
+}
+
+impl LearnerService {
+    /// 已学到的 key/version 的值
+    pub fn get(&self, key: &str, version: i64) -> Option<Value> {
+        let learned = self.learned.lock().unwrap();
+        learned
+            .get(&instance_key(key, version))
+            .and_then(|l| l.value.clone())
+    }
+
+    /// 保存 round 更大的值，返回是否更新
+    fn learn(&self, learned: Learned) -> bool {
+        let mut map = self.learned.lock().unwrap();
+        let key = instance_key(&learned.key, learned.version);
+        if let Some(old) = map.get(&key) {
+            if round_of(old) >= round_of(&learned) {
+                return false;
+            }
+        }
+        map.insert(key, learned.clone());
+        // 没有订阅者时发送失败，可以忽略
+        let _ = self.updates.send(learned);
+        true
+    }
+}
+
+impl LearnerService {
+    /// 从 Acceptor 补齐错过的值，返回新学到的值的个数
+    ///
+    /// 在同一个 round 接受同一个值的 Acceptor 构成 quorum 的 phase 2 quorum 时该值已被选定，
+    /// 从其中保存完整值的 Acceptor 拉取。quorum 中的下标对应 acceptors 中的位置。
+    /// token 须在 Acceptor 的 `auth.nodes` 中。
+    pub async fn catch_up_with(
+        &self,
+        acceptors: &[String],
+        quorum: &dyn QuorumSystem,
+        tls: Option<&ClientTlsConfig>,
+        token: Option<&str>,
+    ) -> Result<usize> {
+        if quorum.acceptors() != acceptors.len() {
+            return Err(Error::msg(format!(
+                "quorum {:?} covers {} acceptors but {} are given",
+                quorum,
+                quorum.acceptors(),
+                acceptors.len()
+            )));
+        }
+        let mut clients = HashMap::new();
+        // (存储 key, round, 值的摘要) -> 接受了该值的 Acceptor 在 acceptors 中的下标
+        let mut accepted: HashMap<(String, RoundNum, u64), Vec<usize>> = HashMap::new();
+        for (index, addr) in acceptors.iter().enumerate() {
+            let channel = match tls::endpoint(addr, tls)?.connect().await {
+                Ok(channel) => channel,
+                Err(e) => {
+                    warn!(acceptor = %addr, error = %e, "catch-up cannot reach acceptor");
+                    continue;
+                }
+            };
+            let mut client =
+                PeerClient::with_interceptor(channel, bearer_interceptor(token.map(String::from)));
+            let request = DigestsRequest::default();
+            let digests = match client.digests(request).await {
+                Ok(reply) => reply.into_inner().instances,
+                Err(e) => {
+                    warn!(acceptor = %addr, error = %e, "catch-up failed to list digests");
+                    continue;
+                }
+            };
+            for d in digests.into_iter().filter(|d| d.digest != 0) {
+                let round = d.round.unwrap_or_default();
+                accepted
+                    .entry((d.key, round, d.digest))
+                    .or_default()
+                    .push(index);
+            }
+            clients.insert(index, client);
+        }
+
+        let mut learned = 0;
+        for ((stored, round, _), holders) in accepted {
+            if !quorum.is_phase2_quorum(&holders) {
+                continue;
+            }
+            let known = self.learned.lock().unwrap().get(&stored).map(round_of);
+            if known.is_some_and(|known| known >= round) {
+                continue;
+            }
+            // 见证者只有摘要，RS-Paxos 的 Acceptor 只有分片，找一个保存完整值的
+            for i in holders {
+                let request = GetInstanceRequest {
+                    key: stored.clone(),
+                };
+                let client = match clients.get_mut(&i) {
+                    Some(client) => client,
+                    None => continue,
+                };
+                let value = match client.fetch(request).await {
+                    Ok(reply) => reply.into_inner().acceptor.and_then(|acc| acc.value),
+                    Err(e) => {
+                        warn!(key = %stored, error = %e, "catch-up failed to fetch value");
+                        continue;
+                    }
+                };
+                if let Some(value) = value.filter(|v| v.digest == 0 && v.shard.is_none()) {
+                    let (key, version) = split_instance_key(&stored);
+                    let chosen = Learned {
+                        key,
+                        round: Some(round),
+                        value: Some(value),
+                        version,
+                    };
+                    if self.learn(chosen) {
+                        learned += 1;
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(learned)
+    }
+}
+
+/// 后台 catch-up：启动时立即从 Acceptor 补齐一次，之后每隔 interval 一次
+///
+/// quorum 与提议时使用的相同，例如 Flexible Paxos 或 RS-Paxos 的 phase 2 quorum。
+/// Acceptor 开启认证时，token 或本节点证书的 CN 须在其 `auth.nodes` 中。
+pub async fn catch_up(
+    learner: LearnerService,
+    acceptors: Vec<String>,
+    quorum: Arc<dyn QuorumSystem>,
+    tls: Option<ClientTlsConfig>,
+    token: Option<String>,
+    interval: Duration,
+) {
+    loop {
+        match learner
+            .catch_up_with(&acceptors, quorum.as_ref(), tls.as_ref(), token.as_deref())
+            .await
+        {
+            Ok(0) => debug!("catch-up found nothing new"),
+            Ok(n) => info!(learned = n, "caught up from acceptors"),
+            Err(e) => warn!(error = %e, "catch-up failed"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[tonic::async_trait]
+impl Learner for LearnerService {
+    async fn learn(&self, request: Request<Learned>) -> Result<Response<LearnReply>, Status> {
+        if let Some(status) = auth::deny_non_node(request.metadata()) {
+            return Err(status);
+        }
+        let learned = request.into_inner();
+        let key = learned.key.clone();
+        let updated = LearnerService::learn(self, learned);
+        debug!(%key, updated, "value learned");
+        Ok(Response::new(LearnReply {}))
+    }
+
+    async fn get(&self, request: Request<LearnedRequest>) -> Result<Response<Learned>, Status> {
+        let request = request.get_ref();
+        let key = instance_key(&request.key, request.version);
+        let learned = self.learned.lock().unwrap().get(&key).cloned();
+        learned.map(Response::new).ok_or_else(|| {
+            Status::not_found(format!("{}@{} not learned", request.key, request.version))
+        })
+    }
+
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<Learned, Status>> + Send + Sync + 'static>>;
+
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let prefix = request.into_inner().prefix;
+        let mut updates = self.updates.subscribe();
+        let (mut tx, rx) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let learned = match updates.recv().await {
+                    Ok(learned) => learned,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "watcher lagged behind");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if !learned.key.starts_with(&prefix) {
+                    continue;
+                }
+                // 订阅者断开
+                if tx.send(Ok(learned)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(rx)))
+    }
+}
+
+/// Proposer 端：把被选定的值发给所有 Learner，不等待结果；没有送达的值由 Learner 的 catch-up 补齐
+#[derive(Debug, Clone)]
+pub(crate) struct Learners {
+    clients: Vec<LearnerClient<Channel>>,
+    token: Option<String>,
+    rpc_timeout: Option<Duration>,
+    /// 尚未完成的通知数，每完成一个唤醒一次 [`Learners::flush`]
+    pending: Arc<AtomicUsize>,
+    done: Arc<Notify>,
+}
+
+impl Learners {
+    /// 连接在第一次发送时建立
+    pub(crate) fn new(
+        addrs: &[String],
+        tls: Option<&ClientTlsConfig>,
+        token: Option<String>,
+        rpc_timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let mut clients = vec![];
+        for addr in addrs {
+            let channel = tls::endpoint(addr, tls)?.connect_lazy()?;
+            clients.push(LearnerClient::new(channel));
+        }
+        Ok(Learners {
+            clients,
+            token,
+            rpc_timeout,
+            pending: Default::default(),
+            done: Default::default(),
+        })
+    }
+
+    pub(crate) fn publish(&self, key: String, version: i64, round: RoundNum, value: Value) {
+        let learned = Learned {
+            key,
+            round: Some(round),
+            value: Some(value),
+            version,
+        };
+        for client in &self.clients {
+            let mut client = client.clone();
+            let mut request = Request::new(learned.clone());
+            if let Some(token) = &self.token {
+                auth::bearer(&mut request, token);
+            }
+            if let Some(timeout) = self.rpc_timeout {
+                grpc_timeout(&mut request, timeout);
+            }
+            let pending = self.pending.clone();
+            let done = self.done.clone();
+            pending.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                if let Err(e) = client.learn(request).await {
+                    warn!(error = %e, "failed to notify learner");
+                }
+                pending.fetch_sub(1, Ordering::SeqCst);
+                done.notify_one();
+            });
+        }
+    }
+
+    /// 等待已发出的通知完成
+    pub(crate) async fn flush(&self) {
+        while self.pending.load(Ordering::SeqCst) > 0 {
+            self.done.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{spawn, start_server, value};
+    use crate::*;
+    use futures::StreamExt;
+    use scopeguard::defer;
+    use tonic::transport::Server;
+
+    fn start_learner(address: String, learner: LearnerService) -> triggered::Trigger {
+        spawn(address, move |addr, signal| {
+            Server::builder()
+                .add_service(LearnerServer::new(learner))
+                .serve_with_shutdown(addr, signal)
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_learner() {
+        let servers: Vec<_> = (11170..11173).map(|p| format!("[::1]:{}", p)).collect();
+        let learner_addr = "[::1]:11173".to_string();
+        let learner = LearnerService::default();
+        let mut triggers: Vec<_> = servers
+            .iter()
+            .map(|s| start_server(s.clone(), PaxosService::default()))
+            .collect();
+        triggers.push(start_learner(learner_addr.clone(), learner.clone()));
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut watcher = LearnerClient::connect(format!("http://{}", learner_addr))
+            .await
+            .unwrap();
+        let mut updates = watcher
+            .watch(WatchRequest {
+                prefix: "a".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut client = Client::new(servers.clone(), 37);
+        client.set_learners(vec![learner_addr]);
+        assert!(client.connect().await.is_ok());
+        for (key, v) in [("b", 1), ("a", 2)] {
+            let chosen = client.run_propose(key.to_string(), Some(value(v))).await;
+            assert_eq!(chosen, Ok(Some(value(v))));
+        }
+        // 再次提议沿用已被选定的值
+        let chosen = client.run_propose("a".to_string(), Some(value(3))).await;
+        assert_eq!(chosen, Ok(Some(value(2))));
+
+        // 通知是异步发送的，只订阅了 a 开头的 key
+        let learned = updates.next().await.unwrap().unwrap();
+        assert_eq!(learned.key, "a");
+        assert_eq!(learned.value, Some(value(2)));
+        client.flush_learners().await;
+        assert_eq!(learner.get("a", 0), Some(value(2)));
+        client.flush_learners().await;
+        assert_eq!(learner.get("b", 0), Some(value(1)));
+
+        // 迟到的旧值不会覆盖新值
+        let stale = Learned {
+            key: "a".to_string(),
+            round: Some(RoundNum::default()),
+            value: Some(value(0)),
+            version: 0,
+        };
+        assert!(!learner.learn(stale));
+        assert_eq!(learner.get("a", 0), Some(value(2)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_learner_catch_up() {
+        let servers: Vec<_> = (11233..11236).map(|p| format!("[::1]:{}", p)).collect();
+        let learner_addr = "[::1]:11236".to_string();
+        let triggers: Vec<_> = servers
+            .iter()
+            .map(|s| start_server(s.clone(), PaxosService::default()))
+            .collect();
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // Learner 宕机期间选定的值，推送失败
+        let mut client = Client::new(servers.clone(), 38);
+        client.set_learners(vec![learner_addr.clone()]);
+        assert!(client.connect().await.is_ok());
+        let chosen = client.run_propose("k".to_string(), Some(value(5))).await;
+        assert_eq!(chosen, Ok(Some(value(5))));
+        client.flush_learners().await;
+
+        // 只有一个 Acceptor 接受的值还没有被选定
+        let mut prop = client
+            .propose("minority".to_string(), Some(value(6)))
+            .unwrap();
+        prop.set_round(9);
+        let mut acceptor = PaxosClient::connect(format!("http://{}", servers[0]))
+            .await
+            .unwrap();
+        acceptor.accept(prop.proposer().clone()).await.unwrap();
+
+        let learner = LearnerService::default();
+        let trigger = start_learner(learner_addr, learner.clone());
+        defer! {
+            trigger.trigger();
+        }
+        assert_eq!(learner.get("k", 0), None);
+        let majority = Majority::new(3);
+        assert_eq!(
+            learner
+                .catch_up_with(&servers, &majority, None, None)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(learner.get("k", 0), Some(value(5)));
+        assert_eq!(learner.get("minority", 0), None);
+        assert_eq!(
+            learner
+                .catch_up_with(&servers, &majority, None, None)
+                .await
+                .unwrap(),
+            0
+        );
+
+        // phase 2 quorum 只需一个 Acceptor 时，该值已被选定
+        let flexible = Flexible::new(3, 3, 1).unwrap();
+        assert_eq!(
+            learner
+                .catch_up_with(&servers, &flexible, None, None)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(learner.get("minority", 0), Some(value(6)));
+        assert!(learner
+            .catch_up_with(&servers, &Majority::new(2), None, None)
+            .await
+            .is_err());
+    }
+}
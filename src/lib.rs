@@ -1,9 +1,61 @@
+mod admin;
+mod anti_entropy;
+mod auth;
+mod ballot;
+mod batch;
 mod client;
+mod config;
+mod conn;
+mod epaxos;
+mod erasure;
+mod error;
+mod fast;
+mod health;
+mod learner;
+mod membership;
+mod mencius;
+mod metrics;
 mod paxos;
+mod quorum;
 mod server;
+mod storage;
+mod stream;
+#[cfg(test)]
+mod testing;
+mod tls;
+mod trace;
+mod witness;
 
+pub use crate::admin::AdminService;
+pub use crate::anti_entropy::{anti_entropy, sync_with, PeerService};
+pub use crate::auth::{
+    admin_interceptor, bearer, bearer_interceptor, interceptor, learner_interceptor,
+    node_interceptor, AuthConfig, Principals, PROPOSER_ID_HEADER,
+};
+pub use crate::ballot::{BallotAllocator, BallotScheme};
 pub use crate::client::{Client, Propose};
+pub use crate::config::{ServerConfig, Timeouts};
+pub use crate::conn::ServerHealth;
+pub use crate::epaxos::{execution_order, ECommit, EpaxosService};
+pub use crate::error::ProposeError;
+pub use crate::fast::FastRound;
+pub use crate::health::{check_health, watch_peers, PAXOS_SERVICE_NAME};
+pub use crate::learner::{catch_up, LearnerService};
+pub use crate::mencius::Mencius;
+pub use crate::metrics::{serve_metrics, AcceptorMetrics, ProposerMetrics};
+pub use crate::paxos::admin_client::AdminClient;
+pub use crate::paxos::admin_server::AdminServer;
+pub use crate::paxos::epaxos_client::EpaxosClient;
+pub use crate::paxos::epaxos_server::EpaxosServer;
+pub use crate::paxos::learner_client::LearnerClient;
+pub use crate::paxos::learner_server::LearnerServer;
 pub use crate::paxos::paxos_client::PaxosClient;
 pub use crate::paxos::paxos_server::PaxosServer;
+pub use crate::paxos::peer_client::PeerClient;
+pub use crate::paxos::peer_server::PeerServer;
 pub use crate::paxos::*;
-pub use crate::server::PaxosService;
+pub use crate::quorum::{Flexible, Grid, Majority, QuorumSystem, Weighted, ZonePolicy, ZoneQuorum};
+pub use crate::server::{instance_key, PaxosService};
+pub use crate::storage::{FsyncMode, Journal};
+pub use crate::tls::{endpoint, TlsConfig};
+pub use crate::trace::TRACE_ID_HEADER;
@@ -0,0 +1,41 @@
+use crate::client::Propose;
+use crate::error::ProposeError;
+use crate::{Membership, Value};
+
+/// 第 e 个配置保存在 key 为 `\0membership/e` 的实例中
+///
+/// 以 `\0` 开头的 key 保留给系统实例，用户的提议不能使用，见 [`crate::Client::propose`]。
+const KEY_PREFIX: &str = "\0membership/";
+
+pub(crate) fn key(epoch: i64) -> String {
+    format!("{}{}", KEY_PREFIX, epoch)
+}
+
+/// 第 e 个配置的新成员追上旧配置中的数据后，在第 e-1 个配置中选定该实例，之后才能切换到第 e 个配置
+pub(crate) fn active_key(epoch: i64) -> String {
+    format!("{}{}/active", KEY_PREFIX, epoch)
+}
+
+/// 成员变更实例不受 epoch 限制，旧配置中的 Proposer 需要从中学到新配置
+pub(crate) fn is_membership_key(key: &str) -> bool {
+    key.starts_with(KEY_PREFIX)
+}
+
+/// 系统实例的 key，用户的提议不能使用
+pub(crate) fn is_reserved(key: &str) -> bool {
+    key.starts_with('\0')
+}
+
+/// 对一个配置实例执行一轮 Paxos，见 [`Propose::decide`]
+pub(crate) async fn decide(
+    mut prop: Propose,
+    proposal: Option<Membership>,
+) -> Result<Option<Membership>, ProposeError> {
+    if let Some(membership) = proposal {
+        prop.set_value(Value {
+            membership: Some(membership),
+            ..Default::default()
+        });
+    }
+    Ok(prop.decide().await?.and_then(|v| v.membership))
+}
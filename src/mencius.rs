@@ -0,0 +1,171 @@
+use crate::client::Client;
+use crate::error::ProposeError;
+use crate::Value;
+use tracing::debug;
+
+const KEY_PREFIX: &str = "\0mencius/";
+
+/// key 为 Mencius 日志中的 slot 时返回 slot 编号
+pub(crate) fn slot(key: &str) -> Option<i64> {
+    let (_, slot) = key.strip_prefix(KEY_PREFIX)?.rsplit_once('/')?;
+    slot.parse().ok()
+}
+
+/// Mencius 复制日志
+///
+/// slot s 属于第 s % nodes 个节点。所有者在自己的 slot 中用 round 0 直接 accept，
+/// 不需要 phase 1；空闲的节点用 no-op 放弃自己的 slot，其他节点可以用更大的 round
+/// 撤销迟迟未填的 slot。日志中第 s 个 slot 保存在 key 为 `\0mencius/{log}/{s}` 的系统实例中。
+///
+/// Acceptor 须通过 [`crate::PaxosService::set_mencius_owners`] 知道每个 slot 的所有者，
+/// 第 me 个节点的 client 的 proposer_id 须是其中的第 me 个，否则 round 0 的 accept 会被拒绝。
+#[derive(Debug)]
+pub struct Mencius {
+    client: Client,
+    log: String,
+    nodes: usize,
+    /// 本节点下一个尚未使用的 slot
+    next: i64,
+}
+
+impl Mencius {
+    /// 本节点是 nodes 个节点中的第 me 个，client 须已连接
+    pub fn new(client: Client, log: String, nodes: usize, me: usize) -> Self {
+        Mencius {
+            client,
+            log,
+            nodes,
+            next: me as i64,
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn key(&self, slot: i64) -> String {
+        format!("{}{}/{}", KEY_PREFIX, self.log, slot)
+    }
+
+    /// slot 的所有者
+    pub fn owner(&self, slot: i64) -> usize {
+        (slot as usize) % self.nodes
+    }
+
+    /// 在本节点的下一个 slot 中写入 value，返回 slot
+    pub async fn append(&mut self, value: Value) -> Result<i64, ProposeError> {
+        let slot = self.next;
+        self.next += self.nodes as i64;
+        self.fill(slot, value).await?;
+        Ok(slot)
+    }
+
+    /// 放弃本节点在 slot 之前的所有未使用的 slot，例如看到其他节点已经写到 slot 时
+    pub async fn skip_to(&mut self, slot: i64) -> Result<(), ProposeError> {
+        while self.next < slot {
+            let skipped = self.next;
+            self.next += self.nodes as i64;
+            let noop = Value {
+                noop: true,
+                ..Default::default()
+            };
+            self.fill(skipped, noop).await?;
+            debug!(slot = skipped, "slot skipped");
+        }
+        Ok(())
+    }
+
+    /// 所有者用 round 0 直接 accept
+    async fn fill(&self, slot: i64, value: Value) -> Result<(), ProposeError> {
+        let mut prop = self.client.instance_propose(self.key(slot), Some(value))?;
+        prop.set_round(0);
+        let result = prop.run_phase2().await;
+        prop.finish(result).map(|_| ())
+    }
+
+    /// 读取 slot 中被选定的值，no-op 返回 None
+    ///
+    /// slot 尚未被选定时用新的 round 撤销它并写入 no-op，所有者之后在该 slot 的 accept 会被拒绝。
+    pub async fn read(&self, slot: i64) -> Result<Option<Value>, ProposeError> {
+        let noop = Value {
+            noop: true,
+            ..Default::default()
+        };
+        let prop = self.client.instance_propose(self.key(slot), Some(noop))?;
+        let value = prop.decide().await?;
+        Ok(value.filter(|v| !v.noop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{start_server, value};
+    use crate::*;
+    use scopeguard::defer;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_mencius_log() {
+        let servers: Vec<_> = (11120..11123).map(|p| format!("[::1]:{}", p)).collect();
+        let triggers: Vec<_> = servers
+            .iter()
+            .map(|s| {
+                let mut service = PaxosService::default();
+                service.set_mencius_owners(vec![27, 28]);
+                start_server(s.clone(), service)
+            })
+            .collect();
+        defer! {
+            for t in &triggers {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let mut clients = vec![];
+        for id in 27..29 {
+            let mut client = Client::new(servers.clone(), id);
+            assert!(client.connect().await.is_ok());
+            clients.push(client);
+        }
+        let mut bob = Mencius::new(clients.pop().unwrap(), "log".to_string(), 2, 1);
+        let mut alice = Mencius::new(clients.pop().unwrap(), "log".to_string(), 2, 0);
+
+        // 各自在自己的 slot 中写入，不需要 phase 1
+        assert_eq!(alice.append(value(10)).await.unwrap(), 0);
+        assert_eq!(alice.append(value(11)).await.unwrap(), 2);
+        bob.skip_to(3).await.unwrap();
+        assert_eq!(bob.append(value(20)).await.unwrap(), 3);
+        let prepares = |m: &Mencius| {
+            m.client()
+                .metrics()
+                .latency
+                .with_label_values(&["prepare"])
+                .get_sample_count()
+        };
+        assert_eq!(prepares(&alice) + prepares(&bob), 0);
+
+        assert_eq!(alice.read(0).await.unwrap(), Some(value(10)));
+        assert_eq!(alice.read(1).await.unwrap(), None);
+        assert_eq!(alice.read(3).await.unwrap(), Some(value(20)));
+
+        // 撤销 bob 尚未使用的 slot 5 后，bob 不能再写入该 slot
+        assert_eq!(alice.read(5).await.unwrap(), None);
+        assert!(bob.append(value(21)).await.is_err());
+
+        // 不是所有者的节点不能跳过 phase 1
+        let mut client = Client::new(servers.clone(), 27);
+        assert!(client.connect().await.is_ok());
+        let mut mallory = Mencius::new(client, "other".to_string(), 2, 1);
+        assert!(mallory.append(value(30)).await.is_err());
+        let other = Mencius::new(bob.client, "other".to_string(), 2, 1);
+        assert_eq!(other.read(1).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_slot() {
+        assert_eq!(slot("\0mencius/a/b/12"), Some(12));
+        assert_eq!(slot("a/12"), None);
+        assert_eq!(slot("\0mencius/a/x"), None);
+    }
+}
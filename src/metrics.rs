@@ -0,0 +1,202 @@
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Response, StatusCode};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// Acceptor 端的指标
+#[derive(Debug, Clone)]
+pub struct AcceptorMetrics {
+    registry: Registry,
+    /// 收到的请求数，按 method 区分
+    pub requests: IntCounterVec,
+    /// Prepare 的处理结果，result 为 granted 或 rejected
+    pub promises: IntCounterVec,
+    /// Accept 的处理结果，result 为 accepted 或 rejected
+    pub accepts: IntCounterVec,
+    /// 请求处理耗时，按 method 区分
+    pub latency: HistogramVec,
+}
+
+/// Proposer 端的指标
+#[derive(Debug, Clone)]
+pub struct ProposerMetrics {
+    registry: Registry,
+    /// 提议结果，result 为 chosen 或 failed
+    pub proposals: IntCounterVec,
+    /// 因被抢占而重新发起的提议次数
+    pub retries: IntCounter,
+    /// 未能获得多数派应答的次数
+    pub quorum_failures: IntCounter,
+    /// 发往 Acceptor 的 RPC 耗时，按 method 区分
+    pub latency: HistogramVec,
+}
+
+impl Default for AcceptorMetrics {
+    fn default() -> Self {
+        let registry = Registry::new_custom(Some("rpaxos_acceptor".to_string()), None).unwrap();
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Prepare/Accept requests received"),
+            &["method"],
+        )
+        .unwrap();
+        let promises = IntCounterVec::new(
+            Opts::new("promises_total", "Prepare requests by result"),
+            &["result"],
+        )
+        .unwrap();
+        let accepts = IntCounterVec::new(
+            Opts::new("accepts_total", "Accept requests by result"),
+            &["result"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("request_duration_seconds", "Request handling latency"),
+            &["method"],
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(promises.clone())).unwrap();
+        registry.register(Box::new(accepts.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        AcceptorMetrics {
+            registry,
+            requests,
+            promises,
+            accepts,
+            latency,
+        }
+    }
+}
+
+impl Default for ProposerMetrics {
+    fn default() -> Self {
+        let registry = Registry::new_custom(Some("rpaxos_proposer".to_string()), None).unwrap();
+        let proposals = IntCounterVec::new(
+            Opts::new("proposals_total", "Proposals by result"),
+            &["result"],
+        )
+        .unwrap();
+        let retries =
+            IntCounter::new("retries_total", "Proposals retried after preemption").unwrap();
+        let quorum_failures =
+            IntCounter::new("quorum_failures_total", "Phases without a quorum").unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("rpc_duration_seconds", "Acceptor RPC latency"),
+            &["method"],
+        )
+        .unwrap();
+        registry.register(Box::new(proposals.clone())).unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry
+            .register(Box::new(quorum_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        ProposerMetrics {
+            registry,
+            proposals,
+            retries,
+            quorum_failures,
+            latency,
+        }
+    }
+}
+
+impl AcceptorMetrics {
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// 以 Prometheus 文本格式输出
+    pub fn render(&self) -> String {
+        render(&self.registry)
+    }
+
+    pub(crate) fn observe(&self, method: &str, start: Instant) {
+        self.requests.with_label_values(&[method]).inc();
+        self.latency
+            .with_label_values(&[method])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+impl ProposerMetrics {
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// 以 Prometheus 文本格式输出
+    pub fn render(&self) -> String {
+        render(&self.registry)
+    }
+
+    pub(crate) fn observe(&self, method: &str, start: Instant) {
+        self.latency
+            .with_label_values(&[method])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+fn render(registry: &Registry) -> String {
+    let mut buf = vec![];
+    let encoder = TextEncoder::new();
+    encoder.encode(&registry.gather(), &mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+/// 在 addr 上提供 `GET /metrics`
+pub async fn serve_metrics(addr: SocketAddr, registry: Registry) -> Result<()> {
+    let make_svc = make_service_fn(move |_| {
+        let registry = registry.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let registry = registry.clone();
+                async move {
+                    let resp = if req.method() == Method::GET && req.uri().path() == "/metrics" {
+                        Response::builder()
+                            .header(
+                                hyper::header::CONTENT_TYPE,
+                                TextEncoder::new().format_type(),
+                            )
+                            .body(Body::from(render(&registry)))
+                    } else {
+                        Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::empty())
+                    };
+                    Ok::<_, Infallible>(resp.unwrap())
+                }
+            }))
+        }
+    });
+    hyper::Server::try_bind(&addr)?.serve(make_svc).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = AcceptorMetrics::default();
+        metrics.promises.with_label_values(&["granted"]).inc();
+        metrics.observe("prepare", Instant::now());
+        let text = metrics.render();
+        assert!(text.contains("rpaxos_acceptor_promises_total{result=\"granted\"} 1"));
+        assert!(text.contains("rpaxos_acceptor_requests_total{method=\"prepare\"} 1"));
+        assert!(
+            text.contains("rpaxos_acceptor_request_duration_seconds_count{method=\"prepare\"} 1")
+        );
+
+        let metrics = ProposerMetrics::default();
+        metrics.quorum_failures.inc();
+        assert!(metrics
+            .render()
+            .contains("rpaxos_proposer_quorum_failures_total 1"));
+    }
+}
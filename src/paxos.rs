@@ -1,20 +1,64 @@
-/// 每一轮的编号，全局唯一
+/// 每一轮的编号，全局唯一，按 (number, proposer_id) 比较大小
 /// number: 本地单调递增计数器
 /// proposer_id: 全局唯一 ID
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Eq, Hash, PartialOrd, Ord, Clone, PartialEq, ::prost::Message)]
 pub struct RoundNum {
     #[prost(int64, tag = "1")]
     pub number: i64,
     #[prost(int64, tag = "2")]
     pub proposer_id: i64,
 }
-/// 保存的值，此处为整型
+/// 保存的值，此处为整型；成员变更实例中为新的配置
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Value {
     #[prost(int64, tag = "1")]
     pub value: i64,
+    #[prost(message, optional, tag = "2")]
+    pub membership: ::core::option::Option<Membership>,
+    /// Mencius 中 slot 的所有者放弃该 slot
+    #[prost(bool, tag = "3")]
+    pub noop: bool,
+    /// 见证者只保存值的摘要，非 0 时其他字段为空
+    #[prost(uint64, tag = "4")]
+    pub digest: u64,
+    /// 任意二进制内容，如较大的值
+    #[prost(bytes = "vec", tag = "5")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// RS-Paxos 中 Acceptor 只保存值的一个分片，此时其他字段为空
+    #[prost(message, optional, tag = "6")]
+    pub shard: ::core::option::Option<Shard>,
+}
+/// 纠删码分片：值编码后切成 data_shards 个数据分片和 total_shards - data_shards 个校验分片，
+/// 任意 data_shards 个分片可以恢复值
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Shard {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub data_shards: u32,
+    #[prost(uint32, tag = "3")]
+    pub total_shards: u32,
+    /// 编码前的长度，用于去掉补齐的字节
+    #[prost(uint64, tag = "4")]
+    pub length: u64,
+    /// 完整值的摘要，用于找出同一个值的分片
+    #[prost(uint64, tag = "5")]
+    pub digest: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
 }
-/// 一个 Paxos 实例，对应一次完整的投票
+/// 集群配置：epoch 从 0 开始，第 e 个配置由第 e-1 个配置中的 Acceptor 选定
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Membership {
+    #[prost(int64, tag = "1")]
+    pub epoch: i64,
+    #[prost(string, repeated, tag = "2")]
+    pub servers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// 与 servers 一一对应，每个节点所在的 zone；为空时不区分 zone
+    #[prost(string, repeated, tag = "3")]
+    pub zones: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 一个 Paxos 实例，对应一次完整的投票；同一 key 的不同 version 是互不相关的实例
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PaxosInstanceId {
     #[prost(string, tag = "1")]
@@ -39,6 +83,284 @@ pub struct Proposer {
     pub round: ::core::option::Option<RoundNum>,
     #[prost(message, optional, tag = "3")]
     pub value: ::core::option::Option<Value>,
+    /// Proposer 所在配置的 epoch，小于 Acceptor 已知的 epoch 时请求被拒绝
+    #[prost(int64, tag = "4")]
+    pub epoch: i64,
+}
+/// 持久化到日志中的一条记录：key 对应的 Acceptor 状态，acceptor 为空表示已删除；
+/// key 是由 key 和 version 组成的存储 key，见 instance_key。
+/// epaxos 非空时记录的是一个 EPaxos 实例，其他字段为空
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Instance {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub acceptor: ::core::option::Option<Acceptor>,
+    #[prost(message, optional, tag = "3")]
+    pub epaxos: ::core::option::Option<EInstance>,
+}
+/// 多个 Paxos 实例的请求合并为一次 RPC，应答与请求一一对应
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ProposerBatch {
+    #[prost(message, repeated, tag = "1")]
+    pub proposers: ::prost::alloc::vec::Vec<Proposer>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AcceptorBatch {
+    #[prost(message, repeated, tag = "1")]
+    pub acceptors: ::prost::alloc::vec::Vec<Acceptor>,
+}
+/// 双向流上的一条请求，tag 由 Proposer 分配，用于匹配应答
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamRequest {
+    #[prost(uint64, tag = "1")]
+    pub tag: u64,
+    #[prost(string, tag = "4")]
+    pub trace_id: ::prost::alloc::string::String,
+    #[prost(oneof = "stream_request::Op", tags = "2, 3")]
+    pub op: ::core::option::Option<stream_request::Op>,
+}
+/// Nested message and enum types in `StreamRequest`.
+pub mod stream_request {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Op {
+        #[prost(message, tag = "2")]
+        Prepare(super::Proposer),
+        #[prost(message, tag = "3")]
+        Accept(super::Proposer),
+    }
+}
+/// error 非空时表示该请求失败，流本身不受影响
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamReply {
+    #[prost(uint64, tag = "1")]
+    pub tag: u64,
+    #[prost(message, optional, tag = "2")]
+    pub acceptor: ::core::option::Option<Acceptor>,
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
+}
+/// 成员变更时通知 Acceptor 新的 epoch，之后拒绝更早配置中的请求；
+/// Acceptor 须已接受该 epoch 的配置实例
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FenceRequest {
+    #[prost(int64, tag = "1")]
+    pub epoch: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FenceReply {
+    #[prost(int64, tag = "1")]
+    pub epoch: i64,
+}
+/// EPaxos 实例：第 replica 个 Proposer 发起的第 slot 个命令
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EInstanceId {
+    #[prost(int64, tag = "1")]
+    pub replica: i64,
+    #[prost(int64, tag = "2")]
+    pub slot: i64,
+}
+/// 命令的执行顺序属性：seq 越小越先执行，deps 中的实例须先执行
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EAttributes {
+    #[prost(int64, tag = "1")]
+    pub seq: i64,
+    #[prost(message, repeated, tag = "2")]
+    pub deps: ::prost::alloc::vec::Vec<EInstanceId>,
+}
+/// 写入 key 的命令，同一 key 上的命令相互冲突
+/// ballot: 发起者使用 0，恢复实例时使用更大的 ballot；保存在 Acceptor 上时为记录属性时的 ballot
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ECommand {
+    #[prost(message, optional, tag = "1")]
+    pub id: ::core::option::Option<EInstanceId>,
+    #[prost(string, tag = "2")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub value: ::core::option::Option<Value>,
+    #[prost(message, optional, tag = "4")]
+    pub attributes: ::core::option::Option<EAttributes>,
+    #[prost(message, optional, tag = "5")]
+    pub ballot: ::core::option::Option<RoundNum>,
+}
+/// EPaxos 实例在 Acceptor 上的状态；command 为空表示只承诺过 ballot，还没有见过命令
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EInstance {
+    #[prost(message, optional, tag = "1")]
+    pub id: ::core::option::Option<EInstanceId>,
+    #[prost(message, optional, tag = "2")]
+    pub command: ::core::option::Option<ECommand>,
+    #[prost(enumeration = "EStatus", tag = "3")]
+    pub status: i32,
+    #[prost(message, optional, tag = "4")]
+    pub promised: ::core::option::Option<RoundNum>,
+}
+/// attributes 为 Acceptor 合并本地冲突之后的属性；
+/// ok 为 false 时请求的 ballot 小于 Acceptor 承诺过的 promised，请求被拒绝
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EReply {
+    #[prost(message, optional, tag = "1")]
+    pub attributes: ::core::option::Option<EAttributes>,
+    #[prost(bool, tag = "2")]
+    pub ok: bool,
+    #[prost(message, optional, tag = "3")]
+    pub promised: ::core::option::Option<RoundNum>,
+}
+/// 恢复发起者失联的实例：以更大的 ballot 取得各 Acceptor 上实例的状态
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EPrepareRequest {
+    #[prost(message, optional, tag = "1")]
+    pub id: ::core::option::Option<EInstanceId>,
+    #[prost(message, optional, tag = "2")]
+    pub ballot: ::core::option::Option<RoundNum>,
+}
+/// instance 为 Acceptor 上实例的状态，其中 command 为空表示还没有见过该命令
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EPrepareReply {
+    #[prost(bool, tag = "1")]
+    pub ok: bool,
+    #[prost(message, optional, tag = "2")]
+    pub promised: ::core::option::Option<RoundNum>,
+    #[prost(message, optional, tag = "3")]
+    pub instance: ::core::option::Option<EInstance>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ECommittedRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ECommittedReply {
+    #[prost(message, repeated, tag = "1")]
+    pub commands: ::prost::alloc::vec::Vec<ECommand>,
+}
+/// 被选定的值，由 Proposer 发给 Learner；同一 key 上 round 更大的值覆盖之前的值
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Learned {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub round: ::core::option::Option<RoundNum>,
+    #[prost(message, optional, tag = "3")]
+    pub value: ::core::option::Option<Value>,
+    #[prost(int64, tag = "4")]
+    pub version: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LearnReply {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LearnedRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(int64, tag = "2")]
+    pub version: i64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListKeysRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListKeysReply {
+    #[prost(string, repeated, tag = "1")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetInstanceRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StorageSizeRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StorageSizeReply {
+    #[prost(uint64, tag = "1")]
+    pub instances: u64,
+    /// 日志文件大小，纯内存存储时为 0
+    #[prost(uint64, tag = "2")]
+    pub journal_bytes: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ForgetRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ForgetReply {
+    #[prost(bool, tag = "1")]
+    pub existed: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportReply {
+    #[prost(message, repeated, tag = "1")]
+    pub instances: ::prost::alloc::vec::Vec<Instance>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DigestsRequest {
+    #[prost(string, tag = "1")]
+    pub prefix: ::prost::alloc::string::String,
+    /// ranges 大于 0 时只返回落在 indexes 这些区间中的实例，区间的划分与 Ranges 相同
+    #[prost(uint32, tag = "2")]
+    pub ranges: u32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub indexes: ::prost::alloc::vec::Vec<u32>,
+}
+/// 实例状态的摘要，节点之间据此找出需要修复的实例；没有值时 digest 为 0
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InstanceDigest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub round: ::core::option::Option<RoundNum>,
+    #[prost(message, optional, tag = "3")]
+    pub last_round: ::core::option::Option<RoundNum>,
+    #[prost(uint64, tag = "4")]
+    pub digest: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DigestsReply {
+    #[prost(message, repeated, tag = "1")]
+    pub instances: ::prost::alloc::vec::Vec<InstanceDigest>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RangesRequest {
+    #[prost(uint32, tag = "1")]
+    pub ranges: u32,
+}
+/// 按 key 把实例分成若干区间，每个区间已接受的值汇总成一个 hash
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RangesReply {
+    #[prost(uint64, repeated, tag = "1")]
+    pub hashes: ::prost::alloc::vec::Vec<u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FetchRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 只包含已有的实例
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FetchReply {
+    #[prost(message, repeated, tag = "1")]
+    pub instances: ::prost::alloc::vec::Vec<Instance>,
+}
+/// 实例在 Acceptor 上的进度，只会向后推进
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum EStatus {
+    PreAccepted = 0,
+    Accepted = 1,
+    Committed = 2,
 }
 #[doc = r" Generated client implementations."]
 pub mod paxos_client {
@@ -101,6 +423,51 @@ pub mod paxos_client {
             let path = http::uri::PathAndQuery::from_static("/paxos.Paxos/Accept");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn prepare_batch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ProposerBatch>,
+        ) -> Result<tonic::Response<super::AcceptorBatch>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Paxos/PrepareBatch");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn accept_batch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ProposerBatch>,
+        ) -> Result<tonic::Response<super::AcceptorBatch>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Paxos/AcceptBatch");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::StreamRequest>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::StreamReply>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Paxos/Stream");
+            self.inner
+                .streaming(request.into_streaming_request(), path, codec)
+                .await
+        }
     }
     impl<T: Clone> Clone for PaxosClient<T> {
         fn clone(&self) -> Self {
@@ -115,97 +482,1330 @@ pub mod paxos_client {
         }
     }
 }
-#[doc = r" Generated server implementations."]
-pub mod paxos_server {
+#[doc = r" Generated client implementations."]
+pub mod epaxos_client {
     #![allow(unused_variables, dead_code, missing_docs)]
     use tonic::codegen::*;
-    #[doc = "Generated trait containing gRPC methods that should be implemented for use with PaxosServer."]
-    #[async_trait]
-    pub trait Paxos: Send + Sync + 'static {
-        async fn prepare(
-            &self,
-            request: tonic::Request<super::Proposer>,
-        ) -> Result<tonic::Response<super::Acceptor>, tonic::Status>;
-        async fn accept(
-            &self,
-            request: tonic::Request<super::Proposer>,
-        ) -> Result<tonic::Response<super::Acceptor>, tonic::Status>;
+    #[doc = " 无 leader 的 EPaxos，任一 Proposer 都可以发起命令"]
+    pub struct EpaxosClient<T> {
+        inner: tonic::client::Grpc<T>,
     }
-    #[derive(Debug)]
-    pub struct PaxosServer<T: Paxos> {
-        inner: _Inner<T>,
+    impl EpaxosClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
     }
-    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
-    impl<T: Paxos> PaxosServer<T> {
+    impl<T> EpaxosClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
         pub fn new(inner: T) -> Self {
-            let inner = Arc::new(inner);
-            let inner = _Inner(inner, None);
+            let inner = tonic::client::Grpc::new(inner);
             Self { inner }
         }
         pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
-            let inner = Arc::new(inner);
-            let inner = _Inner(inner, Some(interceptor.into()));
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
             Self { inner }
         }
-    }
-    impl<T, B> Service<http::Request<B>> for PaxosServer<T>
-    where
-        T: Paxos,
-        B: HttpBody + Send + Sync + 'static,
-        B::Error: Into<StdError> + Send + 'static,
-    {
-        type Response = http::Response<tonic::body::BoxBody>;
-        type Error = Never;
-        type Future = BoxFuture<Self::Response, Self::Error>;
-        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-            Poll::Ready(Ok(()))
+        pub async fn pre_accept(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Epaxos/PreAccept");
+            self.inner.unary(request.into_request(), path, codec).await
         }
-        fn call(&mut self, req: http::Request<B>) -> Self::Future {
-            let inner = self.inner.clone();
-            match req.uri().path() {
-                "/paxos.Paxos/Prepare" => {
-                    #[allow(non_camel_case_types)]
-                    struct PrepareSvc<T: Paxos>(pub Arc<T>);
-                    impl<T: Paxos> tonic::server::UnaryService<super::Proposer> for PrepareSvc<T> {
-                        type Response = super::Acceptor;
-                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
-                        fn call(
-                            &mut self,
-                            request: tonic::Request<super::Proposer>,
-                        ) -> Self::Future {
-                            let inner = self.0.clone();
-                            let fut = async move { (*inner).prepare(request).await };
-                            Box::pin(fut)
-                        }
-                    }
-                    let inner = self.inner.clone();
-                    let fut = async move {
-                        let interceptor = inner.1.clone();
-                        let inner = inner.0;
-                        let method = PrepareSvc(inner);
-                        let codec = tonic::codec::ProstCodec::default();
-                        let mut grpc = if let Some(interceptor) = interceptor {
-                            tonic::server::Grpc::with_interceptor(codec, interceptor)
-                        } else {
-                            tonic::server::Grpc::new(codec)
-                        };
-                        let res = grpc.unary(method, req).await;
-                        Ok(res)
-                    };
-                    Box::pin(fut)
-                }
-                "/paxos.Paxos/Accept" => {
+        pub async fn accept(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Epaxos/Accept");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn commit(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Epaxos/Commit");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn prepare(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EPrepareRequest>,
+        ) -> Result<tonic::Response<super::EPrepareReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Epaxos/Prepare");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " key 上已提交的命令，未按执行顺序排列"]
+        pub async fn committed(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ECommittedRequest>,
+        ) -> Result<tonic::Response<super::ECommittedReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Epaxos/Committed");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+    impl<T: Clone> Clone for EpaxosClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for EpaxosClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "EpaxosClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated client implementations."]
+pub mod learner_client {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = " 不参与投票的 Learner：接收被选定的值，提供可以容忍过期的读取"]
+    pub struct LearnerClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl LearnerClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> LearnerClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+            Self { inner }
+        }
+        pub async fn learn(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Learned>,
+        ) -> Result<tonic::Response<super::LearnReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Learner/Learn");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 还没有学到该 key 时返回 NOT_FOUND"]
+        pub async fn get(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LearnedRequest>,
+        ) -> Result<tonic::Response<super::Learned>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Learner/Get");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 之后学到的、key 以 prefix 开头的值"]
+        pub async fn watch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WatchRequest>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::Learned>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Learner/Watch");
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+    }
+    impl<T: Clone> Clone for LearnerClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for LearnerClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "LearnerClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated client implementations."]
+pub mod admin_client {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = " 查看和维护 Acceptor 状态，用于排查问题；默认不开启，开启认证时只允许管理员调用"]
+    pub struct AdminClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl AdminClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> AdminClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+            Self { inner }
+        }
+        pub async fn list_keys(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListKeysRequest>,
+        ) -> Result<tonic::Response<super::ListKeysReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Admin/ListKeys");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_instance(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetInstanceRequest>,
+        ) -> Result<tonic::Response<super::Instance>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Admin/GetInstance");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn storage_size(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StorageSizeRequest>,
+        ) -> Result<tonic::Response<super::StorageSizeReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Admin/StorageSize");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 强制删除一个实例的状态，会破坏 Paxos 的安全性，只在修复故障时使用"]
+        pub async fn forget(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ForgetRequest>,
+        ) -> Result<tonic::Response<super::ForgetReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Admin/Forget");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn export(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRequest>,
+        ) -> Result<tonic::Response<super::ExportReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Admin/Export");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+    impl<T: Clone> Clone for AdminClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for AdminClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "AdminClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated client implementations."]
+pub mod peer_client {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = " 节点之间的 anti-entropy 和成员变更，只允许节点的身份调用"]
+    pub struct PeerClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl PeerClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> PeerClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+            Self { inner }
+        }
+        #[doc = " 各区间的 hash，先据此找出不一致的区间"]
+        pub async fn ranges(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RangesRequest>,
+        ) -> Result<tonic::Response<super::RangesReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/Ranges");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 以 prefix 开头的实例的摘要"]
+        pub async fn digests(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DigestsRequest>,
+        ) -> Result<tonic::Response<super::DigestsReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/Digests");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 还没有该实例时返回 NOT_FOUND"]
+        pub async fn fetch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetInstanceRequest>,
+        ) -> Result<tonic::Response<super::Instance>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/Fetch");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 一次拉取多个实例，其中的值只是已接受，不一定已被选定"]
+        pub async fn fetch_many(
+            &mut self,
+            request: impl tonic::IntoRequest<super::FetchRequest>,
+        ) -> Result<tonic::Response<super::FetchReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/FetchMany");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn fence(
+            &mut self,
+            request: impl tonic::IntoRequest<super::FenceRequest>,
+        ) -> Result<tonic::Response<super::FenceReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/Fence");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " 成员变更时列出需要复制到新配置的实例"]
+        pub async fn list_keys(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListKeysRequest>,
+        ) -> Result<tonic::Response<super::ListKeysReply>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/paxos.Peer/ListKeys");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+    impl<T: Clone> Clone for PeerClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for PeerClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "PeerClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod paxos_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with PaxosServer."]
+    #[async_trait]
+    pub trait Paxos: Send + Sync + 'static {
+        async fn prepare(
+            &self,
+            request: tonic::Request<super::Proposer>,
+        ) -> Result<tonic::Response<super::Acceptor>, tonic::Status>;
+        async fn accept(
+            &self,
+            request: tonic::Request<super::Proposer>,
+        ) -> Result<tonic::Response<super::Acceptor>, tonic::Status>;
+        async fn prepare_batch(
+            &self,
+            request: tonic::Request<super::ProposerBatch>,
+        ) -> Result<tonic::Response<super::AcceptorBatch>, tonic::Status>;
+        async fn accept_batch(
+            &self,
+            request: tonic::Request<super::ProposerBatch>,
+        ) -> Result<tonic::Response<super::AcceptorBatch>, tonic::Status>;
+        #[doc = "Server streaming response type for the Stream method."]
+        type StreamStream: Stream<Item = Result<super::StreamReply, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        async fn stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::StreamRequest>>,
+        ) -> Result<tonic::Response<Self::StreamStream>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct PaxosServer<T: Paxos> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Paxos> PaxosServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for PaxosServer<T>
+    where
+        T: Paxos,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/paxos.Paxos/Prepare" => {
+                    #[allow(non_camel_case_types)]
+                    struct PrepareSvc<T: Paxos>(pub Arc<T>);
+                    impl<T: Paxos> tonic::server::UnaryService<super::Proposer> for PrepareSvc<T> {
+                        type Response = super::Acceptor;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Proposer>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).prepare(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = PrepareSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Paxos/Accept" => {
+                    #[allow(non_camel_case_types)]
+                    struct AcceptSvc<T: Paxos>(pub Arc<T>);
+                    impl<T: Paxos> tonic::server::UnaryService<super::Proposer> for AcceptSvc<T> {
+                        type Response = super::Acceptor;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Proposer>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).accept(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = AcceptSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Paxos/PrepareBatch" => {
+                    #[allow(non_camel_case_types)]
+                    struct PrepareBatchSvc<T: Paxos>(pub Arc<T>);
+                    impl<T: Paxos> tonic::server::UnaryService<super::ProposerBatch> for PrepareBatchSvc<T> {
+                        type Response = super::AcceptorBatch;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ProposerBatch>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).prepare_batch(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = PrepareBatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Paxos/AcceptBatch" => {
+                    #[allow(non_camel_case_types)]
+                    struct AcceptBatchSvc<T: Paxos>(pub Arc<T>);
+                    impl<T: Paxos> tonic::server::UnaryService<super::ProposerBatch> for AcceptBatchSvc<T> {
+                        type Response = super::AcceptorBatch;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ProposerBatch>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).accept_batch(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = AcceptBatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Paxos/Stream" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamSvc<T: Paxos>(pub Arc<T>);
+                    impl<T: Paxos> tonic::server::StreamingService<super::StreamRequest> for StreamSvc<T> {
+                        type Response = super::StreamReply;
+                        type ResponseStream = T::StreamStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::StreamRequest>>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).stream(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = StreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Paxos> Clone for PaxosServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Paxos> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Paxos> tonic::transport::NamedService for PaxosServer<T> {
+        const NAME: &'static str = "paxos.Paxos";
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod epaxos_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with EpaxosServer."]
+    #[async_trait]
+    pub trait Epaxos: Send + Sync + 'static {
+        async fn pre_accept(
+            &self,
+            request: tonic::Request<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status>;
+        async fn accept(
+            &self,
+            request: tonic::Request<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status>;
+        async fn commit(
+            &self,
+            request: tonic::Request<super::ECommand>,
+        ) -> Result<tonic::Response<super::EReply>, tonic::Status>;
+        async fn prepare(
+            &self,
+            request: tonic::Request<super::EPrepareRequest>,
+        ) -> Result<tonic::Response<super::EPrepareReply>, tonic::Status>;
+        #[doc = " key 上已提交的命令，未按执行顺序排列"]
+        async fn committed(
+            &self,
+            request: tonic::Request<super::ECommittedRequest>,
+        ) -> Result<tonic::Response<super::ECommittedReply>, tonic::Status>;
+    }
+    #[doc = " 无 leader 的 EPaxos，任一 Proposer 都可以发起命令"]
+    #[derive(Debug)]
+    pub struct EpaxosServer<T: Epaxos> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Epaxos> EpaxosServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for EpaxosServer<T>
+    where
+        T: Epaxos,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/paxos.Epaxos/PreAccept" => {
+                    #[allow(non_camel_case_types)]
+                    struct PreAcceptSvc<T: Epaxos>(pub Arc<T>);
+                    impl<T: Epaxos> tonic::server::UnaryService<super::ECommand> for PreAcceptSvc<T> {
+                        type Response = super::EReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ECommand>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).pre_accept(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = PreAcceptSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Epaxos/Accept" => {
+                    #[allow(non_camel_case_types)]
+                    struct AcceptSvc<T: Epaxos>(pub Arc<T>);
+                    impl<T: Epaxos> tonic::server::UnaryService<super::ECommand> for AcceptSvc<T> {
+                        type Response = super::EReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ECommand>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).accept(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = AcceptSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Epaxos/Commit" => {
+                    #[allow(non_camel_case_types)]
+                    struct CommitSvc<T: Epaxos>(pub Arc<T>);
+                    impl<T: Epaxos> tonic::server::UnaryService<super::ECommand> for CommitSvc<T> {
+                        type Response = super::EReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ECommand>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).commit(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CommitSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Epaxos/Prepare" => {
+                    #[allow(non_camel_case_types)]
+                    struct PrepareSvc<T: Epaxos>(pub Arc<T>);
+                    impl<T: Epaxos> tonic::server::UnaryService<super::EPrepareRequest> for PrepareSvc<T> {
+                        type Response = super::EPrepareReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EPrepareRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).prepare(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = PrepareSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Epaxos/Committed" => {
+                    #[allow(non_camel_case_types)]
+                    struct CommittedSvc<T: Epaxos>(pub Arc<T>);
+                    impl<T: Epaxos> tonic::server::UnaryService<super::ECommittedRequest> for CommittedSvc<T> {
+                        type Response = super::ECommittedReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ECommittedRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).committed(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CommittedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Epaxos> Clone for EpaxosServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Epaxos> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Epaxos> tonic::transport::NamedService for EpaxosServer<T> {
+        const NAME: &'static str = "paxos.Epaxos";
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod learner_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with LearnerServer."]
+    #[async_trait]
+    pub trait Learner: Send + Sync + 'static {
+        async fn learn(
+            &self,
+            request: tonic::Request<super::Learned>,
+        ) -> Result<tonic::Response<super::LearnReply>, tonic::Status>;
+        #[doc = " 还没有学到该 key 时返回 NOT_FOUND"]
+        async fn get(
+            &self,
+            request: tonic::Request<super::LearnedRequest>,
+        ) -> Result<tonic::Response<super::Learned>, tonic::Status>;
+        #[doc = "Server streaming response type for the Watch method."]
+        type WatchStream: Stream<Item = Result<super::Learned, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        #[doc = " 之后学到的、key 以 prefix 开头的值"]
+        async fn watch(
+            &self,
+            request: tonic::Request<super::WatchRequest>,
+        ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status>;
+    }
+    #[doc = " 不参与投票的 Learner：接收被选定的值，提供可以容忍过期的读取"]
+    #[derive(Debug)]
+    pub struct LearnerServer<T: Learner> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Learner> LearnerServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for LearnerServer<T>
+    where
+        T: Learner,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/paxos.Learner/Learn" => {
+                    #[allow(non_camel_case_types)]
+                    struct LearnSvc<T: Learner>(pub Arc<T>);
+                    impl<T: Learner> tonic::server::UnaryService<super::Learned> for LearnSvc<T> {
+                        type Response = super::LearnReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Learned>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).learn(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = LearnSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Learner/Get" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetSvc<T: Learner>(pub Arc<T>);
+                    impl<T: Learner> tonic::server::UnaryService<super::LearnedRequest> for GetSvc<T> {
+                        type Response = super::Learned;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::LearnedRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Learner/Watch" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchSvc<T: Learner>(pub Arc<T>);
+                    impl<T: Learner> tonic::server::ServerStreamingService<super::WatchRequest> for WatchSvc<T> {
+                        type Response = super::Learned;
+                        type ResponseStream = T::WatchStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).watch(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = WatchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Learner> Clone for LearnerServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Learner> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Learner> tonic::transport::NamedService for LearnerServer<T> {
+        const NAME: &'static str = "paxos.Learner";
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod admin_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with AdminServer."]
+    #[async_trait]
+    pub trait Admin: Send + Sync + 'static {
+        async fn list_keys(
+            &self,
+            request: tonic::Request<super::ListKeysRequest>,
+        ) -> Result<tonic::Response<super::ListKeysReply>, tonic::Status>;
+        async fn get_instance(
+            &self,
+            request: tonic::Request<super::GetInstanceRequest>,
+        ) -> Result<tonic::Response<super::Instance>, tonic::Status>;
+        async fn storage_size(
+            &self,
+            request: tonic::Request<super::StorageSizeRequest>,
+        ) -> Result<tonic::Response<super::StorageSizeReply>, tonic::Status>;
+        #[doc = " 强制删除一个实例的状态，会破坏 Paxos 的安全性，只在修复故障时使用"]
+        async fn forget(
+            &self,
+            request: tonic::Request<super::ForgetRequest>,
+        ) -> Result<tonic::Response<super::ForgetReply>, tonic::Status>;
+        async fn export(
+            &self,
+            request: tonic::Request<super::ExportRequest>,
+        ) -> Result<tonic::Response<super::ExportReply>, tonic::Status>;
+    }
+    #[doc = " 查看和维护 Acceptor 状态，用于排查问题；默认不开启，开启认证时只允许管理员调用"]
+    #[derive(Debug)]
+    pub struct AdminServer<T: Admin> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Admin> AdminServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for AdminServer<T>
+    where
+        T: Admin,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/paxos.Admin/ListKeys" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListKeysSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ListKeysRequest> for ListKeysSvc<T> {
+                        type Response = super::ListKeysReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListKeysRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list_keys(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ListKeysSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Admin/GetInstance" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetInstanceSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::GetInstanceRequest> for GetInstanceSvc<T> {
+                        type Response = super::Instance;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetInstanceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_instance(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetInstanceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Admin/StorageSize" => {
+                    #[allow(non_camel_case_types)]
+                    struct StorageSizeSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::StorageSizeRequest> for StorageSizeSvc<T> {
+                        type Response = super::StorageSizeReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StorageSizeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).storage_size(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = StorageSizeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Admin/Forget" => {
                     #[allow(non_camel_case_types)]
-                    struct AcceptSvc<T: Paxos>(pub Arc<T>);
-                    impl<T: Paxos> tonic::server::UnaryService<super::Proposer> for AcceptSvc<T> {
-                        type Response = super::Acceptor;
+                    struct ForgetSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ForgetRequest> for ForgetSvc<T> {
+                        type Response = super::ForgetReply;
                         type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::Proposer>,
+                            request: tonic::Request<super::ForgetRequest>,
                         ) -> Self::Future {
                             let inner = self.0.clone();
-                            let fut = async move { (*inner).accept(request).await };
+                            let fut = async move { (*inner).forget(request).await };
                             Box::pin(fut)
                         }
                     }
@@ -213,7 +1813,38 @@ pub mod paxos_server {
                     let fut = async move {
                         let interceptor = inner.1.clone();
                         let inner = inner.0;
-                        let method = AcceptSvc(inner);
+                        let method = ForgetSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Admin/Export" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportSvc<T: Admin>(pub Arc<T>);
+                    impl<T: Admin> tonic::server::UnaryService<super::ExportRequest> for ExportSvc<T> {
+                        type Response = super::ExportReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).export(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ExportSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = if let Some(interceptor) = interceptor {
                             tonic::server::Grpc::with_interceptor(codec, interceptor)
@@ -236,13 +1867,13 @@ pub mod paxos_server {
             }
         }
     }
-    impl<T: Paxos> Clone for PaxosServer<T> {
+    impl<T: Admin> Clone for AdminServer<T> {
         fn clone(&self) -> Self {
             let inner = self.inner.clone();
             Self { inner }
         }
     }
-    impl<T: Paxos> Clone for _Inner<T> {
+    impl<T: Admin> Clone for _Inner<T> {
         fn clone(&self) -> Self {
             Self(self.0.clone(), self.1.clone())
         }
@@ -252,7 +1883,294 @@ pub mod paxos_server {
             write!(f, "{:?}", self.0)
         }
     }
-    impl<T: Paxos> tonic::transport::NamedService for PaxosServer<T> {
-        const NAME: &'static str = "paxos.Paxos";
+    impl<T: Admin> tonic::transport::NamedService for AdminServer<T> {
+        const NAME: &'static str = "paxos.Admin";
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod peer_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with PeerServer."]
+    #[async_trait]
+    pub trait Peer: Send + Sync + 'static {
+        #[doc = " 各区间的 hash，先据此找出不一致的区间"]
+        async fn ranges(
+            &self,
+            request: tonic::Request<super::RangesRequest>,
+        ) -> Result<tonic::Response<super::RangesReply>, tonic::Status>;
+        #[doc = " 以 prefix 开头的实例的摘要"]
+        async fn digests(
+            &self,
+            request: tonic::Request<super::DigestsRequest>,
+        ) -> Result<tonic::Response<super::DigestsReply>, tonic::Status>;
+        #[doc = " 还没有该实例时返回 NOT_FOUND"]
+        async fn fetch(
+            &self,
+            request: tonic::Request<super::GetInstanceRequest>,
+        ) -> Result<tonic::Response<super::Instance>, tonic::Status>;
+        #[doc = " 一次拉取多个实例，其中的值只是已接受，不一定已被选定"]
+        async fn fetch_many(
+            &self,
+            request: tonic::Request<super::FetchRequest>,
+        ) -> Result<tonic::Response<super::FetchReply>, tonic::Status>;
+        async fn fence(
+            &self,
+            request: tonic::Request<super::FenceRequest>,
+        ) -> Result<tonic::Response<super::FenceReply>, tonic::Status>;
+        #[doc = " 成员变更时列出需要复制到新配置的实例"]
+        async fn list_keys(
+            &self,
+            request: tonic::Request<super::ListKeysRequest>,
+        ) -> Result<tonic::Response<super::ListKeysReply>, tonic::Status>;
+    }
+    #[doc = " 节点之间的 anti-entropy 和成员变更，只允许节点的身份调用"]
+    #[derive(Debug)]
+    pub struct PeerServer<T: Peer> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Peer> PeerServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for PeerServer<T>
+    where
+        T: Peer,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/paxos.Peer/Ranges" => {
+                    #[allow(non_camel_case_types)]
+                    struct RangesSvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::RangesRequest> for RangesSvc<T> {
+                        type Response = super::RangesReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RangesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).ranges(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = RangesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Peer/Digests" => {
+                    #[allow(non_camel_case_types)]
+                    struct DigestsSvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::DigestsRequest> for DigestsSvc<T> {
+                        type Response = super::DigestsReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DigestsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).digests(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = DigestsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Peer/Fetch" => {
+                    #[allow(non_camel_case_types)]
+                    struct FetchSvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::GetInstanceRequest> for FetchSvc<T> {
+                        type Response = super::Instance;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetInstanceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).fetch(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = FetchSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Peer/FetchMany" => {
+                    #[allow(non_camel_case_types)]
+                    struct FetchManySvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::FetchRequest> for FetchManySvc<T> {
+                        type Response = super::FetchReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FetchRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).fetch_many(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = FetchManySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Peer/Fence" => {
+                    #[allow(non_camel_case_types)]
+                    struct FenceSvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::FenceRequest> for FenceSvc<T> {
+                        type Response = super::FenceReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FenceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).fence(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = FenceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/paxos.Peer/ListKeys" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListKeysSvc<T: Peer>(pub Arc<T>);
+                    impl<T: Peer> tonic::server::UnaryService<super::ListKeysRequest> for ListKeysSvc<T> {
+                        type Response = super::ListKeysReply;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListKeysRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list_keys(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ListKeysSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Peer> Clone for PeerServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Peer> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Peer> tonic::transport::NamedService for PeerServer<T> {
+        const NAME: &'static str = "paxos.Peer";
     }
 }
@@ -0,0 +1,390 @@
+use anyhow::{Error, Result};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// 判断一组 Acceptor 能否构成 quorum，Acceptor 用其在配置中的下标表示
+///
+/// 任意 phase 1 quorum 与任意 phase 2 quorum 必须相交；
+/// phase 2 quorum 之间不要求相交，因此可以用更大的 prepare quorum 换更小的 accept quorum。
+pub trait QuorumSystem: Debug + Send + Sync {
+    /// acceptors 能否完成 prepare
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool;
+    /// acceptors 能否完成 accept
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool;
+    /// phase 1 quorum 最少包含的 Acceptor 数，用于报错
+    fn phase1_size(&self) -> usize;
+    /// phase 2 quorum 最少包含的 Acceptor 数，用于报错
+    fn phase2_size(&self) -> usize;
+    /// 构造时针对的 Acceptor 数，须与配置中的节点数相同
+    fn acceptors(&self) -> usize;
+    /// 成员变更后，为 n 个节点构造同类的 quorum，zones 与新的节点一一对应；无法构造时返回 None
+    fn resize(&self, _n: usize, _zones: &[String]) -> Option<Arc<dyn QuorumSystem>> {
+        None
+    }
+}
+
+/// 去掉重复和超出范围的下标
+fn distinct(acceptors: &[usize], n: usize) -> Vec<usize> {
+    let mut list: Vec<usize> = acceptors.iter().copied().filter(|i| *i < n).collect();
+    list.sort_unstable();
+    list.dedup();
+    list
+}
+
+/// 简单多数派
+#[derive(Debug, Clone)]
+pub struct Majority {
+    n: usize,
+}
+
+impl Majority {
+    pub fn new(n: usize) -> Self {
+        Majority { n }
+    }
+}
+
+impl QuorumSystem for Majority {
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool {
+        distinct(acceptors, self.n).len() >= self.phase1_size()
+    }
+
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool {
+        distinct(acceptors, self.n).len() >= self.phase2_size()
+    }
+
+    fn phase1_size(&self) -> usize {
+        self.n / 2 + 1
+    }
+
+    fn phase2_size(&self) -> usize {
+        self.n / 2 + 1
+    }
+
+    fn acceptors(&self) -> usize {
+        self.n
+    }
+
+    fn resize(&self, n: usize, _zones: &[String]) -> Option<Arc<dyn QuorumSystem>> {
+        Some(Arc::new(Majority::new(n)))
+    }
+}
+
+/// 加权投票，权重之和超过总权重一半即为 quorum
+#[derive(Debug, Clone)]
+pub struct Weighted {
+    weights: Vec<u64>,
+}
+
+impl Weighted {
+    pub fn new(weights: Vec<u64>) -> Result<Self> {
+        if weights.iter().sum::<u64>() == 0 {
+            return Err(Error::msg("total weight must be positive"));
+        }
+        Ok(Weighted { weights })
+    }
+
+    fn is_quorum(&self, acceptors: &[usize]) -> bool {
+        let total: u64 = self.weights.iter().sum();
+        let votes: u64 = distinct(acceptors, self.weights.len())
+            .into_iter()
+            .map(|i| self.weights[i])
+            .sum();
+        votes * 2 > total
+    }
+
+    /// 按权重从大到小选取，最少需要的 Acceptor 数
+    fn size(&self) -> usize {
+        let total: u64 = self.weights.iter().sum();
+        let mut weights = self.weights.clone();
+        weights.sort_unstable_by(|a, b| b.cmp(a));
+        let mut votes = 0;
+        for (i, w) in weights.into_iter().enumerate() {
+            votes += w;
+            if votes * 2 > total {
+                return i + 1;
+            }
+        }
+        self.weights.len()
+    }
+}
+
+impl QuorumSystem for Weighted {
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool {
+        self.is_quorum(acceptors)
+    }
+
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool {
+        self.is_quorum(acceptors)
+    }
+
+    fn phase1_size(&self) -> usize {
+        self.size()
+    }
+
+    fn phase2_size(&self) -> usize {
+        self.size()
+    }
+
+    fn acceptors(&self) -> usize {
+        self.weights.len()
+    }
+}
+
+/// 网格 quorum：Acceptor i 位于第 i / cols 行、第 i % cols 列
+///
+/// phase 1 需要完整的一列，phase 2 需要完整的一行，任意一行与一列都相交。
+#[derive(Debug, Clone)]
+pub struct Grid {
+    rows: usize,
+    cols: usize,
+}
+
+impl Grid {
+    pub fn new(rows: usize, cols: usize) -> Result<Self> {
+        if rows == 0 || cols == 0 {
+            return Err(Error::msg("grid must have at least one row and column"));
+        }
+        Ok(Grid { rows, cols })
+    }
+}
+
+impl QuorumSystem for Grid {
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool {
+        let acceptors = distinct(acceptors, self.rows * self.cols);
+        (0..self.cols).any(|c| (0..self.rows).all(|r| acceptors.contains(&(r * self.cols + c))))
+    }
+
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool {
+        let acceptors = distinct(acceptors, self.rows * self.cols);
+        (0..self.rows).any(|r| (0..self.cols).all(|c| acceptors.contains(&(r * self.cols + c))))
+    }
+
+    fn phase1_size(&self) -> usize {
+        self.rows
+    }
+
+    fn phase2_size(&self) -> usize {
+        self.cols
+    }
+
+    fn acceptors(&self) -> usize {
+        self.rows * self.cols
+    }
+}
+
+/// Flexible Paxos：任意 phase1 个 Acceptor 完成 prepare，任意 phase2 个完成 accept，
+/// 要求 phase1 + phase2 > n
+#[derive(Debug, Clone)]
+pub struct Flexible {
+    n: usize,
+    phase1: usize,
+    phase2: usize,
+}
+
+impl Flexible {
+    pub fn new(n: usize, phase1: usize, phase2: usize) -> Result<Self> {
+        if phase1 > n || phase2 > n || phase1 + phase2 <= n {
+            return Err(Error::msg(format!(
+                "invalid flexible quorum: |Q1|={} |Q2|={} n={}",
+                phase1, phase2, n
+            )));
+        }
+        Ok(Flexible { n, phase1, phase2 })
+    }
+}
+
+impl QuorumSystem for Flexible {
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool {
+        distinct(acceptors, self.n).len() >= self.phase1
+    }
+
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool {
+        distinct(acceptors, self.n).len() >= self.phase2
+    }
+
+    fn phase1_size(&self) -> usize {
+        self.phase1
+    }
+
+    fn phase2_size(&self) -> usize {
+        self.phase2
+    }
+
+    fn acceptors(&self) -> usize {
+        self.n
+    }
+}
+
+/// 按 zone 计算 quorum 的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZonePolicy {
+    /// 多数 zone 中各有多数 Acceptor 应答
+    MajorityOfZones,
+    /// 多数派，但要求任意一个 zone 整体失效后剩下的 Acceptor 仍构成多数派
+    AnyZoneMayFail,
+}
+
+impl FromStr for ZonePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "majority-of-zones" => Ok(ZonePolicy::MajorityOfZones),
+            "any-zone-may-fail" => Ok(ZonePolicy::AnyZoneMayFail),
+            _ => Err(Error::msg(format!("unknown zone policy: {}", s))),
+        }
+    }
+}
+
+/// 感知 zone 的 quorum，同一机架或机房的 Acceptor 可能一起失效
+#[derive(Debug, Clone)]
+pub struct ZoneQuorum {
+    /// 每个 Acceptor 所在的 zone
+    zones: Vec<String>,
+    /// 去重后的 zone，以及各 zone 中 Acceptor 的下标
+    members: Vec<Vec<usize>>,
+    policy: ZonePolicy,
+}
+
+impl ZoneQuorum {
+    pub fn new(zones: Vec<String>, policy: ZonePolicy) -> Result<Self> {
+        let mut names: Vec<&String> = zones.iter().collect();
+        names.sort_unstable();
+        names.dedup();
+        let members: Vec<Vec<usize>> = names
+            .iter()
+            .map(|name| (0..zones.len()).filter(|i| &zones[*i] == *name).collect())
+            .collect();
+        if members.is_empty() {
+            return Err(Error::msg("no zone configured"));
+        }
+        let n = zones.len();
+        if policy == ZonePolicy::AnyZoneMayFail {
+            if let Some(zone) = members.iter().find(|m| n - m.len() < n / 2 + 1) {
+                return Err(Error::msg(format!(
+                    "losing zone {} leaves no majority",
+                    zones[zone[0]]
+                )));
+            }
+        }
+        Ok(ZoneQuorum {
+            zones,
+            members,
+            policy,
+        })
+    }
+
+    /// Acceptor i 所在的 zone
+    pub fn zone(&self, i: usize) -> Option<&str> {
+        self.zones.get(i).map(|z| z.as_str())
+    }
+
+    fn is_quorum(&self, acceptors: &[usize]) -> bool {
+        let acceptors = distinct(acceptors, self.zones.len());
+        match self.policy {
+            ZonePolicy::MajorityOfZones => {
+                let zones = self
+                    .members
+                    .iter()
+                    .filter(|m| {
+                        let acks = m.iter().filter(|i| acceptors.contains(i)).count();
+                        acks > m.len() / 2
+                    })
+                    .count();
+                zones > self.members.len() / 2
+            }
+            ZonePolicy::AnyZoneMayFail => acceptors.len() > self.zones.len() / 2,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self.policy {
+            ZonePolicy::MajorityOfZones => {
+                let mut sizes: Vec<usize> = self.members.iter().map(|m| m.len() / 2 + 1).collect();
+                sizes.sort_unstable();
+                sizes[..self.members.len() / 2 + 1].iter().sum()
+            }
+            ZonePolicy::AnyZoneMayFail => self.zones.len() / 2 + 1,
+        }
+    }
+}
+
+impl QuorumSystem for ZoneQuorum {
+    fn is_phase1_quorum(&self, acceptors: &[usize]) -> bool {
+        self.is_quorum(acceptors)
+    }
+
+    fn is_phase2_quorum(&self, acceptors: &[usize]) -> bool {
+        self.is_quorum(acceptors)
+    }
+
+    fn phase1_size(&self) -> usize {
+        self.size()
+    }
+
+    fn phase2_size(&self) -> usize {
+        self.size()
+    }
+
+    fn acceptors(&self) -> usize {
+        self.zones.len()
+    }
+
+    fn resize(&self, n: usize, zones: &[String]) -> Option<Arc<dyn QuorumSystem>> {
+        if zones.len() != n {
+            return None;
+        }
+        let quorum = ZoneQuorum::new(zones.to_vec(), self.policy).ok()?;
+        Some(Arc::new(quorum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quorum_systems() {
+        let majority = Majority::new(3);
+        assert!(majority.is_phase2_quorum(&[0, 2]));
+        assert!(!majority.is_phase2_quorum(&[1, 1]));
+
+        let weighted = Weighted::new(vec![3, 1, 1]).unwrap();
+        assert!(weighted.is_phase1_quorum(&[0]));
+        assert!(!weighted.is_phase1_quorum(&[1, 2]));
+        assert_eq!(weighted.phase1_size(), 1);
+
+        // 0 1 2
+        // 3 4 5
+        let grid = Grid::new(2, 3).unwrap();
+        assert!(grid.is_phase1_quorum(&[1, 4]));
+        assert!(!grid.is_phase1_quorum(&[0, 1, 2]));
+        assert!(grid.is_phase2_quorum(&[3, 4, 5]));
+        assert!(!grid.is_phase2_quorum(&[0, 4]));
+
+        let flexible = Flexible::new(5, 4, 2).unwrap();
+        assert!(flexible.is_phase2_quorum(&[0, 4]));
+        assert!(!flexible.is_phase1_quorum(&[0, 1, 2]));
+        assert!(Flexible::new(5, 3, 2).is_err());
+    }
+
+    #[test]
+    fn test_zone_quorum() {
+        let zones: Vec<String> = ["a", "a", "b", "b", "c"]
+            .iter()
+            .map(|z| z.to_string())
+            .collect();
+        let quorum = ZoneQuorum::new(zones.clone(), ZonePolicy::MajorityOfZones).unwrap();
+        // a 和 c 各有多数应答
+        assert!(quorum.is_phase2_quorum(&[0, 1, 4]));
+        // 三个 Acceptor 但只覆盖了 b 一个 zone 的多数
+        assert!(!quorum.is_phase2_quorum(&[0, 2, 3]));
+        assert_eq!(quorum.phase2_size(), 3);
+        assert_eq!(quorum.zone(4), Some("c"));
+
+        assert!(ZoneQuorum::new(zones, ZonePolicy::AnyZoneMayFail).is_ok());
+        let zones = vec!["a".to_string(), "a".to_string(), "b".to_string()];
+        assert!(ZoneQuorum::new(zones, ZonePolicy::AnyZoneMayFail).is_err());
+    }
+}
@@ -1,34 +1,387 @@
+use crate::admin::AdminService;
+use crate::anti_entropy::PeerService;
+use crate::auth;
+use crate::epaxos::{self, EpaxosService};
+use crate::membership;
+use crate::mencius;
+use crate::metrics::AcceptorMetrics;
 use crate::paxos::paxos_server::Paxos;
-use crate::paxos::{Acceptor, Proposer, RoundNum};
+use crate::paxos::stream_request::Op;
+use crate::paxos::{
+    Acceptor, AcceptorBatch, EInstance, Instance, PaxosInstanceId, Proposer, ProposerBatch,
+    RoundNum, StreamReply, StreamRequest,
+};
+use crate::storage::{epaxos_record, FsyncMode, Journal};
+use crate::trace;
+use crate::witness;
+use anyhow::{bail, Result};
+use futures::{Stream, TryStreamExt};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use tonic::{Request, Response, Status};
+use std::fmt;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Instant;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, error, info, info_span, Span};
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct PaxosService {
     pub storage: Arc<Mutex<HashMap<String, Acceptor>>>,
+    journal: Option<Arc<Journal>>,
+    metrics: Arc<AcceptorMetrics>,
+    pub(crate) epaxos: Arc<Mutex<epaxos::State>>,
+    /// 见证者只保存 ballot 和值的摘要
+    witness: bool,
+    /// 已知的最新配置的 epoch，更早配置中的 Proposer 的请求被拒绝
+    epoch: Arc<RwLock<i64>>,
+    /// Mencius 中 slot 所有者的 proposer_id，见 [`crate::Mencius`]
+    mencius_owners: Vec<i64>,
 }
 
-#[tonic::async_trait]
-impl Paxos for PaxosService {
-    async fn prepare(&self, request: Request<Proposer>) -> Result<Response<Acceptor>, Status> {
-        let proposer = request.get_ref();
-        let id = proposer.id.clone().unwrap();
-        let key = id.key;
-        let request_round = proposer.round.as_ref().unwrap();
+impl PaxosService {
+    /// 从数据目录恢复状态，之后的每次修改都会先写入日志
+    pub fn open(dir: &Path, fsync: FsyncMode) -> Result<Self> {
+        let (journal, storage, epaxos) = Journal::open(dir, fsync)?;
+        Ok(PaxosService {
+            storage: Arc::new(Mutex::new(storage)),
+            epaxos: Arc::new(Mutex::new(epaxos::State::restore(epaxos))),
+            epoch: Arc::new(RwLock::new(journal.epoch()?)),
+            journal: Some(Arc::new(journal)),
+            ..Default::default()
+        })
+    }
+
+    /// 作为见证者运行，接受的值只保存摘要，见 [`crate::Client::set_witnesses`]
+    pub fn set_witness(&mut self, witness: bool) {
+        self.witness = witness;
+    }
+
+    /// Mencius 日志中第 s 个 slot 属于 owners[s % owners.len()]，只有它可以用 round 0 直接 accept
+    pub fn set_mencius_owners(&mut self, owners: Vec<i64>) {
+        self.mencius_owners = owners;
+    }
+
+    pub fn metrics(&self) -> Arc<AcceptorMetrics> {
+        self.metrics.clone()
+    }
+
+    /// 共享本服务存储的 [`AdminService`]
+    pub fn admin(&self) -> AdminService {
+        AdminService::new(self.storage.clone(), self.journal.clone())
+    }
+
+    /// 共享本服务存储的 [`PeerService`]，供其他节点 anti-entropy 和成员变更
+    pub fn peer(&self) -> PeerService {
+        PeerService::new(self.clone())
+    }
+
+    /// 共享本服务指标和日志的 [`EpaxosService`]
+    pub fn epaxos(&self) -> EpaxosService {
+        EpaxosService::new(self.clone())
+    }
+
+    /// 用其他节点上 key 的状态修复本节点，返回是否修改了本地状态
+    ///
+    /// 只在 peer 接受值的 round 大于本地接受值的 round、且不小于本地承诺过的 round 时采纳，
+    /// 相当于本节点在该 round 接受了同一个值，不会违反已做出的承诺；该值不一定已被选定。
+    /// 见证者的摘要和 RS-Paxos 的分片只属于其所在的节点，不会被复制。
+    pub(crate) fn repair(&self, key: &str, peer: &Acceptor) -> Result<bool> {
+        let value = match &peer.value {
+            Some(v) if v.digest == 0 && v.shard.is_none() => v.clone(),
+            _ => return Ok(false),
+        };
+        let round = peer.round.clone().unwrap_or_default();
+        let mut storage = self.storage.lock().unwrap();
+        let local = storage.get(key).cloned().unwrap_or_else(|| Acceptor {
+            round: Some(RoundNum::default()),
+            last_round: Some(RoundNum::default()),
+            value: None,
+        });
+        let value_round = local.round.clone().unwrap_or_default();
+        let last_round = local.last_round.clone().unwrap_or_default();
+        if round <= value_round || round < last_round {
+            return Ok(false);
+        }
+        let repaired = Acceptor {
+            round: Some(round.clone()),
+            last_round: Some(round),
+            value: Some(match self.witness {
+                true => witness::strip(value),
+                false => value,
+            }),
+        };
+        self.persist(key, &repaired)?;
+        storage.insert(key.to_string(), repaired);
+        drop(storage);
+        debug!(key, "instance repaired from peer");
+        self.maybe_compact()?;
+        Ok(true)
+    }
+
+    /// 记录新的 epoch，之后拒绝更早配置中的 Proposer 的请求，返回当前的 epoch
+    ///
+    /// 等待正在处理的请求完成后才修改，返回后不会再有旧配置中的请求生效。
+    /// 本节点须已接受该 epoch 的配置实例，否则拒绝，避免把 epoch 推到不存在的配置。
+    pub(crate) fn fence_epoch(&self, epoch: i64) -> Result<i64> {
+        let mut current = self.epoch.write().unwrap();
+        if epoch > *current {
+            let storage = self.storage.lock().unwrap();
+            let known = storage
+                .get(&membership::key(epoch))
+                .is_some_and(|acc| acc.value.is_some());
+            if !known {
+                return Err(InvalidRequest(format!(
+                    "no configuration accepted for epoch {}",
+                    epoch
+                ))
+                .into());
+            }
+            drop(storage);
+            if let Some(journal) = &self.journal {
+                journal.set_epoch(epoch)?;
+            }
+            *current = epoch;
+            info!(epoch, "older configurations fenced");
+        }
+        Ok(*current)
+    }
+
+    /// 请求的 epoch 早于已知的 epoch 时拒绝，成员变更实例除外，旧配置中的 Proposer 需要从中学到新配置
+    ///
+    /// 返回的读锁在处理请求期间持有，与 [`PaxosService::fence_epoch`] 互斥。
+    fn check_epoch(&self, proposer: &Proposer) -> Result<RwLockReadGuard<'_, i64>> {
+        let epoch = self.epoch.read().unwrap();
+        let key = proposer.id.as_ref().map(|id| id.key.as_str()).unwrap_or("");
+        if proposer.epoch < *epoch && !membership::is_membership_key(key) {
+            debug!(epoch = proposer.epoch, current = *epoch, "stale epoch");
+            return Err(StaleEpoch(*epoch).into());
+        }
+        Ok(epoch)
+    }
+
+    /// key 是 Mencius 的 slot，且 round 来自该 slot 的所有者
+    fn owns_slot(&self, key: &str, round: &RoundNum) -> bool {
+        match mencius::slot(key) {
+            Some(slot) if !self.mencius_owners.is_empty() => {
+                let n = self.mencius_owners.len() as i64;
+                self.mencius_owners[slot.rem_euclid(n) as usize] == round.proposer_id
+            }
+            _ => false,
+        }
+    }
+
+    fn persist(&self, key: &str, acceptor: &Acceptor) -> Result<()> {
+        if let Some(journal) = &self.journal {
+            journal.append(key, acceptor)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn persist_epaxos(&self, instance: &EInstance) -> Result<()> {
+        if let Some(journal) = &self.journal {
+            journal.append_epaxos(instance)?;
+        }
+        Ok(())
+    }
+
+    /// 日志中的记录远多于实例数时，写快照并清空日志
+    pub(crate) fn maybe_compact(&self) -> Result<()> {
+        let journal = match &self.journal {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        // 先后持有存储和 EPaxos 状态的锁，压缩期间没有新的修改
+        let storage = self.storage.lock().unwrap();
+        let epaxos = self.epaxos.lock().unwrap();
+        let live = storage.len() + epaxos.len();
+        if journal.should_compact(live) {
+            let paxos = storage.iter().map(|(key, acc)| Instance {
+                key: key.clone(),
+                acceptor: Some(acc.clone()),
+                epaxos: None,
+            });
+            journal.compact(paxos.chain(epaxos.instances().map(epaxos_record)))?;
+            info!(instances = live, "journal compacted");
+        }
+        Ok(())
+    }
+}
+
+/// 实例在存储中的 key：version 为 0 时即 key 本身，否则为 `key\0version`
+///
+/// 用户的 key 不能包含 `\0`，以 `\0` 开头的 key 保留给系统实例，因此不会与其他实例冲突。
+pub fn instance_key(key: &str, version: i64) -> String {
+    match version {
+        0 => key.to_string(),
+        _ => format!("{}\0{}", key, version),
+    }
+}
+
+/// 从存储 key 还原 key 和 version，见 [`instance_key`]
+pub(crate) fn split_instance_key(stored: &str) -> (String, i64) {
+    if let Some(i) = stored.rfind('\0').filter(|i| *i > 0) {
+        if let Ok(version) = stored[i + 1..].parse() {
+            return (stored[..i].to_string(), version);
+        }
+    }
+    (stored.to_string(), 0)
+}
+
+/// 请求中实例的存储 key，key 只允许在开头出现 `\0`
+fn request_key(id: &PaxosInstanceId) -> Result<String> {
+    if id.key.chars().skip(1).any(|c| c == '\0') {
+        return Err(InvalidRequest(format!("invalid key {:?}", id.key)).into());
+    }
+    Ok(instance_key(&id.key, id.version))
+}
+
+/// 请求中的实例 id 和 round，任一缺少时请求无效
+fn request_fields(proposer: &Proposer) -> Result<(&PaxosInstanceId, &RoundNum)> {
+    let id = proposer
+        .id
+        .as_ref()
+        .ok_or_else(|| InvalidRequest("missing instance id".to_string()))?;
+    let round = proposer
+        .round
+        .as_ref()
+        .ok_or_else(|| InvalidRequest("missing round".to_string()))?;
+    Ok((id, round))
+}
+
+/// 请求本身不合法，重试也不会成功
+#[derive(Debug)]
+struct InvalidRequest(String);
+
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidRequest {}
+
+/// 请求来自更早的配置，Proposer 需要切换到新配置后重试
+#[derive(Debug)]
+struct StaleEpoch(i64);
+
+impl fmt::Display for StaleEpoch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", crate::error::STALE_EPOCH, self.0)
+    }
+}
+
+impl std::error::Error for StaleEpoch {}
+
+pub(crate) fn internal(e: anyhow::Error) -> Status {
+    if let Some(stale) = e.downcast_ref::<StaleEpoch>() {
+        return Status::failed_precondition(stale.to_string());
+    }
+    if let Some(invalid) = e.downcast_ref::<InvalidRequest>() {
+        return Status::invalid_argument(invalid.to_string());
+    }
+    error!(error = %e, "request failed");
+    Status::internal(e.to_string())
+}
+
+/// 每个请求一个 span，带上 Proposer 传来的 trace id
+fn request_span(method: &'static str, request: &Request<Proposer>) -> Span {
+    proposer_span(method, request.get_ref(), &trace::extract(request))
+}
+
+fn proposer_span(method: &'static str, proposer: &Proposer, trace_id: &str) -> Span {
+    let id = proposer.id.clone().unwrap_or_default();
+    let round = proposer.round.clone().unwrap_or_default();
+    info_span!(
+        "acceptor",
+        method,
+        key = %id.key,
+        version = id.version,
+        ballot = %format!("{}.{}", round.number, round.proposer_id),
+        trace_id = %trace_id,
+    )
+}
+
+fn batch_span(method: &'static str, request: &Request<ProposerBatch>) -> Span {
+    info_span!(
+        "acceptor",
+        method,
+        instances = request.get_ref().proposers.len(),
+        trace_id = %trace::extract(request),
+    )
+}
+
+impl PaxosService {
+    /// 处理双向流上的一条请求，失败时在应答中带上原因，不中断流
+    fn handle_stream(&self, metadata: &MetadataMap, request: StreamRequest) -> StreamReply {
+        let tag = request.tag;
+        let failed = |error: String| StreamReply {
+            tag,
+            acceptor: None,
+            error,
+        };
+        let (method, proposer) = match request.op {
+            Some(Op::Prepare(p)) => ("prepare", p),
+            Some(Op::Accept(p)) => ("accept", p),
+            None => return failed("empty stream request".to_string()),
+        };
+        if let Some(status) = auth::deny(metadata, Some(&proposer)) {
+            return failed(status.message().to_string());
+        }
+        let start = Instant::now();
+        let span = proposer_span(method, &proposer, &request.trace_id);
+        let result = span.in_scope(|| match method {
+            "prepare" => self.handle_prepare(&proposer),
+            _ => self.handle_accept(&proposer),
+        });
+        self.metrics.observe(method, start);
+        match result {
+            Ok(acceptor) => StreamReply {
+                tag,
+                acceptor: Some(acceptor),
+                error: String::new(),
+            },
+            Err(e) => {
+                span.in_scope(|| error!(error = %e, "request failed"));
+                failed(e.to_string())
+            }
+        }
+    }
+
+    fn handle_prepare(&self, proposer: &Proposer) -> Result<Acceptor> {
+        let (id, request_round) = request_fields(proposer)?;
+        let key = request_key(id)?;
+        let _epoch = self.check_epoch(proposer)?;
 
         // for lock storage
-        {
+        let result = {
             let mut storage = self.storage.lock().unwrap();
-            if storage.contains_key(&key) {
-                let value = storage.get(&key).cloned().unwrap();
-                if request_round.number > value.round.as_ref().unwrap().number {
+            // round 0 的 prepare 只读取状态，不做承诺，也不为没有状态的实例创建状态，
+            // 之后 Mencius 的所有者仍可直接 accept
+            if request_round.number == 0 {
+                debug!("read without promise");
+                return Ok(storage.get(&key).cloned().unwrap_or_else(|| Acceptor {
+                    round: Some(RoundNum::default()),
+                    last_round: Some(RoundNum::default()),
+                    value: None,
+                }));
+            }
+            if let Some(value) = storage.get(&key).cloned() {
+                // 只承诺比已承诺的 round 更大的 ballot，(number, proposer_id) 整体比较
+                if *request_round > value.last_round.clone().unwrap_or_default() {
                     // 保存请求中的 round 到 last_round
                     let mut new_acc = value.clone();
                     new_acc.last_round = Some(request_round.clone());
+                    self.persist(&key, &new_acc)?;
                     storage.insert(key, new_acc);
+                    self.metrics.promises.with_label_values(&["granted"]).inc();
+                    debug!("promise granted");
+                } else {
+                    self.metrics.promises.with_label_values(&["rejected"]).inc();
+                    debug!(round = ?value.round, "promise rejected");
                 }
-                Ok(Response::new(value))
+                Ok(value)
             } else {
                 let acc = Acceptor {
                     round: Some(RoundNum::default()),
@@ -37,48 +390,161 @@ impl Paxos for PaxosService {
                 };
                 let mut acceptor = acc.clone();
                 acceptor.last_round = proposer.round.clone();
+                self.persist(&key, &acceptor)?;
                 storage.insert(key, acceptor);
-                Ok(Response::new(acc))
+                self.metrics.promises.with_label_values(&["granted"]).inc();
+                debug!("promise granted for new instance");
+                Ok(acc)
             }
-        } // unlock storage
+        }; // unlock storage
+        self.maybe_compact()?;
+        result
     }
 
-    async fn accept(&self, request: Request<Proposer>) -> Result<Response<Acceptor>, Status> {
-        let proposer = request.get_ref();
-        let id = proposer.id.clone().unwrap();
-        let key = id.key;
-        let request_round = proposer.round.as_ref().unwrap();
+    fn handle_accept(&self, proposer: &Proposer) -> Result<Acceptor> {
+        let (id, request_round) = request_fields(proposer)?;
+        let key = request_key(id)?;
+        let _epoch = self.check_epoch(proposer)?;
         let request_value = proposer.value.clone();
 
         // for lock storage
-        {
+        let result = {
             let mut storage = self.storage.lock().unwrap();
-            let acc = storage.get(&key).cloned().unwrap();
-            let last_round = acc.last_round.clone().unwrap().number;
-            let value_round = acc.round.clone().unwrap().number;
-            if request_round.number == last_round && last_round > value_round {
+            let acc = storage.get(&key).cloned().unwrap_or_else(|| Acceptor {
+                round: Some(RoundNum::default()),
+                last_round: Some(RoundNum::default()),
+                value: None,
+            });
+            let last_round = acc.last_round.clone().unwrap_or_default();
+            let value_round = acc.round.clone().unwrap_or_default();
+            let accepted = if request_round.number == 0 {
+                // round 0 是 Mencius 中 slot 所有者隐含的 prepare，slot 被更大的 round prepare 过
+                // 或已接受值之后不再生效
+                if !self.owns_slot(&id.key, request_round) {
+                    bail!("round 0 is reserved for the owner of a Mencius slot");
+                }
+                last_round.number == 0 && acc.value.is_none()
+            } else {
+                // 没有承诺更大的 round 即接受，prepare 可能晚于 accept 到达；同一 round 中只接受一次
+                *request_round >= last_round && *request_round > value_round
+            };
+            if accepted {
                 let mut new_value = acc.clone();
                 new_value.round = Some(request_round.clone());
-                new_value.value = request_value;
+                new_value.value = match self.witness {
+                    true => request_value.map(witness::strip),
+                    false => request_value,
+                };
                 new_value.last_round = Some(request_round.clone());
+                self.persist(&key, &new_value)?;
                 storage.insert(key, new_value);
+                self.metrics.accepts.with_label_values(&["accepted"]).inc();
+                debug!(value = ?proposer.value, "value accepted");
+            } else {
+                self.metrics.accepts.with_label_values(&["rejected"]).inc();
+                debug!(?last_round, ?value_round, "accept rejected");
             }
-            Ok(Response::new(acc))
-        } // unlock storage
+            Ok(acc)
+        }; // unlock storage
+        self.maybe_compact()?;
+        result
+    }
+}
+
+#[tonic::async_trait]
+impl Paxos for PaxosService {
+    async fn prepare(&self, request: Request<Proposer>) -> Result<Response<Acceptor>, Status> {
+        if let Some(status) = auth::deny(request.metadata(), Some(request.get_ref())) {
+            return Err(status);
+        }
+        let start = Instant::now();
+        let span = request_span("prepare", &request);
+        let result = span.in_scope(|| self.handle_prepare(request.get_ref()));
+        self.metrics.observe("prepare", start);
+        result.map(Response::new).map_err(internal)
+    }
+
+    async fn accept(&self, request: Request<Proposer>) -> Result<Response<Acceptor>, Status> {
+        if let Some(status) = auth::deny(request.metadata(), Some(request.get_ref())) {
+            return Err(status);
+        }
+        let start = Instant::now();
+        let span = request_span("accept", &request);
+        let result = span.in_scope(|| self.handle_accept(request.get_ref()));
+        self.metrics.observe("accept", start);
+        result.map(Response::new).map_err(internal)
+    }
+
+    async fn prepare_batch(
+        &self,
+        request: Request<ProposerBatch>,
+    ) -> Result<Response<AcceptorBatch>, Status> {
+        let proposers = &request.get_ref().proposers;
+        if let Some(status) = auth::deny(request.metadata(), proposers) {
+            return Err(status);
+        }
+        let start = Instant::now();
+        let span = batch_span("prepare_batch", &request);
+        let result = span.in_scope(|| {
+            proposers
+                .iter()
+                .map(|p| self.handle_prepare(p))
+                .collect::<Result<Vec<_>>>()
+        });
+        self.metrics.observe("prepare_batch", start);
+        result
+            .map(|acceptors| Response::new(AcceptorBatch { acceptors }))
+            .map_err(internal)
+    }
+
+    async fn accept_batch(
+        &self,
+        request: Request<ProposerBatch>,
+    ) -> Result<Response<AcceptorBatch>, Status> {
+        let proposers = &request.get_ref().proposers;
+        if let Some(status) = auth::deny(request.metadata(), proposers) {
+            return Err(status);
+        }
+        let start = Instant::now();
+        let span = batch_span("accept_batch", &request);
+        let result = span.in_scope(|| {
+            proposers
+                .iter()
+                .map(|p| self.handle_accept(p))
+                .collect::<Result<Vec<_>>>()
+        });
+        self.metrics.observe("accept_batch", start);
+        result
+            .map(|acceptors| Response::new(AcceptorBatch { acceptors }))
+            .map_err(internal)
+    }
+
+    type StreamStream =
+        Pin<Box<dyn Stream<Item = Result<StreamReply, Status>> + Send + Sync + 'static>>;
+
+    async fn stream(
+        &self,
+        request: Request<Streaming<StreamRequest>>,
+    ) -> Result<Response<Self::StreamStream>, Status> {
+        // 认证信息在建立流时确定，之后的每条请求都按同一调用方检查
+        let metadata = request.metadata().clone();
+        let service = self.clone();
+        let replies = request
+            .into_inner()
+            .map_ok(move |r| service.handle_stream(&metadata, r));
+        Ok(Response::new(Box::pin(replies)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::paxos::{PaxosInstanceId, RoundNum, Value};
+    use crate::paxos::{RoundNum, Value};
     use tokio_test::block_on;
 
     #[test]
     fn test_prepare() {
-        let service = PaxosService {
-            storage: Default::default(),
-        };
+        let service = PaxosService::default();
         let r0 = Request::new(Proposer {
             id: Some(PaxosInstanceId {
                 key: "test".to_string(),
@@ -88,7 +554,11 @@ mod tests {
                 number: 1,
                 proposer_id: 0,
             }),
-            value: Some(Value { value: 11 }),
+            value: Some(Value {
+                value: 11,
+                ..Default::default()
+            }),
+            epoch: 0,
         });
         let result = service.prepare(r0);
         let r = block_on(result);
@@ -116,7 +586,11 @@ mod tests {
                 number: 2,
                 proposer_id: 0,
             }),
-            value: Some(Value { value: 03 }),
+            value: Some(Value {
+                value: 3,
+                ..Default::default()
+            }),
+            epoch: 0,
         });
         let r = block_on(service.prepare(r1));
         assert!(r.is_ok());
@@ -140,9 +614,7 @@ mod tests {
 
     #[test]
     fn test_prepare_round() {
-        let service = PaxosService {
-            storage: Default::default(),
-        };
+        let service = PaxosService::default();
         // rnd < last_rnd
         {
             let mut s = service.storage.lock().unwrap();
@@ -155,7 +627,10 @@ mod tests {
                     number: 2,
                     proposer_id: 0,
                 }),
-                value: Some(Value { value: 9 }),
+                value: Some(Value {
+                    value: 9,
+                    ..Default::default()
+                }),
             };
             s.insert("test".to_string(), acc);
         }
@@ -169,6 +644,7 @@ mod tests {
                 proposer_id: 0,
             }),
             value: None,
+            epoch: 0,
         });
         let result = service.prepare(r0);
         let r = block_on(result);
@@ -185,7 +661,10 @@ mod tests {
                     number: 2,
                     proposer_id: 0,
                 }),
-                value: Some(Value { value: 9 }),
+                value: Some(Value {
+                    value: 9,
+                    ..Default::default()
+                }),
             },
             acc
         );
@@ -202,7 +681,10 @@ mod tests {
                     number: 2,
                     proposer_id: 0,
                 }),
-                value: Some(Value { value: 9 }),
+                value: Some(Value {
+                    value: 9,
+                    ..Default::default()
+                }),
             };
             s.insert("test".to_string(), acc);
         }
@@ -216,6 +698,7 @@ mod tests {
                 proposer_id: 0,
             }),
             value: None,
+            epoch: 0,
         });
         let r = block_on(service.prepare(r1));
         assert!(r.is_ok());
@@ -232,16 +715,17 @@ mod tests {
                     number: 2,
                     proposer_id: 0,
                 }),
-                value: Some(Value { value: 9 }),
+                value: Some(Value {
+                    value: 9,
+                    ..Default::default()
+                }),
             }
         );
     }
 
     #[test]
     fn test_accept() {
-        let service = PaxosService {
-            storage: Default::default(),
-        };
+        let service = PaxosService::default();
         let proposer = Proposer {
             id: Some(PaxosInstanceId {
                 key: "test1".to_string(),
@@ -251,7 +735,11 @@ mod tests {
                 number: 1,
                 proposer_id: 0,
             }),
-            value: Some(Value { value: 11 }),
+            value: Some(Value {
+                value: 11,
+                ..Default::default()
+            }),
+            epoch: 0,
         };
         let r0 = Request::new(proposer.clone());
         let r = block_on(service.prepare(r0));
@@ -289,4 +777,271 @@ mod tests {
             acc
         );
     }
+
+    #[test]
+    fn test_recover_from_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let proposer = Proposer {
+            id: Some(PaxosInstanceId {
+                key: "test".to_string(),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 0,
+            }),
+            value: Some(Value {
+                value: 11,
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        {
+            let service = PaxosService::open(dir.path(), FsyncMode::Always).unwrap();
+            assert!(block_on(service.prepare(Request::new(proposer.clone()))).is_ok());
+            assert!(block_on(service.accept(Request::new(proposer.clone()))).is_ok());
+        }
+
+        let service = PaxosService::open(dir.path(), FsyncMode::Always).unwrap();
+        let storage = service.storage.lock().unwrap();
+        assert_eq!(
+            storage.get("test"),
+            Some(&Acceptor {
+                round: proposer.round.clone(),
+                last_round: proposer.round.clone(),
+                value: Some(Value {
+                    value: 11,
+                    ..Default::default()
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_fence_requires_known_configuration() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PaxosService::open(dir.path(), FsyncMode::Always).unwrap();
+        // 没有接受过的配置不能用来 fence
+        let err = service.fence_epoch(i64::MAX).unwrap_err();
+        assert_eq!(internal(err).code(), tonic::Code::InvalidArgument);
+        assert_eq!(service.fence_epoch(0).unwrap(), 0);
+
+        let proposer = Proposer {
+            id: Some(PaxosInstanceId {
+                key: membership::key(1),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 0,
+            }),
+            value: Some(Value {
+                membership: Some(crate::Membership {
+                    epoch: 1,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        assert!(block_on(service.accept(Request::new(proposer))).is_ok());
+        assert!(service.fence_epoch(2).is_err());
+        assert_eq!(service.fence_epoch(1).unwrap(), 1);
+
+        // fence 之后重启仍然生效
+        drop(service);
+        let service = PaxosService::open(dir.path(), FsyncMode::Always).unwrap();
+        assert_eq!(service.fence_epoch(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_prepare_batch() {
+        let service = PaxosService::default();
+        let proposer = |key: &str, number| Proposer {
+            id: Some(PaxosInstanceId {
+                key: key.to_string(),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number,
+                proposer_id: 0,
+            }),
+            value: Some(Value {
+                value: number,
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        let batch = ProposerBatch {
+            proposers: vec![proposer("a", 1), proposer("b", 2)],
+        };
+        let r = block_on(service.prepare_batch(Request::new(batch.clone())));
+        assert_eq!(r.unwrap().into_inner().acceptors.len(), 2);
+        let r = block_on(service.accept_batch(Request::new(batch)));
+        assert_eq!(r.unwrap().into_inner().acceptors.len(), 2);
+
+        let storage = service.storage.lock().unwrap();
+        assert_eq!(
+            storage["a"].value,
+            Some(Value {
+                value: 1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            storage["b"].value,
+            Some(Value {
+                value: 2,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_versions_are_separate_instances() {
+        let service = PaxosService::default();
+        let proposer = |version, value| Proposer {
+            id: Some(PaxosInstanceId {
+                key: "sz".to_string(),
+                version,
+            }),
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 0,
+            }),
+            value: Some(Value {
+                value,
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        for (version, value) in [(0, 3), (2, 5)] {
+            let p = proposer(version, value);
+            assert!(block_on(service.prepare(Request::new(p.clone()))).is_ok());
+            assert!(block_on(service.accept(Request::new(p))).is_ok());
+        }
+        {
+            let storage = service.storage.lock().unwrap();
+            assert_eq!(storage.len(), 2);
+            assert_eq!(storage["sz"].value.as_ref().unwrap().value, 3);
+            assert_eq!(
+                storage[&instance_key("sz", 2)]
+                    .value
+                    .as_ref()
+                    .unwrap()
+                    .value,
+                5
+            );
+        }
+
+        // key 中间不能出现 \0，否则会与其他实例的存储 key 冲突
+        let mut p = proposer(0, 1);
+        p.id.as_mut().unwrap().key = "sz\u{0}2".to_string();
+        let code = |r: Result<Response<Acceptor>, Status>| r.unwrap_err().code();
+        assert_eq!(
+            code(block_on(service.prepare(Request::new(p)))),
+            tonic::Code::InvalidArgument
+        );
+
+        // 缺少 id 或 round 的请求无效，不会让 Acceptor panic
+        let mut p = proposer(2, 1);
+        p.id = None;
+        assert_eq!(
+            code(block_on(service.accept(Request::new(p)))),
+            tonic::Code::InvalidArgument
+        );
+        let mut p = proposer(2, 1);
+        p.round = None;
+        assert_eq!(
+            code(block_on(service.prepare(Request::new(p)))),
+            tonic::Code::InvalidArgument
+        );
+        let batch = ProposerBatch {
+            proposers: vec![Proposer::default()],
+        };
+        let r = block_on(service.accept_batch(Request::new(batch)));
+        assert_eq!(r.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_ballot_tie_break() {
+        let service = PaxosService::default();
+        let proposer = |proposer_id, value| Proposer {
+            id: Some(PaxosInstanceId {
+                key: "tie".to_string(),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id,
+            }),
+            value: Some(Value {
+                value,
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        let stored = || service.storage.lock().unwrap()["tie"].clone();
+
+        // number 相同的两个 ballot 按 proposer_id 排序，(1, 2) 之后不能再接受 (1, 1)
+        assert!(block_on(service.prepare(Request::new(proposer(1, 1)))).is_ok());
+        assert!(block_on(service.prepare(Request::new(proposer(2, 2)))).is_ok());
+        assert!(block_on(service.accept(Request::new(proposer(1, 1)))).is_ok());
+        assert_eq!(stored().value, None);
+        assert!(block_on(service.accept(Request::new(proposer(2, 2)))).is_ok());
+        assert_eq!(stored().value.unwrap().value, 2);
+
+        // 更小的 ballot 不会降低已承诺的 round
+        assert!(block_on(service.prepare(Request::new(proposer(1, 1)))).is_ok());
+        assert_eq!(stored().last_round.unwrap().proposer_id, 2);
+    }
+
+    #[test]
+    fn test_mencius_owner() {
+        let mut service = PaxosService::default();
+        service.set_mencius_owners(vec![7, 8]);
+        let proposer = |key: &str, number, proposer_id, value: Option<i64>| Proposer {
+            id: Some(PaxosInstanceId {
+                key: key.to_string(),
+                version: 0,
+            }),
+            round: Some(RoundNum {
+                number,
+                proposer_id,
+            }),
+            value: value.map(|value| Value {
+                value,
+                ..Default::default()
+            }),
+            epoch: 0,
+        };
+        let stored = |key: &str| service.storage.lock().unwrap().get(key).cloned();
+
+        // slot 1 属于 8，其他 proposer 和普通的 key 都不能用 round 0 跳过 phase 1
+        let slot = "\0mencius/log/1";
+        for request in [
+            proposer(slot, 0, 9, Some(1)),
+            proposer(slot, 0, 7, Some(1)),
+            proposer("plain", 0, 8, Some(1)),
+        ] {
+            assert!(block_on(service.accept(Request::new(request))).is_err());
+        }
+        assert_eq!(stored(slot), None);
+        assert_eq!(stored("plain"), None);
+        assert!(block_on(service.accept(Request::new(proposer(slot, 0, 8, Some(2))))).is_ok());
+        assert_eq!(stored(slot).unwrap().value.unwrap().value, 2);
+
+        // round 0 的 prepare 只读取，不创建状态，所有者之后仍可直接 accept
+        let slot = "\0mencius/log/3";
+        assert!(block_on(service.prepare(Request::new(proposer(slot, 0, 9, None)))).is_ok());
+        assert_eq!(stored(slot), None);
+        assert!(block_on(service.accept(Request::new(proposer(slot, 0, 8, Some(3))))).is_ok());
+        assert_eq!(stored(slot).unwrap().value.unwrap().value, 3);
+
+        // 被更大的 round 撤销后，所有者的 accept 不再生效
+        let slot = "\0mencius/log/5";
+        assert!(block_on(service.prepare(Request::new(proposer(slot, 5, 9, Some(0))))).is_ok());
+        assert!(block_on(service.accept(Request::new(proposer(slot, 0, 8, Some(4))))).is_ok());
+        assert_eq!(stored(slot).unwrap().value, None);
+    }
 }
@@ -0,0 +1,414 @@
+use crate::paxos::{Acceptor, EInstance, Instance};
+use anyhow::{Error, Result};
+use prost::Message;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// 日志文件名，位于数据目录下
+const JOURNAL_FILE: &str = "acceptor.log";
+/// 快照文件名，保存压缩时的全部状态
+const SNAPSHOT_FILE: &str = "acceptor.snapshot";
+/// Acceptor 已知的最新配置的 epoch
+const EPOCH_FILE: &str = "epoch";
+/// 日志中的记录数超过该值且远多于实例数时才压缩
+const COMPACT_MIN_RECORDS: usize = 10000;
+
+/// 写日志后的刷盘策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncMode {
+    /// 每次写入后 fsync，Acceptor 应答前保证落盘
+    #[default]
+    Always,
+    /// 只写入操作系统缓存，由操作系统决定刷盘时机
+    Never,
+}
+
+impl FromStr for FsyncMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "always" => Ok(FsyncMode::Always),
+            "never" => Ok(FsyncMode::Never),
+            _ => Err(Error::msg(format!("unknown fsync mode: {}", s))),
+        }
+    }
+}
+
+/// Acceptor 状态的追加写日志
+///
+/// 每次修改都以 [`Instance`] 记录追加到文件末尾，重启时先加载快照再按顺序重放日志，
+/// 同一个 key 以最后一条记录为准。EPaxos 实例的状态也记录在同一个日志中。日志过长时用 [`Journal::compact`] 写快照并清空日志。
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    snapshot: PathBuf,
+    epoch: PathBuf,
+    file: Mutex<File>,
+    fsync: FsyncMode,
+    /// 上次压缩以来日志中的记录数
+    records: AtomicUsize,
+}
+
+impl Journal {
+    /// 打开数据目录下的日志，返回日志以及重放得到的 Paxos 实例和按写入顺序排列的 EPaxos 实例记录
+    ///
+    /// 末尾只写了一半的记录会被截掉并落盘，之后的追加从最后一条完整记录之后开始。
+    pub fn open(
+        dir: &Path,
+        fsync: FsyncMode,
+    ) -> Result<(Self, HashMap<String, Acceptor>, Vec<EInstance>)> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(JOURNAL_FILE);
+        let snapshot = dir.join(SNAPSHOT_FILE);
+        let mut storage = HashMap::new();
+        let mut epaxos = vec![];
+        if snapshot.exists() {
+            let buf = std::fs::read(&snapshot)?;
+            let (_, good) = replay(&buf, &mut storage, &mut epaxos);
+            if good < buf.len() {
+                return Err(Error::msg(format!(
+                    "corrupted snapshot {}",
+                    snapshot.display()
+                )));
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)?;
+        let (records, good) = replay(&buf, &mut storage, &mut epaxos);
+        if good < buf.len() {
+            // 最后一条记录只写了一半，截掉后才能继续追加
+            warn!(
+                path = %path.display(),
+                offset = good,
+                dropped = buf.len() - good,
+                "truncating torn journal tail"
+            );
+            file.set_len(good as u64)?;
+            file.sync_all()?;
+        }
+
+        let journal = Journal {
+            path,
+            snapshot,
+            epoch: dir.join(EPOCH_FILE),
+            file: Mutex::new(file),
+            fsync,
+            records: AtomicUsize::new(records),
+        };
+        Ok((journal, storage, epaxos))
+    }
+
+    /// 追加一条 key 的最新状态
+    pub fn append(&self, key: &str, acceptor: &Acceptor) -> Result<()> {
+        self.write(Instance {
+            key: key.to_string(),
+            acceptor: Some(acceptor.clone()),
+            epaxos: None,
+        })
+    }
+
+    /// 追加一条删除记录
+    pub fn remove(&self, key: &str) -> Result<()> {
+        self.write(Instance {
+            key: key.to_string(),
+            acceptor: None,
+            epaxos: None,
+        })
+    }
+
+    /// 追加一个 EPaxos 实例的最新状态
+    pub fn append_epaxos(&self, instance: &EInstance) -> Result<()> {
+        self.write(epaxos_record(instance))
+    }
+
+    fn write(&self, inst: Instance) -> Result<()> {
+        let mut buf = vec![];
+        inst.encode_length_delimited(&mut buf)?;
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&buf)?;
+        if self.fsync == FsyncMode::Always {
+            file.sync_data()?;
+        }
+        self.records.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 日志中的记录是否远多于 live 个实例，值得压缩
+    pub fn should_compact(&self, live: usize) -> bool {
+        let records = self.records.load(Ordering::Relaxed);
+        records >= COMPACT_MIN_RECORDS && records > 2 * live
+    }
+
+    /// 把当前的全部状态写成快照，然后清空日志
+    ///
+    /// 调用方需保证期间没有其他写入。快照先写临时文件再改名，清空日志前崩溃时，
+    /// 重启会在新快照上再重放一遍旧日志，结果相同。
+    pub fn compact(&self, instances: impl IntoIterator<Item = Instance>) -> Result<()> {
+        let mut buf = vec![];
+        for inst in instances {
+            inst.encode_length_delimited(&mut buf)?;
+        }
+        write_atomic(&self.snapshot, &buf)?;
+
+        let file = self.file.lock().unwrap();
+        file.set_len(0)?;
+        file.sync_all()?;
+        self.records.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 保存的 epoch，没有保存过时为 0
+    pub fn epoch(&self) -> Result<i64> {
+        match std::fs::read_to_string(&self.epoch) {
+            Ok(s) => s.trim().parse().map_err(|e| {
+                Error::msg(format!(
+                    "invalid epoch file {}: {}",
+                    self.epoch.display(),
+                    e
+                ))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 保存新的 epoch，返回时已经落盘
+    pub fn set_epoch(&self, epoch: i64) -> Result<()> {
+        write_atomic(&self.epoch, epoch.to_string().as_bytes())
+    }
+
+    /// 日志和快照当前的总大小
+    pub fn size(&self) -> Result<u64> {
+        let file = self.file.lock().unwrap();
+        let snapshot = match std::fs::metadata(&self.snapshot) {
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        };
+        Ok(file.metadata()?.len() + snapshot)
+    }
+}
+
+/// 日志中记录 EPaxos 实例状态的一条记录
+pub(crate) fn epaxos_record(instance: &EInstance) -> Instance {
+    Instance {
+        key: String::new(),
+        acceptor: None,
+        epaxos: Some(instance.clone()),
+    }
+}
+
+/// 按顺序重放 buf 中的记录，返回完整记录的条数和最后一条完整记录之后的偏移
+fn replay(
+    buf: &[u8],
+    storage: &mut HashMap<String, Acceptor>,
+    epaxos: &mut Vec<EInstance>,
+) -> (usize, usize) {
+    let mut data = buf;
+    let mut records = 0;
+    let mut good = 0;
+    while !data.is_empty() {
+        match Instance::decode_length_delimited(&mut data) {
+            Ok(Instance {
+                epaxos: Some(instance),
+                ..
+            }) => epaxos.push(instance),
+            Ok(inst) => match inst.acceptor {
+                Some(acceptor) => {
+                    storage.insert(inst.key, acceptor);
+                }
+                None => {
+                    storage.remove(&inst.key);
+                }
+            },
+            Err(_) => break,
+        }
+        records += 1;
+        good = buf.len() - data.len();
+    }
+    (records, good)
+}
+
+/// 先写临时文件再改名，然后 fsync 所在目录，返回后断电也不会丢失或只剩一半
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    // 改名本身记录在目录里，目录不落盘时重启可能还是旧文件
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paxos::{EInstanceId, RoundNum, Value};
+
+    #[test]
+    fn test_journal_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let acc = Acceptor {
+            round: Some(RoundNum {
+                number: 2,
+                proposer_id: 1,
+            }),
+            last_round: Some(RoundNum {
+                number: 3,
+                proposer_id: 1,
+            }),
+            value: Some(Value {
+                value: 11,
+                ..Default::default()
+            }),
+        };
+        {
+            let (journal, storage, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            assert!(storage.is_empty());
+            journal.append("sh", &Acceptor::default()).unwrap();
+            journal.append("sh", &acc).unwrap();
+            journal.append("sw", &Acceptor::default()).unwrap();
+            journal.append("sz", &acc).unwrap();
+            journal.remove("sz").unwrap();
+        }
+
+        let (journal, storage, _) = Journal::open(dir.path(), FsyncMode::Never).unwrap();
+        assert!(journal.size().unwrap() > 0);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.get("sh"), Some(&acc));
+        assert_eq!(storage.get("sw"), Some(&Acceptor::default()));
+        assert_eq!(storage.get("sz"), None);
+    }
+
+    #[test]
+    fn test_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let acc = Acceptor {
+            round: Some(RoundNum {
+                number: 1,
+                proposer_id: 1,
+            }),
+            ..Default::default()
+        };
+        {
+            let (journal, _, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            journal.append("sh", &acc).unwrap();
+        }
+        // 模拟写到一半时崩溃
+        let mut buf = vec![];
+        Instance {
+            key: "sz".to_string(),
+            acceptor: Some(acc.clone()),
+            epaxos: None,
+        }
+        .encode_length_delimited(&mut buf)
+        .unwrap();
+        let path = dir.path().join(JOURNAL_FILE);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&buf[..buf.len() / 2]).unwrap();
+        drop(file);
+
+        {
+            let (journal, storage, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            assert_eq!(storage.len(), 1);
+            journal.append("sw", &acc).unwrap();
+        }
+        let (_, storage, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.get("sh"), Some(&acc));
+        assert_eq!(storage.get("sw"), Some(&acc));
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let acc = Acceptor::default();
+        {
+            let (journal, _, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            for _ in 0..10 {
+                journal.append("sh", &acc).unwrap();
+            }
+            journal.append("sz", &acc).unwrap();
+            let before = journal.size().unwrap();
+            journal
+                .compact(vec![Instance {
+                    key: "sh".to_string(),
+                    acceptor: Some(acc.clone()),
+                    epaxos: None,
+                }])
+                .unwrap();
+            assert!(journal.size().unwrap() < before);
+            assert_eq!(std::fs::metadata(journal.path()).unwrap().len(), 0);
+            journal.append("sw", &acc).unwrap();
+        }
+        let (_, storage, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+        assert_eq!(storage.len(), 2);
+        assert!(storage.contains_key("sh"));
+        assert!(storage.contains_key("sw"));
+    }
+
+    #[test]
+    fn test_epaxos_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance = |slot| EInstance {
+            id: Some(EInstanceId { replica: 1, slot }),
+            ..Default::default()
+        };
+        {
+            let (journal, _, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            journal.append_epaxos(&instance(1)).unwrap();
+            journal.append("sh", &Acceptor::default()).unwrap();
+            let sh = Instance {
+                key: "sh".to_string(),
+                acceptor: Some(Acceptor::default()),
+                epaxos: None,
+            };
+            journal
+                .compact(vec![sh, epaxos_record(&instance(1))])
+                .unwrap();
+            journal.append_epaxos(&instance(2)).unwrap();
+        }
+        // 快照和日志中的 EPaxos 记录都按写入顺序重放
+        let (_, storage, epaxos) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+        assert_eq!(storage.len(), 1);
+        assert_eq!(epaxos, vec![instance(1), instance(2)]);
+    }
+
+    #[test]
+    fn test_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let (journal, _, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+            assert_eq!(journal.epoch().unwrap(), 0);
+            journal.set_epoch(3).unwrap();
+        }
+        let (journal, _, _) = Journal::open(dir.path(), FsyncMode::Always).unwrap();
+        assert_eq!(journal.epoch().unwrap(), 3);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::auth;
+use crate::error::ProposeError;
+use crate::paxos::stream_request::Op;
+use crate::trace;
+use crate::{Acceptor, PaxosClient, Proposer, StreamReply, StreamRequest};
+use anyhow::Result;
+use futures::channel::mpsc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::oneshot;
+use tonic::transport::Channel;
+use tonic::{Request, Streaming};
+use tracing::{debug, warn};
+
+type Waiters = HashMap<u64, oneshot::Sender<Result<Acceptor, ProposeError>>>;
+
+/// 与一个 Acceptor 之间的双向流，可以同时有多个未完成的请求
+///
+/// 所有克隆都被丢弃后流随之关闭。
+#[derive(Debug, Clone)]
+pub(crate) struct AcceptorStream {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    tx: mpsc::UnboundedSender<StreamRequest>,
+    /// 等待应答的请求，None 表示流已关闭
+    waiters: Mutex<Option<Waiters>>,
+    next_tag: AtomicU64,
+}
+
+impl AcceptorStream {
+    pub(crate) async fn open(
+        mut client: PaxosClient<Channel>,
+        token: Option<&str>,
+    ) -> Result<Self> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut request = Request::new(rx);
+        if let Some(token) = token {
+            auth::bearer(&mut request, token);
+        }
+        let replies = client.stream(request).await?.into_inner();
+        let inner = Arc::new(Inner {
+            tx,
+            waiters: Mutex::new(Some(HashMap::new())),
+            next_tag: AtomicU64::new(0),
+        });
+        tokio::spawn(dispatch(replies, Arc::downgrade(&inner)));
+        Ok(AcceptorStream { inner })
+    }
+
+    /// Acceptor 断开或流出错后，流不再可用
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner.waiters.lock().unwrap().is_none()
+    }
+
+    async fn call(&self, op: Op, trace_id: String) -> Result<Acceptor, ProposeError> {
+        let tag = self.inner.next_tag.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.inner.waiters.lock().unwrap().as_mut() {
+            Some(waiters) => waiters.insert(tag, tx),
+            None => return Err(closed()),
+        };
+        // 超时或被取消时 future 被丢弃，不再等待的请求随之移除
+        let _pending = Pending {
+            inner: &self.inner,
+            tag,
+        };
+        let request = StreamRequest {
+            tag,
+            op: Some(op),
+            trace_id,
+        };
+        if self.inner.tx.unbounded_send(request).is_err() {
+            return Err(closed());
+        }
+        rx.await.map_err(|_| closed())?
+    }
+}
+
+/// 离开作用域时从等待表中移除 tag，已收到应答时移除不会有影响
+struct Pending<'a> {
+    inner: &'a Inner,
+    tag: u64,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(waiters) = self.inner.waiters.lock().unwrap().as_mut() {
+            waiters.remove(&self.tag);
+        }
+    }
+}
+
+fn closed() -> ProposeError {
+    ProposeError::Transport("stream closed".to_string())
+}
+
+/// 按 tag 把应答交给等待的请求；流结束后让所有等待的请求失败
+async fn dispatch(mut replies: Streaming<StreamReply>, inner: Weak<Inner>) {
+    loop {
+        let reply = replies.message().await;
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut waiters = inner.waiters.lock().unwrap();
+        match reply {
+            Ok(Some(reply)) => {
+                let waiter = waiters.as_mut().and_then(|w| w.remove(&reply.tag));
+                if let Some(waiter) = waiter {
+                    let result = if !reply.error.is_empty() {
+                        Err(ProposeError::from_message(reply.error))
+                    } else {
+                        reply.acceptor.ok_or_else(|| {
+                            ProposeError::Transport("empty stream reply".to_string())
+                        })
+                    };
+                    let _ = waiter.send(result);
+                } else {
+                    debug!(tag = reply.tag, "stream reply without waiter");
+                }
+            }
+            Ok(None) => {
+                debug!("stream closed by acceptor");
+                waiters.take();
+                return;
+            }
+            Err(e) => {
+                warn!(error = %e, "stream failed");
+                waiters.take();
+                return;
+            }
+        }
+    }
+}
+
+/// 发送 Prepare/Accept 的方式：每次一个 unary 调用，或者共用一条双向流
+#[derive(Debug, Clone)]
+pub(crate) enum Transport {
+    Unary(PaxosClient<Channel>),
+    Stream(AcceptorStream),
+}
+
+impl Transport {
+    pub(crate) async fn prepare(
+        &mut self,
+        request: Request<Proposer>,
+    ) -> Result<Acceptor, ProposeError> {
+        match self {
+            Transport::Unary(client) => Ok(client.prepare(request).await?.into_inner()),
+            Transport::Stream(stream) => {
+                let trace_id = trace::extract(&request);
+                stream
+                    .call(Op::Prepare(request.into_inner()), trace_id)
+                    .await
+            }
+        }
+    }
+
+    pub(crate) async fn accept(
+        &mut self,
+        request: Request<Proposer>,
+    ) -> Result<Acceptor, ProposeError> {
+        match self {
+            Transport::Unary(client) => Ok(client.accept(request).await?.into_inner()),
+            Transport::Stream(stream) => {
+                let trace_id = trace::extract(&request);
+                stream
+                    .call(Op::Accept(request.into_inner()), trace_id)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_timed_out_call_is_forgotten() {
+        // Acceptor 永远不应答
+        let (tx, _rx) = mpsc::unbounded();
+        let stream = AcceptorStream {
+            inner: Arc::new(Inner {
+                tx,
+                waiters: Mutex::new(Some(HashMap::new())),
+                next_tag: AtomicU64::new(0),
+            }),
+        };
+        let call = stream.call(Op::Prepare(Proposer::default()), String::new());
+        assert!(tokio::time::timeout(Duration::from_millis(10), call)
+            .await
+            .is_err());
+        let waiters = stream.inner.waiters.lock().unwrap();
+        assert!(waiters.as_ref().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+//! 各模块测试共用的辅助函数
+use crate::{EpaxosServer, PaxosServer, PaxosService, PeerServer, Value};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tonic::transport::Server;
+use triggered::{Listener, Trigger};
+
+/// 在单独的线程和 runtime 中运行 serve，返回用于停止它的 Trigger
+pub(crate) fn spawn<F, Fut>(address: String, serve: F) -> Trigger
+where
+    F: FnOnce(SocketAddr, Listener) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), tonic::transport::Error>>,
+{
+    let (trigger, signal) = triggered::trigger();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let addr = address.parse().unwrap();
+        let _ = rt.block_on(serve(addr, signal));
+    });
+    std::thread::sleep(Duration::from_millis(10));
+    trigger
+}
+
+/// 在 address 上提供 service 的 Paxos、EPaxos 和 Peer 服务，不开启认证
+pub(crate) fn start_server(address: String, service: PaxosService) -> Trigger {
+    spawn(address, move |addr, signal| {
+        Server::builder()
+            .add_service(EpaxosServer::new(service.epaxos()))
+            .add_service(PeerServer::new(service.peer()))
+            .add_service(PaxosServer::new(service))
+            .serve_with_shutdown(addr, signal)
+    })
+}
+
+pub(crate) fn value(v: i64) -> Value {
+    Value {
+        value: v,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Error, Result};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig};
+
+/// TLS 配置，证书和私钥均为 PEM 文件路径
+///
+/// ```toml
+/// [tls]
+/// cert = "node1.pem"
+/// key = "node1.key"
+/// ca = "ca.pem"
+/// client_ca = "ca.pem"
+/// domain = "rpaxos.internal"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// 本节点的证书，服务端必须设置；客户端设置时用于双向认证
+    pub cert: Option<PathBuf>,
+    /// 与 cert 对应的私钥
+    pub key: Option<PathBuf>,
+    /// 校验服务端证书的 CA
+    pub ca: Option<PathBuf>,
+    /// 校验客户端证书的 CA，设置后服务端要求客户端出示证书
+    pub client_ca: Option<PathBuf>,
+    /// 校验服务端证书时使用的域名，默认取连接地址中的主机名
+    pub domain: Option<String>,
+}
+
+impl TlsConfig {
+    fn identity(&self) -> Result<Option<Identity>> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Ok(Some(Identity::from_pem(read(cert)?, read(key)?))),
+            (None, None) => Ok(None),
+            _ => Err(Error::msg("tls cert and key must be set together")),
+        }
+    }
+
+    /// 服务端的 TLS 配置
+    pub fn server_config(&self) -> Result<ServerTlsConfig> {
+        let identity = self
+            .identity()?
+            .ok_or_else(|| Error::msg("tls cert and key are required by the server"))?;
+        let mut config = ServerTlsConfig::new().identity(identity);
+        if let Some(ca) = &self.client_ca {
+            config = config.client_ca_root(Certificate::from_pem(read(ca)?));
+        }
+        Ok(config)
+    }
+
+    /// 连接其他节点时的 TLS 配置
+    pub fn client_config(&self) -> Result<ClientTlsConfig> {
+        let mut config = ClientTlsConfig::new();
+        if let Some(ca) = &self.ca {
+            config = config.ca_certificate(Certificate::from_pem(read(ca)?));
+        }
+        if let Some(identity) = self.identity()? {
+            config = config.identity(identity);
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain.clone());
+        }
+        Ok(config)
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| Error::msg(format!("read {}: {}", path.display(), e)))
+}
+
+/// 节点地址对应的 [`Endpoint`]，设置了 TLS 时使用 https
+pub fn endpoint(server: &str, tls: Option<&ClientTlsConfig>) -> Result<Endpoint> {
+    match tls {
+        Some(tls) => {
+            let dst = Endpoint::try_from(format!("https://{}", server))?;
+            Ok(dst.tls_config(tls.clone())?)
+        }
+        None => Ok(Endpoint::try_from(format!("http://{}", server))?),
+    }
+}
+
+/// 测试用的自签名 CA，以及由它签发的服务端和客户端证书
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::TlsConfig;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+    use std::path::Path;
+
+    fn issue(ca: &Certificate, name: &str, dir: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = Certificate::from_params(params).unwrap();
+        let cert_path = dir.join(format!("{}.pem", name));
+        let key_path = dir.join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert.serialize_pem_with_signer(ca).unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    /// 在 dir 下生成证书，返回服务端和名为 client_name 的客户端配置
+    pub(crate) fn generate(dir: &Path, client_name: &str) -> (TlsConfig, TlsConfig) {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "rpaxos test ca");
+        let ca = Certificate::from_params(params).unwrap();
+        let ca_path = dir.join("ca.pem");
+        std::fs::write(&ca_path, ca.serialize_pem().unwrap()).unwrap();
+
+        let (server_cert, server_key) = issue(&ca, "server", dir);
+        let (client_cert, client_key) = issue(&ca, client_name, dir);
+        let server = TlsConfig {
+            cert: Some(server_cert),
+            key: Some(server_key),
+            client_ca: Some(ca_path.clone()),
+            ..Default::default()
+        };
+        let client = TlsConfig {
+            cert: Some(client_cert),
+            key: Some(client_key),
+            ca: Some(ca_path),
+            domain: Some("localhost".to_string()),
+            ..Default::default()
+        };
+        (server, client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, client) = testing::generate(dir.path(), "client");
+        assert!(server.server_config().is_ok());
+        assert!(client.client_config().is_ok());
+        // 客户端配置没有 client_ca 不影响，但服务端必须有证书
+        assert!(TlsConfig::default().server_config().is_err());
+        let half = TlsConfig {
+            cert: server.cert.clone(),
+            ..Default::default()
+        };
+        assert!(half.client_config().is_err());
+
+        let dst = endpoint("localhost:11030", None).unwrap();
+        assert_eq!(dst.uri().scheme_str(), Some("http"));
+        let tls = client.client_config().unwrap();
+        let dst = endpoint("localhost:11030", Some(&tls)).unwrap();
+        assert_eq!(dst.uri().scheme_str(), Some("https"));
+    }
+}
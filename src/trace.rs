@@ -0,0 +1,42 @@
+use tonic::metadata::MetadataValue;
+use tonic::Request;
+
+/// 在 gRPC metadata 中传递 trace id 的 header
+pub const TRACE_ID_HEADER: &str = "x-rpaxos-trace-id";
+
+/// 生成一个新的 trace id，16 位十六进制
+pub fn new_trace_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// 把 trace id 写入请求的 metadata
+pub fn inject<T>(request: &mut Request<T>, trace_id: &str) {
+    if let Ok(value) = MetadataValue::from_str(trace_id) {
+        request.metadata_mut().insert(TRACE_ID_HEADER, value);
+    }
+}
+
+/// 从请求的 metadata 中读取 trace id，没有时返回空串
+pub fn extract<T>(request: &Request<T>) -> String {
+    request
+        .metadata()
+        .get(TRACE_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_id_roundtrip() {
+        let id = new_trace_id();
+        assert_eq!(id.len(), 16);
+        let mut request = Request::new(());
+        assert_eq!(extract(&request), "");
+        inject(&mut request, &id);
+        assert_eq!(extract(&request), id);
+    }
+}
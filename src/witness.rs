@@ -0,0 +1,143 @@
+use crate::error::ProposeError;
+use crate::{Acceptor, Value};
+use prost::Message;
+
+/// 值的摘要，使用 FNV-1a，不同版本之间保持稳定，可以持久化
+pub(crate) fn digest(value: &Value) -> u64 {
+    if value.digest != 0 {
+        return value.digest;
+    }
+    let mut buf = vec![];
+    // 写入 Vec 不会失败
+    let _ = value.encode(&mut buf);
+    // 0 表示完整的值
+    fnv1a(&buf).max(1)
+}
+
+/// FNV-1a，不同版本之间保持稳定
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// 见证者保存的形式：只有摘要
+pub(crate) fn strip(value: Value) -> Value {
+    Value {
+        digest: digest(&value),
+        ..Default::default()
+    }
+}
+
+/// 把见证者的摘要换成 replies 中完整的值，value 本身完整时直接返回
+pub(crate) fn restore(value: Value, replies: &[(usize, Acceptor)]) -> Result<Value, ProposeError> {
+    if value.digest == 0 {
+        return Ok(value);
+    }
+    replies
+        .iter()
+        .filter_map(|(_, acc)| acc.value.as_ref())
+        .find(|v| v.digest == 0 && digest(v) == value.digest)
+        .cloned()
+        .ok_or(ProposeError::ValueUnavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{start_server, value};
+    use crate::*;
+    use scopeguard::defer;
+
+    #[test]
+    fn test_restore() {
+        let full = (
+            0,
+            Acceptor {
+                value: Some(value(7)),
+                ..Default::default()
+            },
+        );
+        let witness = (
+            2,
+            Acceptor {
+                value: Some(strip(value(7))),
+                ..Default::default()
+            },
+        );
+        assert_ne!(digest(&value(7)), digest(&value(8)));
+        let stripped = strip(value(7));
+        assert_eq!(
+            restore(stripped.clone(), &[witness.clone(), full]),
+            Ok(value(7))
+        );
+        assert_eq!(
+            restore(stripped, &[witness]),
+            Err(ProposeError::ValueUnavailable)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+    async fn test_witness() {
+        let servers: Vec<_> = (11150..11153).map(|p| format!("[::1]:{}", p)).collect();
+        let nodes: Vec<_> = servers
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, s)| {
+                let mut service = PaxosService::default();
+                service.set_witness(i == 2);
+                (start_server(s, service.clone()), service)
+            })
+            .collect();
+        defer! {
+            for (t, _) in &nodes {
+                t.trigger();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let stored = |i: usize, key: &str| {
+            let storage = nodes[i].1.storage.lock().unwrap();
+            storage.get(key).and_then(|acc| acc.value.clone())
+        };
+
+        // 两个完整的 Acceptor 都在时不使用见证者
+        let mut client = Client::new(servers.clone(), 33);
+        client.set_witnesses(vec![2]);
+        assert!(client.connect().await.is_ok());
+        let chosen = client.run_propose("a".to_string(), Some(value(1))).await;
+        assert_eq!(chosen, Ok(Some(value(1))));
+        assert_eq!(stored(2, "a"), None);
+
+        // 批量提议同样不发给见证者，同一批次中重复的 key 只提议一次
+        let mut client = Client::new(servers.clone(), 35);
+        client.set_witnesses(vec![2]);
+        client.set_batch_window(std::time::Duration::from_millis(50));
+        assert!(client.connect().await.is_ok());
+        let (first, second) = futures::join!(
+            client.run_propose("c".to_string(), Some(value(4))),
+            client.run_propose("c".to_string(), Some(value(5)))
+        );
+        assert_eq!(first, Ok(Some(value(4))));
+        assert_eq!(second, Ok(Some(value(4))));
+        assert_eq!(stored(0, "c"), Some(value(4)));
+        assert_eq!(stored(2, "c"), None);
+        let metrics = client.metrics();
+        assert_eq!(metrics.proposals.with_label_values(&["chosen"]).get(), 1);
+
+        // 一个完整的 Acceptor 不可用时由见证者补足 quorum，见证者只保存摘要
+        nodes[1].0.trigger();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut client = Client::new(servers.clone(), 34);
+        client.set_witnesses(vec![2]);
+        assert!(client.connect().await.is_ok());
+        let chosen = client.run_propose("b".to_string(), Some(value(2))).await;
+        assert_eq!(chosen, Ok(Some(value(2))));
+        assert_eq!(stored(0, "b"), Some(value(2)));
+        assert_eq!(stored(2, "b"), Some(strip(value(2))));
+
+        // 修复时从完整的 Acceptor 取回见证者摘要对应的值
+        let prop = client.new_propose("b".to_string(), Some(value(3))).unwrap();
+        assert_eq!(prop.decide().await, Ok(Some(value(2))));
+    }
+}